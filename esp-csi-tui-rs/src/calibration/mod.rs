@@ -0,0 +1,31 @@
+pub mod profile;
+
+pub use profile::BaselineProfile;
+
+use std::time::{Duration, Instant};
+
+/// A guided empty-room recording in progress.
+///
+/// The session only tracks where the recording started, as a position in the
+/// device's measurements; they keep flowing into its buffer as usual and the profile
+/// is computed from the frames since then once the duration has elapsed.
+#[derive(Debug, Clone)]
+pub struct CalibrationSession {
+    pub start_index: usize,
+    pub duration: Duration,
+    started: Instant,
+}
+
+impl CalibrationSession {
+    pub fn new(start_index: usize, duration: Duration) -> Self {
+        Self { start_index, duration, started: Instant::now() }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.started.elapsed())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.started.elapsed() >= self.duration
+    }
+}
@@ -0,0 +1,225 @@
+use crate::detection::motion::MotionBaseline;
+use crate::models::CsiMeasurement;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Per-subcarrier amplitude statistics of an empty environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineProfile {
+    /// Device the profile was recorded with (serial port until devices report an ID).
+    pub device: String,
+    pub channel: u8,
+    pub bandwidth: u16,
+    pub recorded_at: DateTime<Utc>,
+    pub duration_s: f32,
+    pub num_measurements: usize,
+    pub mean_rssi: f32,
+    pub mean: Vec<f32>,
+    pub variance: Vec<f32>,
+    /// Amplitude covariance between subcarriers, row-major `[i][j]`.
+    pub covariance: Vec<Vec<f32>>,
+    pub motion: MotionBaseline,
+}
+
+/// How a window of live CSI differs from a baseline profile.
+#[derive(Debug, Clone)]
+pub struct BaselineComparison {
+    /// Mean absolute z-score of the window's mean amplitude against the baseline.
+    pub mean_abs_z: f32,
+    /// Subcarrier with the largest absolute z-score, and that score.
+    pub max_z: (usize, f32),
+    /// Mean ratio of window variance to baseline variance over subcarriers.
+    pub variance_ratio: f32,
+    /// Normalised inner product of the window and baseline covariance matrices, 1.0 when
+    /// the subcarriers co-vary the same way as in the empty room.
+    pub covariance_similarity: f32,
+}
+
+impl BaselineProfile {
+    pub fn compute(
+        device: &str,
+        measurements: &[CsiMeasurement],
+        motion_window: usize,
+    ) -> Result<Self> {
+        let latest = measurements.last().ok_or_else(|| anyhow!("No measurements recorded"))?;
+        let frames = amplitude_frames(measurements, latest.subcarrier_data.len());
+        if frames.len() < motion_window.max(2) {
+            return Err(anyhow!(
+                "Need at least {} measurements for a baseline, have {}",
+                motion_window.max(2),
+                frames.len()
+            ));
+        }
+
+        let (mean, covariance) = mean_and_covariance(&frames);
+        let variance = (0..mean.len()).map(|i| covariance[i][i]).collect();
+        let motion = MotionBaseline::estimate(measurements, motion_window)
+            .ok_or_else(|| anyhow!("Baseline window has no usable CSI"))?;
+
+        let first = measurements[0].timestamp;
+        Ok(Self {
+            device: device.to_string(),
+            channel: latest.channel,
+            bandwidth: latest.bandwidth,
+            recorded_at: Utc::now(),
+            duration_s: (latest.timestamp - first).num_milliseconds() as f32 / 1000.0,
+            num_measurements: frames.len(),
+            mean_rssi: measurements.iter().map(|m| m.rssi as f32).sum::<f32>() / measurements.len() as f32,
+            mean,
+            variance,
+            covariance,
+            motion,
+        })
+    }
+
+    pub fn compare(&self, measurements: &[CsiMeasurement]) -> Option<BaselineComparison> {
+        let frames = amplitude_frames(measurements, self.mean.len());
+        if frames.len() < 2 {
+            return None;
+        }
+        let (mean, covariance) = mean_and_covariance(&frames);
+
+        let z: Vec<f32> = mean
+            .iter()
+            .zip(&self.mean)
+            .zip(&self.variance)
+            .map(|((m, base), var)| (m - base) / var.sqrt().max(f32::EPSILON))
+            .collect();
+        let max_z = z
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .map(|(i, v)| (i, *v))?;
+
+        let variance_ratio = (0..mean.len())
+            .map(|i| covariance[i][i] / self.variance[i].max(f32::EPSILON))
+            .sum::<f32>()
+            / mean.len() as f32;
+
+        let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+        for (row_a, row_b) in covariance.iter().zip(&self.covariance) {
+            for (a, b) in row_a.iter().zip(row_b) {
+                dot += a * b;
+                norm_a += a * a;
+                norm_b += b * b;
+            }
+        }
+
+        Some(BaselineComparison {
+            mean_abs_z: z.iter().map(|v| v.abs()).sum::<f32>() / z.len() as f32,
+            max_z,
+            variance_ratio,
+            covariance_similarity: dot / (norm_a * norm_b).sqrt().max(f32::EPSILON),
+        })
+    }
+}
+
+fn amplitude_frames(measurements: &[CsiMeasurement], num_subcarriers: usize) -> Vec<Vec<f32>> {
+    measurements
+        .iter()
+        .filter(|m| m.subcarrier_data.len() == num_subcarriers && num_subcarriers > 0)
+        .map(|m| m.subcarrier_data.iter().map(|c| c.magnitude()).collect())
+        .collect()
+}
+
+fn mean_and_covariance(frames: &[Vec<f32>]) -> (Vec<f32>, Vec<Vec<f32>>) {
+    let n = frames[0].len();
+    let count = frames.len() as f32;
+
+    let mut mean = vec![0.0f32; n];
+    for frame in frames {
+        for (acc, x) in mean.iter_mut().zip(frame) {
+            *acc += x;
+        }
+    }
+    mean.iter_mut().for_each(|m| *m /= count);
+
+    let mut covariance = vec![vec![0.0f32; n]; n];
+    for frame in frames {
+        let centred: Vec<f32> = frame.iter().zip(&mean).map(|(x, m)| x - m).collect();
+        for (row, di) in covariance.iter_mut().zip(&centred) {
+            for (cell, dj) in row.iter_mut().zip(&centred) {
+                *cell += di * dj;
+            }
+        }
+    }
+    covariance.iter_mut().flatten().for_each(|c| *c /= count - 1.0);
+
+    (mean, covariance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ComplexNumber;
+
+    /// Subcarrier `k` alternates between `means[k] - 1` and `means[k] + 1`, plus `shift[k]`.
+    fn frames(count: usize, means: &[f32], shift: &[f32]) -> Vec<CsiMeasurement> {
+        let start = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        (0..count)
+            .map(|i| {
+                let swing = if i % 2 == 0 { -1.0 } else { 1.0 };
+                CsiMeasurement {
+                    timestamp: start + chrono::Duration::milliseconds(i as i64 * 100),
+                    channel: 6,
+                    bandwidth: 20,
+                    rssi: -40 - (i % 2) as i8 * 2,
+                    noise_floor: -90,
+                    subcarrier_data: means
+                        .iter()
+                        .zip(shift)
+                        .map(|(m, s)| ComplexNumber::new(0.0, m + s + swing))
+                        .collect(),
+                    sequence: None,
+                    device_timestamp_us: None,
+                    device_id: None,
+                    transmitter: None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn profile_holds_per_subcarrier_statistics() {
+        let profile = BaselineProfile::compute("esp-1", &frames(100, &[10.0, 20.0, 30.0], &[0.0; 3]), 10).unwrap();
+
+        assert_eq!((profile.channel, profile.bandwidth, profile.num_measurements), (6, 20, 100));
+        assert!((profile.duration_s - 9.9).abs() < 1e-4);
+        assert_eq!(profile.mean_rssi, -41.0);
+        for (mean, expected) in profile.mean.iter().zip([10.0, 20.0, 30.0]) {
+            assert!((mean - expected).abs() < 1e-4);
+        }
+        // Sample variance of +-1 over 100 frames, with every pair of subcarriers co-varying fully.
+        for row in &profile.covariance {
+            for cell in row {
+                assert!((cell - 100.0 / 99.0).abs() < 1e-4);
+            }
+        }
+        assert_eq!(profile.variance, profile.covariance.iter().enumerate().map(|(i, r)| r[i]).collect::<Vec<_>>());
+
+        assert!(BaselineProfile::compute("esp-1", &frames(5, &[10.0], &[0.0]), 10).is_err());
+    }
+
+    #[test]
+    fn deviation_scores_against_a_known_baseline() {
+        let means = [10.0, 20.0, 30.0, 40.0];
+        let profile = BaselineProfile::compute("esp-1", &frames(100, &means, &[0.0; 4]), 10).unwrap();
+        let sigma = (100.0f32 / 99.0).sqrt();
+
+        let same = profile.compare(&frames(50, &means, &[0.0; 4])).unwrap();
+        assert!(same.mean_abs_z < 1e-3);
+        assert!((same.covariance_similarity - 1.0).abs() < 1e-4);
+
+        // Shift every subcarrier by two baseline deviations, and subcarrier 2 by four.
+        let shift = [2.0 * sigma, 2.0 * sigma, 4.0 * sigma, 2.0 * sigma];
+        let moved = profile.compare(&frames(50, &means, &shift)).unwrap();
+        assert!((moved.mean_abs_z - 2.5).abs() < 1e-3, "mean |z| {}", moved.mean_abs_z);
+        assert_eq!(moved.max_z.0, 2);
+        assert!((moved.max_z.1 - 4.0).abs() < 1e-3);
+        // 50 frames of +-1 against 100: 50/49 over 100/99.
+        assert!((moved.variance_ratio - (50.0 / 49.0) / (100.0 / 99.0)).abs() < 1e-4);
+
+        assert!(profile.compare(&frames(1, &means, &[0.0; 4])).is_none());
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+
+/// When one timed capture ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopCondition {
+    Duration(Duration),
+    /// Frames received from all devices together.
+    Measurements(usize),
+}
+
+/// A bounded capture, optionally delayed and repeated, for unattended experiments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturePlan {
+    pub stop: StopCondition,
+    /// Captures to run; `None` repeats until cancelled.
+    pub repeat: Option<u32>,
+    /// Time from the start of one capture to the start of the next. A capture that
+    /// runs longer is followed by the next one straight away.
+    pub every: Duration,
+    /// First start.
+    pub start_at: DateTime<Utc>,
+}
+
+impl CapturePlan {
+    /// Parses `duration=<d> | frames=<n>` with optional `repeat=<n>|forever`,
+    /// `every=<d>` and `delay=<d>` or `at=<HH:MM>`; durations are `<n>s`, `<n>m` or
+    /// `<n>h`.
+    pub fn parse(args: &str, now: DateTime<Utc>) -> Result<Self> {
+        let mut stop = None;
+        let mut repeat = Some(1);
+        let mut every = Duration::zero();
+        let mut start_at = now;
+        for arg in args.split_whitespace() {
+            let (key, value) = arg
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value, got '{}'", arg))?;
+            match key {
+                "duration" => stop = Some(StopCondition::Duration(parse_duration(value)?)),
+                "frames" => {
+                    let count: usize = value.parse().map_err(|e| anyhow!("Invalid frames '{}': {}", value, e))?;
+                    stop = Some(StopCondition::Measurements(count.max(1)));
+                }
+                "repeat" if value == "forever" => repeat = None,
+                "repeat" => {
+                    let count: u32 = value.parse().map_err(|e| anyhow!("Invalid repeat '{}': {}", value, e))?;
+                    repeat = Some(count.max(1));
+                }
+                "every" => every = parse_duration(value)?,
+                "delay" => start_at = now + parse_duration(value)?,
+                "at" => start_at = next_local_time(value, now)?,
+                _ => return Err(anyhow!("Unknown capture setting '{}'", key)),
+            }
+        }
+        let stop = stop.ok_or_else(|| anyhow!("A capture needs duration=<d> or frames=<n>"))?;
+        Ok(Self { stop, repeat, every, start_at })
+    }
+}
+
+fn parse_duration(text: &str) -> Result<Duration> {
+    let invalid = || anyhow!("Invalid duration '{}' (e.g. 90s, 5m, 2h)", text);
+    let split = text.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let value: i64 = text[..split].parse().map_err(|_| invalid())?;
+    match &text[split..] {
+        "s" => Ok(Duration::seconds(value)),
+        "m" => Ok(Duration::minutes(value)),
+        "h" => Ok(Duration::hours(value)),
+        _ => Err(invalid()),
+    }
+}
+
+/// The next time the local clock reads `HH:MM`.
+fn next_local_time(text: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let time = NaiveTime::parse_from_str(text, "%H:%M").map_err(|_| anyhow!("Invalid time '{}' (HH:MM)", text))?;
+    let local = now.with_timezone(&Local);
+    let today = Local
+        .from_local_datetime(&local.date_naive().and_time(time))
+        .earliest()
+        .ok_or_else(|| anyhow!("{} does not exist today", text))?;
+    let start = if today > local { today } else { today + Duration::days(1) };
+    Ok(start.with_timezone(&Utc))
+}
+
+/// What the schedule wants done now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureAction {
+    Start,
+    /// Stop collecting and save capture number `index` (from 1).
+    StopAndSave { index: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Waiting { until: DateTime<Utc> },
+    Capturing { started: DateTime<Utc>, first_measurement: usize },
+    Done,
+}
+
+/// Runs a [`CapturePlan`]: driven by [`CaptureSchedule::update`] with the clock and
+/// the running measurement count, it says when to start and when to stop and save.
+#[derive(Debug, Clone)]
+pub struct CaptureSchedule {
+    pub plan: CapturePlan,
+    phase: Phase,
+    completed: u32,
+}
+
+impl CaptureSchedule {
+    pub fn new(plan: CapturePlan) -> Self {
+        let phase = Phase::Waiting { until: plan.start_at };
+        Self { plan, phase, completed: 0 }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.phase == Phase::Done
+    }
+
+    pub fn completed(&self) -> u32 {
+        self.completed
+    }
+
+    /// Index of the first measurement of the running capture.
+    pub fn first_measurement(&self) -> Option<usize> {
+        match self.phase {
+            Phase::Capturing { first_measurement, .. } => Some(first_measurement),
+            _ => None,
+        }
+    }
+
+    pub fn update(&mut self, now: DateTime<Utc>, measurements: usize) -> Option<CaptureAction> {
+        match self.phase {
+            Phase::Waiting { until } if now >= until => {
+                self.phase = Phase::Capturing { started: now, first_measurement: measurements };
+                Some(CaptureAction::Start)
+            }
+            Phase::Capturing { started, first_measurement } => {
+                let finished = match self.plan.stop {
+                    StopCondition::Duration(duration) => now - started >= duration,
+                    StopCondition::Measurements(count) => measurements - first_measurement >= count,
+                };
+                if !finished {
+                    return None;
+                }
+                self.completed += 1;
+                self.phase = if self.plan.repeat.is_some_and(|repeat| self.completed >= repeat) {
+                    Phase::Done
+                } else {
+                    Phase::Waiting { until: (started + self.plan.every).max(now) }
+                };
+                Some(CaptureAction::StopAndSave { index: self.completed })
+            }
+            _ => None,
+        }
+    }
+
+    /// Header text: the running capture's remaining time or frames, or the time to
+    /// the next one.
+    pub fn countdown(&self, now: DateTime<Utc>, measurements: usize) -> String {
+        let run = match self.plan.repeat {
+            Some(repeat) => format!("{}/{}", (self.completed + 1).min(repeat), repeat),
+            None => format!("{}", self.completed + 1),
+        };
+        match self.phase {
+            Phase::Waiting { until } => format!("Capture {} in {}", run, format_duration(until - now)),
+            Phase::Capturing { started, first_measurement } => match self.plan.stop {
+                StopCondition::Duration(duration) => {
+                    format!("Capture {}: {} left", run, format_duration(duration - (now - started)))
+                }
+                StopCondition::Measurements(count) => {
+                    format!("Capture {}: {}/{} frames", run, measurements - first_measurement, count)
+                }
+            },
+            Phase::Done => format!("{} capture(s) done", self.completed),
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_plans() {
+        let plan = CapturePlan::parse("duration=90s repeat=3 every=10m delay=1m", at(0)).unwrap();
+        assert_eq!(plan.stop, StopCondition::Duration(Duration::seconds(90)));
+        assert_eq!((plan.repeat, plan.every, plan.start_at), (Some(3), Duration::minutes(10), at(60)));
+
+        let plan = CapturePlan::parse("frames=500 repeat=forever", at(0)).unwrap();
+        assert_eq!((plan.stop, plan.repeat), (StopCondition::Measurements(500), None));
+
+        assert!(CapturePlan::parse("repeat=2", at(0)).is_err());
+        assert!(CapturePlan::parse("duration=5x", at(0)).is_err());
+        assert!(CapturePlan::parse("at=25:00 duration=1m", at(0)).is_err());
+    }
+
+    #[test]
+    fn repeats_on_schedule_and_stops() {
+        let plan = CapturePlan::parse("duration=30s repeat=2 every=2m", at(0)).unwrap();
+        let mut schedule = CaptureSchedule::new(plan);
+        assert_eq!(schedule.update(at(0), 0), Some(CaptureAction::Start));
+        assert_eq!(schedule.update(at(29), 290), None);
+        assert_eq!(schedule.countdown(at(29), 290), "Capture 1/2: 00:01 left");
+        assert_eq!(schedule.update(at(30), 300), Some(CaptureAction::StopAndSave { index: 1 }));
+        assert_eq!(schedule.update(at(100), 300), None);
+        assert_eq!(schedule.countdown(at(100), 300), "Capture 2/2 in 00:20");
+        assert_eq!(schedule.update(at(120), 300), Some(CaptureAction::Start));
+        assert_eq!(schedule.first_measurement(), Some(300));
+        assert_eq!(schedule.update(at(150), 600), Some(CaptureAction::StopAndSave { index: 2 }));
+        assert!(schedule.is_done());
+    }
+
+    #[test]
+    fn frame_count_stops_capture() {
+        let plan = CapturePlan::parse("frames=100", at(0)).unwrap();
+        let mut schedule = CaptureSchedule::new(plan);
+        assert_eq!(schedule.update(at(0), 40), Some(CaptureAction::Start));
+        assert_eq!(schedule.update(at(5), 139), None);
+        assert_eq!(schedule.update(at(6), 140), Some(CaptureAction::StopAndSave { index: 1 }));
+        assert!(schedule.is_done());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::classification::features::{FeatureConfig, FeatureExtractor};
+use crate::labelling::LabelInterval;
+use crate::models::CsiMeasurement;
+use crate::storage::label_storage::LabelStorage;
+use crate::storage::rrd_storage::RrdStorage;
+use anyhow::{Result, anyhow};
+use std::path::Path;
+
+/// Directory of training recordings: either one subdirectory per activity
+/// (`training/<label>/*.rrd`) or recordings with label intervals alongside
+/// (`training/*.rrd` + `*.labels.json`).
+pub const TRAINING_DIR: &str = "training";
+
+#[derive(Debug, Clone)]
+pub struct LabelledExample {
+    pub label: String,
+    pub features: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrainingSet {
+    pub examples: Vec<LabelledExample>,
+}
+
+impl TrainingSet {
+    /// Distinct labels in order of first appearance.
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = Vec::new();
+        for example in &self.examples {
+            if !labels.contains(&example.label) {
+                labels.push(example.label.clone());
+            }
+        }
+        labels
+    }
+
+    /// Slices a recording of a single activity into overlapping windows and adds
+    /// the features of each. Returns the number of windows added.
+    pub fn add_recording(
+        &mut self,
+        label: &str,
+        measurements: &[CsiMeasurement],
+        config: &FeatureConfig,
+    ) -> usize {
+        let window = config.window_len();
+        if measurements.len() < window {
+            return 0;
+        }
+        let before = self.examples.len();
+        for start in (0..=measurements.len() - window).step_by(config.hop_len()) {
+            match FeatureExtractor::extract(&measurements[start..start + window], config) {
+                Ok(features) => self.examples.push(LabelledExample { label: label.to_string(), features }),
+                Err(e) => tracing::debug!("Skipping training window at {}: {}", start, e),
+            }
+        }
+        self.examples.len() - before
+    }
+
+    /// Adds the windows of every labelled interval of a recording.
+    pub fn add_labelled_recording(
+        &mut self,
+        measurements: &[CsiMeasurement],
+        intervals: &[LabelInterval],
+        config: &FeatureConfig,
+    ) -> usize {
+        intervals
+            .iter()
+            .map(|interval| self.add_recording(&interval.label, interval.slice(measurements), config))
+            .sum()
+    }
+
+    /// Loads every `<label>/*.rrd` and labelled `*.rrd` recording under `dir`.
+    pub fn from_dir(dir: &Path, config: &FeatureConfig) -> Result<Self> {
+        let mut set = Self::default();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "rrd") {
+                    let intervals = LabelStorage::load(&path)?;
+                    let added = set.add_labelled_recording(&RrdStorage::load(&path)?, &intervals, config);
+                    tracing::info!("{} windows from {} labelled intervals in {}", added, intervals.len(), path.display());
+                }
+                continue;
+            }
+            let label = entry.file_name().to_string_lossy().to_string();
+            for file in std::fs::read_dir(entry.path())? {
+                let path = file?.path();
+                if path.extension().is_some_and(|e| e == "rrd") {
+                    let measurements = RrdStorage::load(&path)?;
+                    let added = set.add_recording(&label, &measurements, config);
+                    tracing::info!("{}: {} windows from {}", label, added, path.display());
+                }
+            }
+        }
+
+        if set.labels().len() < 2 {
+            return Err(anyhow!(
+                "Need recordings for at least two activities in {}",
+                dir.display()
+            ));
+        }
+        Ok(set)
+    }
+}
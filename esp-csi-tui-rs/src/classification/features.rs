@@ -0,0 +1,251 @@
+use crate::detection::motion::MotionFeatures;
+use crate::dsp::pca::Pca;
+use crate::dsp::{Resampler, SpectrogramConfig, SpectrogramProcessor};
+use crate::models::CsiMeasurement;
+use anyhow::{Result, anyhow};
+use ndarray::Array2;
+
+/// Names of the values returned by [`FeatureExtractor::extract`], in order. Models
+/// trained elsewhere (e.g. exported to ONNX) must take their inputs in this order.
+pub const FEATURE_NAMES: [&str; 18] = [
+    "mean_amplitude",
+    "coefficient_of_variation",
+    "skewness",
+    "kurtosis",
+    "relative_range",
+    "relative_diff",
+    "motion_variance",
+    "motion_decorrelation",
+    "dominant_frequency_hz",
+    "spectral_centroid_hz",
+    "spectral_entropy",
+    "band_power_low",
+    "band_power_mid",
+    "band_power_high",
+    "pca_ratio_1",
+    "pca_ratio_2",
+    "pca_ratio_3",
+    "pca_1_relative_std",
+];
+
+pub const NUM_FEATURES: usize = FEATURE_NAMES.len();
+
+/// Upper edges of the low (breathing) and mid (gesture/walking) bands; above is "high".
+const LOW_BAND_HZ: f32 = 0.5;
+const MID_BAND_HZ: f32 = 2.0;
+/// Fewest resampled points a window needs for meaningful spectral features.
+const MIN_SAMPLES: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct FeatureConfig {
+    pub sample_rate_hz: f32,
+    /// Length of one classified window.
+    pub window_s: f32,
+    /// Step between windows when slicing recordings for training.
+    pub hop_s: f32,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self { sample_rate_hz: 10.0, window_s: 4.0, hop_s: 2.0 }
+    }
+}
+
+impl FeatureConfig {
+    pub fn window_len(&self) -> usize {
+        (self.window_s * self.sample_rate_hz).round().max(MIN_SAMPLES as f32) as usize
+    }
+
+    pub fn hop_len(&self) -> usize {
+        (self.hop_s * self.sample_rate_hz).round().max(1.0) as usize
+    }
+}
+
+pub struct FeatureExtractor;
+
+impl FeatureExtractor {
+    /// Statistical, spectral and PCA features of one window of CSI amplitude.
+    ///
+    /// Levels are normalised by the mean amplitude where it matters, so models carry
+    /// over between rooms and AGC settings better than with raw magnitudes.
+    pub fn extract(window: &[CsiMeasurement], config: &FeatureConfig) -> Result<Vec<f32>> {
+        let series = Resampler::amplitudes(window, config.sample_rate_hz)?;
+        if series.samples.len() < MIN_SAMPLES || series.num_subcarriers() == 0 {
+            return Err(anyhow!(
+                "Need at least {} samples for features, have {}",
+                MIN_SAMPLES,
+                series.samples.len()
+            ));
+        }
+        let motion = MotionFeatures::from_window(window).unwrap_or_default();
+
+        let averaged = series.averaged();
+        let n = averaged.len() as f32;
+        let mean = averaged.iter().sum::<f32>() / n;
+        let centred: Vec<f32> = averaged.iter().map(|x| x - mean).collect();
+        let variance = centred.iter().map(|d| d * d).sum::<f32>() / n;
+        let std = variance.sqrt();
+        let (skewness, kurtosis) = if std > f32::EPSILON {
+            (
+                centred.iter().map(|d| (d / std).powi(3)).sum::<f32>() / n,
+                centred.iter().map(|d| (d / std).powi(4)).sum::<f32>() / n - 3.0,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let level = mean.abs().max(f32::EPSILON);
+        let max = averaged.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let min = averaged.iter().cloned().fold(f32::INFINITY, f32::min);
+        let mean_diff = averaged.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f32>() / (n - 1.0);
+
+        let spectral = spectral_features(&averaged, config.sample_rate_hz)?;
+
+        let data = Array2::from_shape_vec(
+            (series.samples.len(), series.num_subcarriers()),
+            series.samples.concat(),
+        )?;
+        let (ratios, pc1_std) = match Pca::fit(&data, 3) {
+            Some(pca) => {
+                let mut ratios = pca.explained_variance_ratio();
+                ratios.resize(3, 0.0);
+                let scores = pca.project(&data);
+                let pc1 = scores.column(0);
+                let pc1_std = (pc1.mapv(|x| x * x).sum() / pc1.len() as f32).sqrt();
+                (ratios, pc1_std / level)
+            }
+            None => (vec![0.0; 3], 0.0),
+        };
+
+        let mut features = vec![
+            mean,
+            std / level,
+            skewness,
+            kurtosis,
+            (max - min) / level,
+            mean_diff / level,
+            motion.variance,
+            motion.decorrelation,
+        ];
+        features.extend(spectral);
+        features.extend(ratios);
+        features.push(pc1_std);
+        debug_assert_eq!(features.len(), NUM_FEATURES);
+        Ok(features)
+    }
+}
+
+/// Dominant frequency, centroid, normalised entropy and relative band powers of the
+/// whole window's periodogram (DC excluded).
+fn spectral_features(signal: &[f32], sample_rate_hz: f32) -> Result<[f32; 6]> {
+    let config = SpectrogramConfig {
+        sample_rate_hz,
+        window_size: signal.len(),
+        hop_size: signal.len(),
+    };
+    let spectrum = SpectrogramProcessor::stft(signal, &config)?;
+    let Some(power) = spectrum.power.first() else {
+        return Ok([0.0; 6]);
+    };
+    let bins: Vec<(f32, f32)> = spectrum.frequencies.iter().cloned().zip(power.iter().cloned()).skip(1).collect();
+    let total: f32 = bins.iter().map(|(_, p)| p).sum();
+    if total <= f32::EPSILON {
+        return Ok([0.0; 6]);
+    }
+
+    let dominant = bins.iter().max_by(|a, b| a.1.total_cmp(&b.1)).map(|(f, _)| *f).unwrap_or(0.0);
+    let centroid = bins.iter().map(|(f, p)| f * p).sum::<f32>() / total;
+    let entropy = -bins
+        .iter()
+        .map(|(_, p)| p / total)
+        .filter(|p| *p > 0.0)
+        .map(|p| p * p.ln())
+        .sum::<f32>()
+        / (bins.len() as f32).ln().max(f32::EPSILON);
+    let band = |low: f32, high: f32| {
+        bins.iter().filter(|(f, _)| *f >= low && *f < high).map(|(_, p)| p).sum::<f32>() / total
+    };
+
+    Ok([
+        dominant,
+        centroid,
+        entropy,
+        band(0.0, LOW_BAND_HZ),
+        band(LOW_BAND_HZ, MID_BAND_HZ),
+        band(MID_BAND_HZ, f32::INFINITY),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ComplexNumber;
+    use chrono::{DateTime, Duration};
+
+    /// Four seconds at 10 Hz of four subcarriers whose amplitude is `10 + 2 sin(2 pi f t)`.
+    fn sine_window(frequency_hz: f32) -> Vec<CsiMeasurement> {
+        let start = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        (0..40)
+            .map(|i| {
+                let t = i as f32 / 10.0;
+                let amplitude = 10.0 + 2.0 * (2.0 * std::f32::consts::PI * frequency_hz * t).sin();
+                CsiMeasurement {
+                    timestamp: start + Duration::milliseconds(i * 100),
+                    channel: 6,
+                    bandwidth: 20,
+                    rssi: -40,
+                    noise_floor: -90,
+                    subcarrier_data: vec![ComplexNumber::new(amplitude, 0.0); 4],
+                    sequence: None,
+                    device_timestamp_us: None,
+                    device_id: None,
+                    transmitter: None,
+                }
+            })
+            .collect()
+    }
+
+    fn feature(features: &[f32], name: &str) -> f32 {
+        features[FEATURE_NAMES.iter().position(|n| *n == name).unwrap()]
+    }
+
+    #[test]
+    fn sine_window_features() {
+        let features = FeatureExtractor::extract(&sine_window(1.0), &FeatureConfig::default()).unwrap();
+        assert_eq!(features.len(), NUM_FEATURES);
+
+        let expect = |name: &str, value: f32, tolerance: f32| {
+            let actual = feature(&features, name);
+            assert!((actual - value).abs() <= tolerance, "{} is {}, expected {}", name, actual, value);
+        };
+        // Four whole periods: the mean is the offset and the spread that of a sine.
+        expect("mean_amplitude", 10.0, 1e-3);
+        expect("coefficient_of_variation", 2.0 / 2f32.sqrt() / 10.0, 1e-3);
+        expect("skewness", 0.0, 1e-3);
+        expect("kurtosis", -1.5, 1e-2);
+        // Ten samples per period peak at sin(0.4 pi).
+        expect("relative_range", 4.0 * (0.4 * std::f32::consts::PI).sin() / 10.0, 1e-3);
+        expect("motion_variance", 0.02, 1e-4);
+        expect("motion_decorrelation", 0.0, 0.0);
+
+        expect("dominant_frequency_hz", 1.0, 0.0);
+        expect("spectral_centroid_hz", 1.0, 0.05);
+        expect("band_power_low", 0.0, 1e-3);
+        expect("band_power_mid", 1.0, 1e-3);
+        expect("band_power_high", 0.0, 1e-3);
+
+        // Identical subcarriers: one component carries everything, with sqrt(4) times
+        // the spread of a single subcarrier.
+        expect("pca_ratio_1", 1.0, 1e-3);
+        expect("pca_ratio_2", 0.0, 1e-3);
+        expect("pca_1_relative_std", 2.0 * 2.0 / 2f32.sqrt() / 10.0, 1e-3);
+    }
+
+    #[test]
+    fn faster_motion_moves_power_up_the_bands() {
+        let features = FeatureExtractor::extract(&sine_window(3.0), &FeatureConfig::default()).unwrap();
+        assert_eq!(feature(&features, "dominant_frequency_hz"), 3.0);
+        assert!(feature(&features, "band_power_high") > 0.99);
+
+        assert!(FeatureExtractor::extract(&sine_window(1.0)[..5], &FeatureConfig::default()).is_err());
+    }
+}
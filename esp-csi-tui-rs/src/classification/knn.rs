@@ -0,0 +1,171 @@
+use crate::classification::dataset::TrainingSet;
+use crate::classification::{Classifier, Prediction};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const DEFAULT_K: usize = 5;
+
+/// k-nearest-neighbour classifier over z-scored feature vectors.
+///
+/// Small enough to train in the TUI from a handful of labelled recordings, and
+/// serialised as plain JSON so a trained model can be shared with the recordings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnnClassifier {
+    k: usize,
+    labels: Vec<String>,
+    mean: Vec<f32>,
+    scale: Vec<f32>,
+    /// Standardised training examples and the index of their label.
+    examples: Vec<(Vec<f32>, usize)>,
+}
+
+impl KnnClassifier {
+    pub fn train(set: &TrainingSet, k: usize) -> Result<Self> {
+        let first = set.examples.first().ok_or_else(|| anyhow!("Training set is empty"))?;
+        let dims = first.features.len();
+        if set.examples.iter().any(|e| e.features.len() != dims) {
+            return Err(anyhow!("Training examples have inconsistent feature lengths"));
+        }
+
+        let count = set.examples.len() as f32;
+        let mut mean = vec![0.0f32; dims];
+        for example in &set.examples {
+            mean.iter_mut().zip(&example.features).for_each(|(m, x)| *m += x / count);
+        }
+        let mut scale = vec![0.0f32; dims];
+        for example in &set.examples {
+            for ((s, x), m) in scale.iter_mut().zip(&example.features).zip(&mean) {
+                *s += (x - m).powi(2) / count;
+            }
+        }
+        // Constant features carry no information; a unit scale keeps them harmless.
+        scale.iter_mut().for_each(|s| *s = if *s > f32::EPSILON { s.sqrt() } else { 1.0 });
+
+        let labels = set.labels();
+        let examples = set
+            .examples
+            .iter()
+            .map(|e| {
+                let label = labels.iter().position(|l| *l == e.label).unwrap_or(0);
+                (standardise(&e.features, &mean, &scale), label)
+            })
+            .collect();
+
+        Ok(Self { k: k.max(1), labels, mean, scale, examples })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        tracing::info!("Saved k-NN model ({} examples) to {}", self.examples.len(), path.display());
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+impl Classifier for KnnClassifier {
+    fn name(&self) -> &str {
+        "k-NN"
+    }
+
+    fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    fn predict(&self, features: &[f32]) -> Result<Prediction> {
+        if features.len() != self.mean.len() {
+            return Err(anyhow!(
+                "Model expects {} features, got {}",
+                self.mean.len(),
+                features.len()
+            ));
+        }
+        let query = standardise(features, &self.mean, &self.scale);
+
+        let mut distances: Vec<(f32, usize)> = self
+            .examples
+            .iter()
+            .map(|(x, label)| {
+                let d = x.iter().zip(&query).map(|(a, b)| (a - b).powi(2)).sum::<f32>();
+                (d.sqrt(), *label)
+            })
+            .collect();
+        distances.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Inverse-distance votes, so a near-exact match outweighs distant neighbours.
+        let mut scores = vec![0.0f32; self.labels.len()];
+        for (distance, label) in distances.iter().take(self.k) {
+            scores[*label] += 1.0 / (distance + 1e-3);
+        }
+        let total: f32 = scores.iter().sum();
+        scores.iter_mut().for_each(|s| *s /= total.max(f32::EPSILON));
+
+        Ok(Prediction::from_scores(&self.labels, scores))
+    }
+}
+
+fn standardise(features: &[f32], mean: &[f32], scale: &[f32]) -> Vec<f32> {
+    features
+        .iter()
+        .zip(mean.iter().zip(scale))
+        .map(|(x, (m, s))| (x - m) / s)
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classification::dataset::LabelledExample;
+
+    /// Two clusters apart on the first two features; the third is constant.
+    fn training_set() -> TrainingSet {
+        let mut set = TrainingSet::default();
+        for i in 0..10 {
+            let jitter = (i as f32 - 4.5) * 0.1;
+            for (label, centre) in [("still", 0.0), ("walking", 10.0)] {
+                set.examples.push(LabelledExample {
+                    label: label.to_string(),
+                    features: vec![centre + jitter, centre - jitter, 1.0],
+                });
+            }
+        }
+        set
+    }
+
+    #[test]
+    fn separable_classes_are_told_apart() {
+        let model = KnnClassifier::train(&training_set(), DEFAULT_K).unwrap();
+        assert_eq!(model.labels(), ["still", "walking"]);
+
+        let still = model.predict(&[0.5, -0.3, 1.0]).unwrap();
+        assert_eq!(still.label, "still");
+        assert_eq!(still.confidence, 1.0);
+        let walking = model.predict(&[9.0, 9.5, 1.0]).unwrap();
+        assert_eq!(walking.label, "walking");
+        assert!((walking.scores.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+
+        assert!(model.predict(&[0.0, 0.0]).is_err());
+        assert!(KnnClassifier::train(&TrainingSet::default(), DEFAULT_K).is_err());
+    }
+
+    #[test]
+    fn saved_model_predicts_the_same() {
+        let model = KnnClassifier::train(&training_set(), 3).unwrap();
+        let path = std::env::temp_dir().join(format!("esp-csi-{}-knn.json", std::process::id()));
+        model.save(&path).unwrap();
+        let loaded = KnnClassifier::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for query in [[4.0, 4.0, 1.0], [6.0, 6.0, 1.0]] {
+            let (a, b) = (model.predict(&query).unwrap(), loaded.predict(&query).unwrap());
+            assert_eq!((a.label, a.scores), (b.label, b.scores));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+pub mod dataset;
+pub mod features;
+pub mod knn;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+
+pub use dataset::{TrainingSet, TRAINING_DIR};
+pub use features::{FeatureConfig, FeatureExtractor, FEATURE_NAMES};
+pub use knn::KnnClassifier;
+
+use anyhow::Result;
+use std::path::Path;
+
+/// Directory, relative to the working directory, searched for trained models.
+pub const MODEL_DIR: &str = "models";
+/// ONNX model taking `[1, NUM_FEATURES]` f32 features; labels in `activity.labels`.
+#[cfg(feature = "onnx")]
+pub const ONNX_MODEL_FILE: &str = "activity.onnx";
+pub const KNN_MODEL_FILE: &str = "activity_knn.json";
+
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    pub label: String,
+    /// Score of the winning label, 0..1.
+    pub confidence: f32,
+    /// Score per label, in the classifier's label order.
+    pub scores: Vec<f32>,
+}
+
+impl Prediction {
+    pub fn from_scores(labels: &[String], scores: Vec<f32>) -> Self {
+        let (best, confidence) = scores
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, s)| (i, *s))
+            .unwrap_or((0, 0.0));
+        Self {
+            label: labels.get(best).cloned().unwrap_or_else(|| "unknown".to_string()),
+            confidence,
+            scores,
+        }
+    }
+}
+
+/// An activity model over the features produced by [`FeatureExtractor`].
+pub trait Classifier: Send {
+    fn name(&self) -> &str;
+    fn labels(&self) -> &[String];
+    fn predict(&self, features: &[f32]) -> Result<Prediction>;
+}
+
+/// Loads the preferred model from `dir`: the ONNX model when built with the `onnx`
+/// feature and present, otherwise a saved k-NN model. `Ok(None)` when there is neither.
+pub fn load_model(dir: &Path) -> Result<Option<Box<dyn Classifier>>> {
+    #[cfg(feature = "onnx")]
+    {
+        let path = dir.join(ONNX_MODEL_FILE);
+        if path.exists() {
+            return Ok(Some(Box::new(onnx::OnnxClassifier::load(&path)?)));
+        }
+    }
+
+    let path = dir.join(KNN_MODEL_FILE);
+    if path.exists() {
+        return Ok(Some(Box::new(KnnClassifier::load(&path)?)));
+    }
+    Ok(None)
+}
+
+/// Trains a k-NN model from the labelled recordings in `training_dir` and saves it
+/// to `model_dir`.
+pub fn train_knn(training_dir: &Path, model_dir: &Path, config: &FeatureConfig) -> Result<KnnClassifier> {
+    let set = TrainingSet::from_dir(training_dir, config)?;
+    let model = KnnClassifier::train(&set, knn::DEFAULT_K)?;
+    model.save(&model_dir.join(KNN_MODEL_FILE))?;
+    Ok(model)
+}
@@ -0,0 +1,71 @@
+use crate::classification::features::NUM_FEATURES;
+use crate::classification::{Classifier, Prediction};
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use tract_onnx::prelude::*;
+
+/// An ONNX activity model run on the CPU with tract.
+///
+/// The model takes a `[1, NUM_FEATURES]` f32 tensor and returns `[1, num_labels]`
+/// scores (probabilities or logits). Labels are read one per line from a file next to
+/// the model with the `.labels` extension.
+pub struct OnnxClassifier {
+    model: TypedRunnableModel<TypedModel>,
+    labels: Vec<String>,
+}
+
+impl OnnxClassifier {
+    pub fn load(path: &Path) -> Result<Self> {
+        let labels_path = path.with_extension("labels");
+        let labels: Vec<String> = std::fs::read_to_string(&labels_path)
+            .map_err(|e| anyhow!("Cannot read labels {}: {}", labels_path.display(), e))?
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        let model = tract_onnx::onnx()
+            .model_for_path(path)?
+            .with_input_fact(0, f32::fact([1, NUM_FEATURES]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+        tracing::info!("Loaded ONNX model {} with {} labels", path.display(), labels.len());
+        Ok(Self { model, labels })
+    }
+}
+
+impl Classifier for OnnxClassifier {
+    fn name(&self) -> &str {
+        "ONNX"
+    }
+
+    fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    fn predict(&self, features: &[f32]) -> Result<Prediction> {
+        let input = tract_ndarray::Array2::from_shape_vec((1, features.len()), features.to_vec())?;
+        let outputs = self.model.run(tvec!(Tensor::from(input).into()))?;
+        let mut scores: Vec<f32> = outputs[0].to_array_view::<f32>()?.iter().copied().collect();
+        if scores.len() != self.labels.len() {
+            return Err(anyhow!(
+                "Model returned {} scores for {} labels",
+                scores.len(),
+                self.labels.len()
+            ));
+        }
+
+        // Pass probabilities through unchanged; turn logits into probabilities.
+        let is_distribution = scores.iter().all(|s| (0.0..=1.0).contains(s))
+            && (scores.iter().sum::<f32>() - 1.0).abs() < 1e-3;
+        if !is_distribution {
+            let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            scores.iter_mut().for_each(|s| *s = (*s - max).exp());
+            let total: f32 = scores.iter().sum();
+            scores.iter_mut().for_each(|s| *s /= total);
+        }
+
+        Ok(Prediction::from_scores(&self.labels, scores))
+    }
+}
@@ -1,31 +1,408 @@
-use crate::models::DeviceCommand;
-use anyhow::Result;
+pub mod script;
 
-pub struct CommandExecutor;
+use crate::device::DeviceManager;
+use crate::models::{DeviceCommand, DeviceConfig, TrafficKind, WifiConfig, WifiMode};
+use crate::synthetic::Scenario;
+use anyhow::{Result, anyhow};
+
+/// Devices a command is sent to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    All,
+    Selected,
+    Device(String),
+}
+
+/// How one device handled one command, reported back by its acquisition task.
+#[derive(Debug, Clone)]
+pub struct CommandOutcome {
+    pub request: u64,
+    pub device: String,
+    pub command: &'static str,
+    pub result: Result<(), String>,
+}
+
+/// A queued command: its ID and how many devices it went to, i.e. how many
+/// outcomes will come back.
+#[derive(Debug, Clone, Copy)]
+pub struct Request {
+    pub id: u64,
+    pub devices: usize,
+}
+
+/// Settings given to `configure`; the rest keep each device's current value.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ConfigChange {
+    channel: Option<u8>,
+    bandwidth: Option<u16>,
+    interval_ms: Option<u64>,
+}
+
+impl ConfigChange {
+    fn apply(&self, config: &DeviceConfig) -> DeviceConfig {
+        DeviceConfig {
+            channel: self.channel.unwrap_or(config.channel),
+            bandwidth: self.bandwidth.unwrap_or(config.bandwidth),
+            collection_interval_ms: self.interval_ms.unwrap_or(config.collection_interval_ms),
+            ..config.clone()
+        }
+    }
+}
+
+/// Wi-Fi settings given to `wifi`; the rest keep each device's current value.
+#[derive(Debug, Clone, Default)]
+struct WifiChange {
+    mode: Option<WifiMode>,
+    sta_ssid: Option<String>,
+    sta_password: Option<String>,
+    ap_ssid: Option<String>,
+    ap_password: Option<String>,
+    ap_hidden: Option<bool>,
+    max_connections: Option<u8>,
+}
+
+impl WifiChange {
+    fn apply(&self, wifi: &WifiConfig) -> WifiConfig {
+        WifiConfig {
+            mode: self.mode.unwrap_or(wifi.mode),
+            sta_ssid: self.sta_ssid.clone().unwrap_or_else(|| wifi.sta_ssid.clone()),
+            sta_password: self.sta_password.clone().unwrap_or_else(|| wifi.sta_password.clone()),
+            ap_ssid: self.ap_ssid.clone().unwrap_or_else(|| wifi.ap_ssid.clone()),
+            ap_password: self.ap_password.clone().unwrap_or_else(|| wifi.ap_password.clone()),
+            ap_hidden: self.ap_hidden.unwrap_or(wifi.ap_hidden),
+            max_connections: self.max_connections.unwrap_or(wifi.max_connections),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Instruction {
+    Command(DeviceCommand),
+    Configure(ConfigChange),
+    Wifi(WifiChange),
+    /// CSI settings by name, checked against each device's chip when sent.
+    Csi(Vec<(String, String)>),
+    /// Traffic type and rate, each kept from the device's config when not given.
+    Traffic(Option<TrafficKind>, Option<u32>),
+}
+
+/// The single path for device control.
+///
+/// Keybindings, the command palette and headless scripts all go through here: each
+/// call gets a request ID, the command is queued to every targeted device's
+/// acquisition task, and the per-device results come back as [`CommandOutcome`]s from
+/// [`DeviceManager::take_outcomes`].
+#[derive(Debug)]
+pub struct CommandExecutor {
+    next_request: u64,
+}
 
 impl CommandExecutor {
-    pub fn execute(command: DeviceCommand) -> Result<()> {
-        match command {
-            DeviceCommand::Configure(_config) => {
-                tracing::info!("Configuring device");
-                Ok(())
+    pub fn new() -> Self {
+        Self { next_request: 1 }
+    }
+
+    /// Queues `command` to the targeted devices.
+    pub fn execute(&mut self, devices: &DeviceManager, target: &Target, command: DeviceCommand) -> Result<Request> {
+        self.dispatch(devices, target, |_| Ok(command.clone()))
+    }
+
+    /// Parses and executes one palette or script line, see [`parse`].
+    pub fn execute_line(&mut self, devices: &DeviceManager, line: &str) -> Result<Request> {
+        let (target, instruction) = parse(line)?;
+        match instruction {
+            Instruction::Command(command) => self.execute(devices, &target, command),
+            Instruction::Configure(change) => {
+                self.dispatch(devices, &target, |config| Ok(DeviceCommand::Configure(change.apply(config))))
             }
-            DeviceCommand::StartCollection => {
-                tracing::info!("Starting data collection");
-                Ok(())
+            Instruction::Wifi(change) => self.dispatch(devices, &target, |config| {
+                let config = DeviceConfig { wifi: change.apply(&config.wifi), ..config.clone() };
+                config.validate()?;
+                Ok(DeviceCommand::SetWifi(config.wifi))
+            }),
+            Instruction::Traffic(kind, frequency_hz) => self.dispatch(devices, &target, |config| {
+                let mut config = config.clone();
+                config.traffic.kind = kind.unwrap_or(config.traffic.kind);
+                config.traffic.frequency_hz = frequency_hz.unwrap_or(config.traffic.frequency_hz);
+                config.validate()?;
+                Ok(DeviceCommand::SetTraffic(config.traffic))
+            }),
+            Instruction::Csi(settings) => self.dispatch(devices, &target, |config| {
+                let mut csi = config.csi.clone();
+                for (name, value) in &settings {
+                    csi.set(name, value)?;
+                }
+                Ok(DeviceCommand::SetCsi(csi))
+            }),
+        }
+    }
+
+    fn dispatch(
+        &mut self,
+        devices: &DeviceManager,
+        target: &Target,
+        command_for: impl Fn(&DeviceConfig) -> Result<DeviceCommand>,
+    ) -> Result<Request> {
+        let indices = resolve(devices, target)?;
+        let commands = indices
+            .iter()
+            .map(|&index| {
+                let device = &devices.devices()[index];
+                let command = command_for(&device.config).and_then(|command| command.validate(device.info.as_ref()).map(|_| command));
+                Ok((index, command.map_err(|e| anyhow!("{}: {}", device.id, e))?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let request = Request { id: self.next_request, devices: indices.len() };
+        self.next_request += 1;
+        for (index, command) in commands {
+            tracing::debug!("Request {}: {} -> {}", request.id, command.name(), devices.devices()[index].id);
+            devices.send(index, request.id, command)?;
+        }
+        Ok(request)
+    }
+}
+
+fn resolve(devices: &DeviceManager, target: &Target) -> Result<Vec<usize>> {
+    let indices: Vec<usize> = match target {
+        Target::All => (0..devices.devices().len()).collect(),
+        Target::Selected => devices.selected().map(|_| devices.selected_index()).into_iter().collect(),
+        Target::Device(id) => {
+            let index = devices
+                .devices()
+                .iter()
+                .position(|d| &d.id == id)
+                .ok_or_else(|| anyhow!("No device '{}'", id))?;
+            vec![index]
+        }
+    };
+    if indices.is_empty() {
+        return Err(anyhow!("No devices to send to"));
+    }
+    Ok(indices)
+}
+
+/// Parses `[@<device>|@all|@selected] <command> [args]`, sending to all devices when no
+/// target is given. Commands: `start`, `stop`, `query`, `disconnect`,
+/// `configure [channel=<n>] [bandwidth=<mhz>] [interval=<ms>]` and
+/// `wifi [mode=<mode>] [sta-ssid=..] [sta-password=..] [ap-ssid=..] [ap-password=..]
+/// [hidden=<bool>] [max-connections=<n>]`, `csi <setting>=<value>...` and
+/// `traffic [type=udp] [rate=<hz>]` or `traffic off`, and
+/// `scenario <scene>[:<s>],... [<setting>=<value>...]` for demo devices (see
+/// [`Scenario::parse`]).
+fn parse(line: &str) -> Result<(Target, Instruction)> {
+    let mut words = line.split_whitespace().peekable();
+    let target = match words.peek().and_then(|w| w.strip_prefix('@')) {
+        Some(name) => {
+            words.next();
+            match name {
+                "all" => Target::All,
+                "selected" => Target::Selected,
+                id => Target::Device(id.to_string()),
             }
-            DeviceCommand::StopCollection => {
-                tracing::info!("Stopping data collection");
-                Ok(())
+        }
+        None => Target::All,
+    };
+
+    let name = words.next().ok_or_else(|| anyhow!("Empty command"))?;
+    let instruction = match name {
+        "start" => Instruction::Command(DeviceCommand::StartCollection),
+        "stop" => Instruction::Command(DeviceCommand::StopCollection),
+        "query" => Instruction::Command(DeviceCommand::Query),
+        "disconnect" => Instruction::Command(DeviceCommand::Disconnect),
+        "configure" => {
+            let mut change = ConfigChange::default();
+            for arg in words.by_ref() {
+                let (key, value) = arg
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Expected key=value, got '{}'", arg))?;
+                let invalid = |e: std::num::ParseIntError| anyhow!("Invalid {} '{}': {}", key, value, e);
+                match key {
+                    "channel" => change.channel = Some(value.parse().map_err(invalid)?),
+                    "bandwidth" => change.bandwidth = Some(value.parse().map_err(invalid)?),
+                    "interval" => change.interval_ms = Some(value.parse().map_err(invalid)?),
+                    _ => return Err(anyhow!("Unknown setting '{}'", key)),
+                }
             }
-            DeviceCommand::Query => {
-                tracing::info!("Querying device status");
-                Ok(())
+            Instruction::Configure(change)
+        }
+        "wifi" => {
+            let mut change = WifiChange::default();
+            for arg in words.by_ref() {
+                let (key, value) = arg
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Expected key=value, got '{}'", arg))?;
+                match key {
+                    "mode" => change.mode = Some(WifiMode::parse(value)?),
+                    "sta-ssid" => change.sta_ssid = Some(value.to_string()),
+                    "sta-password" => change.sta_password = Some(value.to_string()),
+                    "ap-ssid" => change.ap_ssid = Some(value.to_string()),
+                    "ap-password" => change.ap_password = Some(value.to_string()),
+                    "hidden" => {
+                        change.ap_hidden = Some(value.parse().map_err(|_| anyhow!("Invalid hidden '{}' (true or false)", value))?)
+                    }
+                    "max-connections" => {
+                        change.max_connections = Some(value.parse().map_err(|e| anyhow!("Invalid {} '{}': {}", key, value, e))?)
+                    }
+                    _ => return Err(anyhow!("Unknown Wi-Fi setting '{}'", key)),
+                }
+            }
+            Instruction::Wifi(change)
+        }
+        "csi" => {
+            let settings = words
+                .by_ref()
+                .map(|arg| {
+                    let (key, value) = arg
+                        .split_once('=')
+                        .ok_or_else(|| anyhow!("Expected key=value, got '{}'", arg))?;
+                    Ok((key.to_string(), value.to_string()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if settings.is_empty() {
+                return Err(anyhow!("csi needs at least one setting, e.g. htltf=off"));
             }
-            DeviceCommand::Disconnect => {
-                tracing::info!("Disconnecting from device");
-                Ok(())
+            Instruction::Csi(settings)
+        }
+        "traffic" => {
+            let (mut kind, mut rate) = (None, None);
+            for arg in words.by_ref() {
+                match arg.split_once('=') {
+                    None if arg == "off" => rate = Some(0),
+                    Some(("type", value)) => kind = Some(TrafficKind::parse(value)?),
+                    Some(("rate", value)) => {
+                        rate = Some(value.parse().map_err(|e| anyhow!("Invalid rate '{}': {}", value, e))?)
+                    }
+                    _ => return Err(anyhow!("Expected type=.., rate=.. or off, got '{}'", arg)),
+                }
             }
+            Instruction::Traffic(kind, rate)
+        }
+        "scenario" => {
+            let args: Vec<&str> = words.by_ref().collect();
+            Instruction::Command(DeviceCommand::SetScenario(Scenario::parse(&args.join(" "))?))
+        }
+        other => return Err(anyhow!("Unknown command '{}'", other)),
+    };
+    if let Some(extra) = words.next() {
+        return Err(anyhow!("Unexpected '{}' after {}", extra, name));
+    }
+    Ok((target, instruction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CsiConfig, DeviceInfo, TrafficConfig};
+
+    #[test]
+    fn parses_targets_and_commands() {
+        let (target, instruction) = parse("start").unwrap();
+        assert_eq!(target, Target::All);
+        assert!(matches!(instruction, Instruction::Command(DeviceCommand::StartCollection)));
+
+        let (target, instruction) = parse("@ttyUSB1 query").unwrap();
+        assert_eq!(target, Target::Device("ttyUSB1".to_string()));
+        assert!(matches!(instruction, Instruction::Command(DeviceCommand::Query)));
+
+        let (_, instruction) = parse("@demo1 scenario empty:10,walking:20 seed=3").unwrap();
+        assert!(matches!(instruction, Instruction::Command(DeviceCommand::SetScenario(s)) if s.seed == Some(3)));
+
+        assert!(parse("").is_err());
+        assert!(parse("@all jump").is_err());
+        assert!(parse("stop now").is_err());
+    }
+
+    #[test]
+    fn configure_keeps_unset_values() {
+        let (target, instruction) = parse("@selected configure channel=11 interval=50").unwrap();
+        assert_eq!(target, Target::Selected);
+        let Instruction::Configure(change) = instruction else { panic!("expected configure") };
+        let config = change.apply(&DeviceConfig::default());
+        assert_eq!((config.channel, config.collection_interval_ms), (11, 50));
+        assert_eq!(config.bandwidth, DeviceConfig::default().bandwidth);
+
+        assert!(parse("configure channel=abc").is_err());
+        assert!(parse("configure power=5").is_err());
+    }
+
+    #[test]
+    fn wifi_changes_are_validated() {
+        let (_, instruction) = parse("wifi mode=station sta-ssid=lab sta-password=secret123").unwrap();
+        let Instruction::Wifi(change) = instruction else { panic!("expected wifi") };
+        let wifi = change.apply(&WifiConfig::default());
+        assert_eq!((wifi.mode, wifi.sta_ssid.as_str()), (WifiMode::Station, "lab"));
+        assert!(wifi.validate().is_ok());
+
+        let (_, instruction) = parse("wifi mode=ap ap-password=short").unwrap();
+        let Instruction::Wifi(change) = instruction else { panic!("expected wifi") };
+        assert!(change.apply(&WifiConfig::default()).validate().is_err());
+
+        assert!(parse("wifi mode=mesh").is_err());
+    }
+
+    #[test]
+    fn csi_flags_follow_the_chip() {
+        let (_, instruction) = parse("csi htltf=off ltf-merge=0").unwrap();
+        let Instruction::Csi(settings) = instruction else { panic!("expected csi") };
+        let mut csi = CsiConfig::default();
+        for (name, value) in &settings {
+            csi.set(name, value).unwrap();
         }
+        assert_eq!(csi.enabled(), vec!["lltf", "stbc-htltf"]);
+
+        let mut c6 = CsiConfig::for_chip("esp32c6");
+        assert!(c6.set("htltf", "off").is_err());
+        assert!(c6.set("he-stbc", "3").is_err());
+        c6.set("he-stbc", "1").unwrap();
+        assert!(c6.matches_chip("esp32c6") && !c6.matches_chip("esp32c3"));
+        assert!(parse("csi").is_err());
+    }
+
+    #[test]
+    fn traffic_needs_a_station() {
+        let (_, instruction) = parse("traffic type=udp rate=50").unwrap();
+        assert!(matches!(instruction, Instruction::Traffic(Some(TrafficKind::Udp), Some(50))));
+        assert!(matches!(parse("traffic off").unwrap().1, Instruction::Traffic(None, Some(0))));
+        assert!(parse("traffic type=arp").is_err());
+        assert!(parse("traffic type=ping").is_err());
+
+        let mut config = DeviceConfig::default();
+        config.traffic.frequency_hz = 50;
+        assert!(config.validate().is_err());
+        config.wifi = WifiConfig { mode: WifiMode::Station, sta_ssid: "lab".to_string(), ..WifiConfig::default() };
+        config.validate().unwrap();
+        assert_eq!(config.requested_rate_hz(), 50.0);
+        config.traffic.frequency_hz = 5000;
+        assert!(config.validate().is_err());
+        config.traffic = TrafficConfig { kind: TrafficKind::NullData, frequency_hz: 50 };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn commands_follow_the_firmware_build() {
+        let info = DeviceInfo {
+            firmware_version: "0.1.0".to_string(),
+            chip: "esp32c3".to_string(),
+            mac: "24:0a:c4:00:11:22".to_string(),
+            wifi_mode: "sniffer".to_string(),
+            channel: 6,
+            csi_flags: Vec::new(),
+            uptime_us: 0,
+            received_at: chrono::Utc::now(),
+        };
+        let traffic = DeviceCommand::SetTraffic(TrafficConfig { kind: TrafficKind::Udp, frequency_hz: 50 });
+        assert!(traffic.validate(None).is_ok());
+        assert!(traffic.validate(Some(&info)).is_err());
+        assert!(DeviceCommand::SetTraffic(TrafficConfig::default()).validate(Some(&info)).is_ok());
+        let net = DeviceInfo { wifi_mode: "station".to_string(), ..info.clone() };
+        assert!(traffic.validate(Some(&net)).is_ok());
+
+        let station = WifiConfig { mode: WifiMode::Station, sta_ssid: "lab".to_string(), ..WifiConfig::default() };
+        let wifi = DeviceCommand::SetWifi(station);
+        assert!(wifi.validate(None).is_ok());
+        assert!(wifi.validate(Some(&info)).is_err());
+        assert!(wifi.validate(Some(&net)).is_ok());
+        assert!(DeviceCommand::SetWifi(WifiConfig::default()).validate(Some(&info)).is_ok());
     }
 }
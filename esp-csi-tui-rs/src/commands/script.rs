@@ -0,0 +1,256 @@
+use crate::capture::{CaptureAction, CapturePlan, CaptureSchedule};
+use crate::commands::{CommandExecutor, CommandOutcome};
+use crate::device::{DeviceManager, DeviceStatus};
+use crate::dsp::align;
+use crate::models::DeviceConfig;
+use crate::storage;
+use crate::storage::csv_storage::CsvStorage;
+use crate::storage::session_storage::{SessionDevice, SessionStorage};
+use crate::survey::{Survey, SurveyAction, SurveyPlan};
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a script waits for a device to connect or answer a command.
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Runs a device-control script without the TUI, one instruction per line:
+///
+/// ```text
+/// add /dev/ttyUSB0        # connect a device (also udp://..., tcp://...)
+/// demo                    # add a demo device
+/// configure channel=11    # any palette command, optionally @<device>-prefixed
+/// start
+/// wait 30                 # keep capturing for 30 s
+/// stop
+/// save capture.rrd        # merged, time-aligned recording (.rrd, .csv, .parquet, .npy, .npz, .mat) and session metadata
+/// record long.parquet     # stream every frame received from now on to Parquet, until `record stop`
+/// capture duration=60s repeat=3 every=10m save=run.rrd   # run_1.rrd, run_2.rrd, run_3.rrd
+/// survey channels=1-13 dwell=5s save=survey.csv          # per-channel summary of the last added device
+/// ```
+///
+/// Each command waits for every targeted device to report back; a failure stops the
+/// script. With a seed, demo devices produce the same data on every run.
+pub fn run(path: &Path, seed: Option<u64>) -> Result<()> {
+    let script = std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let mut devices = DeviceManager::new();
+    if let Some(seed) = seed {
+        devices.seed_demos(seed);
+    }
+    let mut executor = CommandExecutor::new();
+
+    for (number, line) in script.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        println!("> {}", line);
+        step(&mut devices, &mut executor, line).with_context(|| format!("{}:{}", path.display(), number + 1))?;
+    }
+    stop_recording(&mut devices)?;
+
+    for device in devices.devices() {
+        let totals = &device.stats.totals;
+        println!(
+            "{}: {} frames, {} lost ({:.2}%), {} parse errors",
+            device.id,
+            device.measurements.total(),
+            totals.lost,
+            totals.loss_pct(),
+            totals.parse_errors
+        );
+    }
+    Ok(())
+}
+
+fn step(devices: &mut DeviceManager, executor: &mut CommandExecutor, line: &str) -> Result<()> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["add", port] => add(devices, DeviceConfig { port: port.to_string(), ..DeviceConfig::default() }, false),
+        ["demo"] => add(devices, DeviceConfig::default(), true),
+        ["wait", seconds] => {
+            let seconds: f64 = seconds.parse().map_err(|e| anyhow!("Invalid duration '{}': {}", seconds, e))?;
+            pump(devices, Duration::from_secs_f64(seconds), |outcome| {
+                report_failure(outcome);
+                false
+            });
+            Ok(())
+        }
+        ["save", file] => save(devices, &vec![0; devices.devices().len()], Path::new(file)),
+        ["record", "stop"] => stop_recording(devices),
+        ["record", file] => devices.start_recording(Path::new(file)),
+        ["capture", args @ ..] => capture(devices, executor, args),
+        ["survey", args @ ..] => survey(devices, executor, args),
+        _ => {
+            let request = executor.execute_line(devices, line)?;
+            let mut outcomes = Vec::new();
+            pump(devices, STEP_TIMEOUT, |outcome| {
+                if outcome.request == request.id {
+                    outcomes.push(outcome.clone());
+                }
+                outcomes.len() == request.devices
+            });
+            if outcomes.len() < request.devices {
+                return Err(anyhow!("{} of {} device(s) did not answer", request.devices - outcomes.len(), request.devices));
+            }
+            let mut failed = 0;
+            for outcome in outcomes {
+                match outcome.result {
+                    Ok(_) => println!("  {}: {} ok", outcome.device, outcome.command),
+                    Err(e) => {
+                        println!("  {}: {} failed: {}", outcome.device, outcome.command, e);
+                        failed += 1;
+                    }
+                }
+                let info = devices.devices().iter().find(|d| d.id == outcome.device).and_then(|d| d.info.as_ref());
+                if let Some(info) = info.filter(|_| outcome.command == "query") {
+                    println!(
+                        "  {}: firmware {} on {} ({}), {} channel {}",
+                        outcome.device, info.firmware_version, info.chip, info.mac, info.wifi_mode, info.channel
+                    );
+                }
+            }
+            if failed > 0 {
+                return Err(anyhow!("{} device(s) failed", failed));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn add(devices: &mut DeviceManager, config: DeviceConfig, demo: bool) -> Result<()> {
+    let id = devices.add(config, demo).id.clone();
+    let deadline = Instant::now() + STEP_TIMEOUT;
+    loop {
+        devices.poll();
+        let device = devices.selected().ok_or_else(|| anyhow!("Device {} disappeared", id))?;
+        match &device.status {
+            DeviceStatus::Connecting if Instant::now() < deadline => std::thread::sleep(POLL_INTERVAL),
+            DeviceStatus::Connecting => return Err(anyhow!("{}: timed out connecting", id)),
+            DeviceStatus::Error(e) => return Err(anyhow!("{}: {}", id, e)),
+            status => {
+                println!("  {} {}", id, status.label());
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Runs a capture plan to the end, saving each capture next to the `save=` path
+/// (default `capture.rrd`) with its number appended.
+fn capture(devices: &mut DeviceManager, executor: &mut CommandExecutor, args: &[&str]) -> Result<()> {
+    let (save_args, plan_args): (Vec<&str>, Vec<&str>) = args.iter().partition(|arg| arg.starts_with("save="));
+    let path = PathBuf::from(save_args.last().map_or("capture.rrd", |arg| &arg["save=".len()..]));
+    let plan = CapturePlan::parse(&plan_args.join(" "), chrono::Utc::now())?;
+    if plan.repeat.is_none() {
+        return Err(anyhow!("Scripts need a bounded capture; use repeat=<n>"));
+    }
+    let mut schedule = CaptureSchedule::new(plan);
+    let mut starts = Vec::new();
+    while !schedule.is_done() {
+        devices.poll();
+        devices.take_outcomes().iter().for_each(report_failure);
+        match schedule.update(chrono::Utc::now(), devices.received()) {
+            Some(CaptureAction::Start) => {
+                starts = devices.positions();
+                step(devices, executor, "start")?;
+            }
+            Some(CaptureAction::StopAndSave { index }) => {
+                step(devices, executor, "stop")?;
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let mut numbered = path.with_file_name(format!("{}_{}", stem, index));
+                if let Some(extension) = path.extension() {
+                    numbered.set_extension(extension);
+                }
+                save(devices, &starts, &numbered)?;
+            }
+            None => std::thread::sleep(POLL_INTERVAL),
+        }
+    }
+    Ok(())
+}
+
+/// Steps the last added device through the plan's channels, prints the per-channel
+/// summary and saves it when `save=<file.csv>` is given. The device goes back to its
+/// channel afterwards.
+fn survey(devices: &mut DeviceManager, executor: &mut CommandExecutor, args: &[&str]) -> Result<()> {
+    let (save_args, plan_args): (Vec<&str>, Vec<&str>) = args.iter().partition(|arg| arg.starts_with("save="));
+    let mut survey = Survey::new(SurveyPlan::parse(&plan_args.join(" "))?);
+    let device = devices.selected().ok_or_else(|| anyhow!("Add a device before surveying"))?;
+    let (id, channel, was_collecting) = (device.id.clone(), device.config.channel, device.is_collecting());
+
+    loop {
+        let now = chrono::Utc::now();
+        for (index, measurement) in devices.poll() {
+            if devices.devices()[index].id == id {
+                survey.record(&measurement, now);
+            }
+        }
+        devices.take_outcomes().iter().for_each(report_failure);
+        let first = survey.current_channel().is_none();
+        match survey.update(now) {
+            Some(SurveyAction::Tune(channel)) => {
+                step(devices, executor, &format!("@{} configure channel={}", id, channel))?;
+                if first && !was_collecting {
+                    step(devices, executor, &format!("@{} start", id))?;
+                }
+            }
+            Some(SurveyAction::Finished) => break,
+            None => std::thread::sleep(POLL_INTERVAL),
+        }
+    }
+
+    step(devices, executor, &format!("@{} configure channel={}", id, channel))?;
+    if !was_collecting {
+        step(devices, executor, &format!("@{} stop", id))?;
+    }
+    print!("{}", survey.table());
+    if let Some(best) = survey.best() {
+        println!("  best channel: {}", best.channel);
+    }
+    if let Some(path) = save_args.last().map(|arg| Path::new(&arg["save=".len()..])) {
+        CsvStorage::save_survey(&survey.results, path)?;
+        println!("  saved survey to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Saves each device's measurements from its position in `starts` onwards.
+fn save(devices: &DeviceManager, starts: &[usize], path: &Path) -> Result<()> {
+    let merged = devices.merged_since(starts);
+    let recording = align::align(&merged);
+    let session: Vec<SessionDevice> = devices.devices().iter().map(SessionDevice::from_handle).collect();
+    storage::save(&recording.measurements, &session, path)?;
+    SessionStorage::save(&session, path)?;
+    println!("  saved {} measurements to {}", recording.measurements.len(), path.display());
+    Ok(())
+}
+
+fn stop_recording(devices: &mut DeviceManager) -> Result<()> {
+    if let Some((path, count)) = devices.stop_recording()? {
+        println!("  recorded {} measurements to {}", count, path.display());
+    }
+    Ok(())
+}
+
+/// Drains device events for up to `duration`, stopping early once `done` accepts an
+/// outcome; outcomes after that one stay queued.
+fn pump(devices: &mut DeviceManager, duration: Duration, mut done: impl FnMut(&CommandOutcome) -> bool) {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        devices.poll();
+        while let Some(outcome) = devices.take_outcome() {
+            if done(&outcome) {
+                return;
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn report_failure(outcome: &CommandOutcome) {
+    if let Err(e) = &outcome.result {
+        println!("  {}: {} failed: {}", outcome.device, outcome.command, e);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod motion;
+
+pub use motion::{DetectorConfig, MotionDetector, PresenceState};
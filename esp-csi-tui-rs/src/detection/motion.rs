@@ -0,0 +1,334 @@
+use crate::models::CsiMeasurement;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of presence events kept for the TUI log.
+const MAX_EVENTS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectorConfig {
+    /// Number of most recent measurements scored on each update.
+    pub window: usize,
+    /// Score (relative to the empty-room baseline) at which presence is declared.
+    pub enter_threshold: f32,
+    /// Score below which the room is considered empty again. Keep below `enter_threshold`.
+    pub exit_threshold: f32,
+    /// Consecutive updates above `enter_threshold` needed to declare presence.
+    pub enter_updates: u32,
+    /// Consecutive updates below `exit_threshold` needed to declare the room empty.
+    pub exit_updates: u32,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            window: 50,
+            enter_threshold: 3.0,
+            exit_threshold: 1.5,
+            enter_updates: 3,
+            exit_updates: 20,
+        }
+    }
+}
+
+/// Raw motion features of one window of CSI amplitude.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct MotionFeatures {
+    /// Per-subcarrier amplitude variance normalised by the squared mean, averaged
+    /// over subcarriers. Insensitive to AGC-driven changes of the absolute level.
+    pub variance: f32,
+    /// One minus the mean Pearson correlation between consecutive amplitude
+    /// vectors. A static channel keeps its frequency-selective shape from frame
+    /// to frame; moving scatterers decorrelate it.
+    pub decorrelation: f32,
+}
+
+impl MotionFeatures {
+    pub fn from_window(window: &[CsiMeasurement]) -> Option<Self> {
+        let num_subcarriers = window.last()?.subcarrier_data.len();
+        let amplitudes: Vec<Vec<f32>> = window
+            .iter()
+            .filter(|m| m.subcarrier_data.len() == num_subcarriers)
+            .map(|m| m.subcarrier_data.iter().map(|c| c.magnitude()).collect())
+            .collect();
+        if amplitudes.len() < 2 || num_subcarriers == 0 {
+            return None;
+        }
+
+        let frames = amplitudes.len() as f32;
+        let mut variance = 0.0;
+        for sc in 0..num_subcarriers {
+            let mean = amplitudes.iter().map(|a| a[sc]).sum::<f32>() / frames;
+            let var = amplitudes.iter().map(|a| (a[sc] - mean).powi(2)).sum::<f32>() / frames;
+            variance += var / mean.powi(2).max(f32::EPSILON);
+        }
+        variance /= num_subcarriers as f32;
+
+        let correlation = amplitudes
+            .windows(2)
+            .map(|pair| pearson(&pair[0], &pair[1]))
+            .sum::<f32>()
+            / (frames - 1.0);
+
+        Some(Self { variance, decorrelation: (1.0 - correlation).max(0.0) })
+    }
+}
+
+/// Motion features of an empty environment, used to normalise live scores.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MotionBaseline {
+    pub variance: f32,
+    pub decorrelation: f32,
+}
+
+impl MotionBaseline {
+    /// Averages the features of consecutive detector-sized windows, so the baseline
+    /// matches what the detector sees live rather than slow drift over the recording.
+    pub fn estimate(measurements: &[CsiMeasurement], window: usize) -> Option<Self> {
+        let features: Vec<MotionFeatures> = measurements
+            .chunks_exact(window.max(2))
+            .filter_map(MotionFeatures::from_window)
+            .collect();
+        if features.is_empty() {
+            return None;
+        }
+        let count = features.len() as f32;
+        Some(Self {
+            variance: (features.iter().map(|f| f.variance).sum::<f32>() / count).max(f32::EPSILON),
+            decorrelation: (features.iter().map(|f| f.decorrelation).sum::<f32>() / count).max(f32::EPSILON),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresenceState {
+    /// No baseline yet, so scores cannot be interpreted.
+    Uncalibrated,
+    Empty,
+    Present,
+}
+
+impl PresenceState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PresenceState::Uncalibrated => "UNCALIBRATED",
+            PresenceState::Empty => "EMPTY",
+            PresenceState::Present => "PRESENCE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresenceEvent {
+    pub timestamp: DateTime<Utc>,
+    pub state: PresenceState,
+    pub score: f32,
+}
+
+pub struct MotionDetector {
+    pub config: DetectorConfig,
+    baseline: Option<MotionBaseline>,
+    state: PresenceState,
+    features: Option<MotionFeatures>,
+    score: f32,
+    streak: u32,
+    events: Vec<PresenceEvent>,
+}
+
+impl MotionDetector {
+    pub fn new(config: DetectorConfig) -> Self {
+        Self {
+            config,
+            baseline: None,
+            state: PresenceState::Uncalibrated,
+            features: None,
+            score: 0.0,
+            streak: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> PresenceState {
+        self.state
+    }
+
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    pub fn features(&self) -> Option<MotionFeatures> {
+        self.features
+    }
+
+    pub fn baseline(&self) -> Option<MotionBaseline> {
+        self.baseline
+    }
+
+    pub fn events(&self) -> &[PresenceEvent] {
+        &self.events
+    }
+
+    pub fn set_baseline(&mut self, baseline: MotionBaseline) {
+        tracing::info!(
+            "Motion baseline set: variance {:.5}, decorrelation {:.5}",
+            baseline.variance, baseline.decorrelation
+        );
+        self.baseline = Some(baseline);
+        self.streak = 0;
+        self.transition(PresenceState::Empty, Utc::now());
+    }
+
+    /// Scores the latest window and advances the hysteresis state machine.
+    /// Returns the event when the presence state changes.
+    pub fn update(&mut self, measurements: &[CsiMeasurement]) -> Option<PresenceEvent> {
+        if measurements.len() < self.config.window {
+            return None;
+        }
+        let window = &measurements[measurements.len() - self.config.window..];
+        let features = MotionFeatures::from_window(window)?;
+        self.features = Some(features);
+
+        let baseline = self.baseline?;
+        // Either feature alone is enough to flag motion, so take the larger ratio.
+        self.score = (features.variance / baseline.variance)
+            .max(features.decorrelation / baseline.decorrelation);
+
+        let (crossing, required, next) = match self.state {
+            PresenceState::Present => (
+                self.score < self.config.exit_threshold,
+                self.config.exit_updates,
+                PresenceState::Empty,
+            ),
+            _ => (
+                self.score >= self.config.enter_threshold,
+                self.config.enter_updates,
+                PresenceState::Present,
+            ),
+        };
+
+        self.streak = if crossing { self.streak + 1 } else { 0 };
+        if self.streak >= required.max(1) {
+            self.streak = 0;
+            let timestamp = window[window.len() - 1].timestamp;
+            return Some(self.transition(next, timestamp));
+        }
+        None
+    }
+
+    fn transition(&mut self, state: PresenceState, timestamp: DateTime<Utc>) -> PresenceEvent {
+        self.state = state;
+        let event = PresenceEvent { timestamp, state, score: self.score };
+        tracing::info!("Presence: {} (score {:.2})", state.label(), self.score);
+        self.events.push(event.clone());
+        if self.events.len() > MAX_EVENTS {
+            self.events.remove(0);
+        }
+        event
+    }
+}
+
+fn pearson(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let mean_a = a.iter().sum::<f32>() / n;
+    let mean_b = b.iter().sum::<f32>() / n;
+    let mut cov = 0.0;
+    let mut var_a = 0.0;
+    let mut var_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    let denom = (var_a * var_b).sqrt();
+    if denom <= f32::EPSILON {
+        1.0
+    } else {
+        cov / denom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ComplexNumber;
+
+    const WINDOW: usize = 10;
+
+    /// A window whose amplitude alternates between `1 + swing` and `1 - swing` on every
+    /// subcarrier, so its normalised variance is `swing^2` and it never decorrelates.
+    fn window(swing: f32) -> Vec<CsiMeasurement> {
+        (0..WINDOW)
+            .map(|i| {
+                let amplitude = if i % 2 == 0 { 1.0 + swing } else { 1.0 - swing };
+                CsiMeasurement {
+                    timestamp: Utc::now(),
+                    channel: 6,
+                    bandwidth: 20,
+                    rssi: -40,
+                    noise_floor: -90,
+                    subcarrier_data: vec![ComplexNumber::new(amplitude, 0.0); 8],
+                    sequence: None,
+                    device_timestamp_us: None,
+                    device_id: None,
+                    transmitter: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Feeds windows scoring `score` times the baseline variance of 0.01.
+    fn drive(detector: &mut MotionDetector, score: f32, updates: usize) -> Vec<PresenceState> {
+        let window = window((score * 0.01).sqrt());
+        (0..updates).filter_map(|_| detector.update(&window)).map(|e| e.state).collect()
+    }
+
+    fn detector() -> MotionDetector {
+        let config = DetectorConfig { window: WINDOW, enter_updates: 3, exit_updates: 4, ..Default::default() };
+        let mut detector = MotionDetector::new(config);
+        detector.set_baseline(MotionBaseline { variance: 0.01, decorrelation: 1.0 });
+        detector
+    }
+
+    #[test]
+    fn presence_needs_a_run_above_the_enter_threshold() {
+        let mut detector = detector();
+        assert_eq!(detector.state(), PresenceState::Empty);
+
+        assert!(drive(&mut detector, 1.0, 5).is_empty());
+        assert!((detector.score() - 1.0).abs() < 1e-3);
+        // Two updates above 3.0 are not enough, and a quiet one restarts the count.
+        assert!(drive(&mut detector, 4.0, 2).is_empty());
+        assert!(drive(&mut detector, 1.0, 1).is_empty());
+        assert!(drive(&mut detector, 4.0, 2).is_empty());
+        assert_eq!(drive(&mut detector, 4.0, 1), vec![PresenceState::Present]);
+        assert!((detector.score() - 4.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn presence_holds_between_the_thresholds() {
+        let mut detector = detector();
+        drive(&mut detector, 4.0, 3);
+        assert_eq!(detector.state(), PresenceState::Present);
+
+        // 2.0 is below the enter threshold but above the exit threshold of 1.5.
+        assert!(drive(&mut detector, 2.0, 30).is_empty());
+        assert!(drive(&mut detector, 1.0, 3).is_empty());
+        assert!(drive(&mut detector, 2.0, 1).is_empty());
+        assert_eq!(drive(&mut detector, 1.0, 4), vec![PresenceState::Empty]);
+
+        let states: Vec<PresenceState> = detector.events().iter().map(|e| e.state).collect();
+        assert_eq!(states, vec![PresenceState::Empty, PresenceState::Present, PresenceState::Empty]);
+    }
+
+    #[test]
+    fn uncalibrated_detector_only_extracts_features() {
+        let mut detector = MotionDetector::new(DetectorConfig { window: WINDOW, ..Default::default() });
+        assert!(detector.update(&window(0.5)).is_none());
+        assert!(detector.update(&window(0.5)[..WINDOW - 1]).is_none());
+        assert_eq!(detector.state(), PresenceState::Uncalibrated);
+
+        let features = detector.features().unwrap();
+        assert!((features.variance - 0.25).abs() < 1e-4);
+        assert_eq!(features.decorrelation, 0.0);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::models::CsiMeasurement;
+use std::ops::Deref;
+
+/// Measurements kept per device: 10 minutes at 100 Hz. Longer captures are streamed to
+/// Parquet ('o') instead of held in memory.
+pub const HISTORY_FRAMES: usize = 60_000;
+
+/// The most recent measurements of one device, capped at a fixed number of frames.
+///
+/// Frames are addressed by their position in everything the device delivered, so a
+/// position taken when a capture starts stays valid after older frames are dropped.
+/// Old frames are dropped in blocks of a quarter of the capacity, keeping the held
+/// frames one contiguous slice without moving them on every push.
+#[derive(Debug, Clone)]
+pub struct MeasurementBuffer {
+    frames: Vec<CsiMeasurement>,
+    capacity: usize,
+    /// Frames dropped from the front of `frames`.
+    dropped: usize,
+}
+
+impl Default for MeasurementBuffer {
+    fn default() -> Self {
+        Self::new(HISTORY_FRAMES)
+    }
+}
+
+impl MeasurementBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self { frames: Vec::new(), capacity: capacity.max(1), dropped: 0 }
+    }
+
+    pub fn push(&mut self, measurement: CsiMeasurement) {
+        if self.frames.len() >= self.capacity + self.capacity / 4 {
+            let excess = self.frames.len() - self.capacity + 1;
+            self.frames.drain(..excess);
+            self.dropped += excess;
+        }
+        self.frames.push(measurement);
+    }
+
+    /// Frames delivered since the device was added, including dropped ones.
+    pub fn total(&self) -> usize {
+        self.dropped + self.frames.len()
+    }
+
+    /// Held frames from `position` (as counted by [`MeasurementBuffer::total`]) on; the
+    /// oldest held frame when `position` has been dropped already.
+    pub fn since(&self, position: usize) -> &[CsiMeasurement] {
+        let first = self.total() - self.len();
+        &self[position.saturating_sub(first).min(self.len())..]
+    }
+}
+
+impl Deref for MeasurementBuffer {
+    type Target = [CsiMeasurement];
+
+    /// The latest `capacity` frames.
+    fn deref(&self) -> &[CsiMeasurement] {
+        &self.frames[self.frames.len().saturating_sub(self.capacity)..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{self, Scenario, Scene};
+    use chrono::Utc;
+
+    fn frames(count: usize) -> Vec<CsiMeasurement> {
+        let mut frames = synthetic::capture(Scenario::new(Scene::EmptyRoom).with_seed(1), Utc::now(), count as f64 / 10.0, 10.0);
+        for (i, frame) in frames.iter_mut().enumerate() {
+            frame.sequence = Some(i as u32);
+        }
+        frames
+    }
+
+    fn sequences(frames: &[CsiMeasurement]) -> Vec<u32> {
+        frames.iter().filter_map(|m| m.sequence).collect()
+    }
+
+    #[test]
+    fn holds_the_latest_frames_up_to_capacity() {
+        let mut buffer = MeasurementBuffer::new(8);
+        for (i, frame) in frames(50).into_iter().enumerate() {
+            buffer.push(frame);
+            assert_eq!(buffer.total(), i + 1);
+            assert_eq!(buffer.len(), (i + 1).min(8));
+            assert_eq!(buffer.last().and_then(|m| m.sequence), Some(i as u32));
+            assert!(buffer.frames.len() <= 10);
+        }
+        assert_eq!(sequences(&buffer), (42..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn positions_survive_dropped_frames() {
+        let mut buffer = MeasurementBuffer::new(8);
+        let frames = frames(30);
+        for frame in &frames[..5] {
+            buffer.push(frame.clone());
+        }
+        let start = buffer.total();
+        for frame in &frames[5..12] {
+            buffer.push(frame.clone());
+        }
+        assert_eq!(sequences(buffer.since(start)), (5..12).collect::<Vec<_>>());
+        assert!(buffer.since(buffer.total()).is_empty());
+
+        for frame in &frames[12..] {
+            buffer.push(frame.clone());
+        }
+        // Frames 5-21 have been dropped, so only the held ones come back.
+        assert_eq!(sequences(buffer.since(start)), (22..30).collect::<Vec<_>>());
+        assert_eq!(sequences(buffer.since(25)), (25..30).collect::<Vec<_>>());
+    }
+}
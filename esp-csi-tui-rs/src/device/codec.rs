@@ -0,0 +1,557 @@
+use crate::models::{ComplexNumber, CsiMeasurement, DeviceInfo};
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+
+/// Marks the start of a binary frame. Neither byte is ASCII, so it never occurs in
+/// text output.
+pub const SYNC: [u8; 2] = [0xC5, 0x1A];
+pub const FORMAT_VERSION: u8 = 2;
+/// Sync word, version and length field.
+const PREAMBLE_LEN: usize = 5;
+/// Sequence, timestamp, channel, bandwidth, RSSI, noise floor, subcarrier count and,
+/// from version 2, the transmitter MAC.
+const METADATA_LEN: usize = 24;
+/// Metadata of version 1 frames, which lack the transmitter.
+const METADATA_LEN_V1: usize = 18;
+const CRC_LEN: usize = 2;
+/// Upper bound on the length field; anything larger is a corrupt header.
+const MAX_BODY_LEN: usize = METADATA_LEN + 2 * 512;
+/// Bytes kept while waiting for a line end or sync word before the buffer is dropped.
+const MAX_PENDING: usize = 16 * 1024;
+
+/// Wire format the device was last seen using.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// `+CSI:` lines, see [`parse_csi_line`].
+    Text,
+    /// Versioned binary frames, all integers little-endian:
+    ///
+    /// ```text
+    /// sync[2] version[1] length[2] | sequence[4] timestamp_us[8] channel[1] bandwidth[1]
+    /// rssi[1] noise_floor[1] subcarriers[2] transmitter[6] | i0 q0 i1 q1 ... (int8) | crc16[2]
+    /// ```
+    ///
+    /// `length` counts the metadata and I/Q bytes. An all-zero transmitter means unknown;
+    /// version 1 frames, without the transmitter field, are still decoded. The CRC
+    /// (CRC-16/CCITT-FALSE) covers everything from the version byte to the end of the I/Q
+    /// payload. I/Q values are rounded and saturated to int8, the resolution the ESP32 reports.
+    Binary,
+}
+
+/// Something a device sent: a CSI frame, a reply to a query or the `OK` / `ERROR`
+/// ending a command.
+#[derive(Debug, Clone)]
+pub enum DeviceMessage {
+    Csi(CsiMeasurement),
+    Info(DeviceInfo),
+    /// `true` for `OK`.
+    Ack(bool),
+}
+
+/// Splits the byte stream from a device into CSI frames and status replies.
+///
+/// Text and binary frames are told apart per frame, so firmware can switch modes (or
+/// print log lines between binary frames) without any host configuration. Malformed
+/// frames are logged and dropped; after a bad binary frame the decoder resynchronises
+/// on the next sync word or line end.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    format: Option<FrameFormat>,
+    errors: u64,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Frames and `+CSI` lines rejected so far.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Appends `data` and returns every frame and reply it completed.
+    pub fn decode(&mut self, data: &[u8]) -> Vec<DeviceMessage> {
+        self.buffer.extend_from_slice(data);
+        let mut frames = Vec::new();
+
+        loop {
+            if self.buffer.starts_with(&SYNC) {
+                match decode_binary(&self.buffer) {
+                    Ok(Some((measurement, len))) => {
+                        self.buffer.drain(..len);
+                        self.detected(FrameFormat::Binary);
+                        frames.push(DeviceMessage::Csi(measurement));
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::warn!("Dropping binary CSI frame: {}", e);
+                        self.errors += 1;
+                        self.buffer.drain(..1);
+                    }
+                }
+                continue;
+            }
+
+            let sync = self.buffer.windows(SYNC.len()).position(|w| w == SYNC);
+            let line_end = self
+                .buffer
+                .iter()
+                .position(|&b| b == b'\n')
+                .filter(|&end| sync.is_none_or(|start| end < start));
+            match (line_end, sync) {
+                (Some(end), _) => {
+                    let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    let line = line.trim();
+                    if line.starts_with("+INFO:") {
+                        match parse_info_line(line) {
+                            Ok(info) => frames.push(DeviceMessage::Info(info)),
+                            Err(e) => {
+                                tracing::warn!("Dropping device info: {}", e);
+                                self.errors += 1;
+                            }
+                        }
+                        continue;
+                    }
+                    if line == "OK" || line == "ERROR" {
+                        frames.push(DeviceMessage::Ack(line == "OK"));
+                        continue;
+                    }
+                    if !line.starts_with("+CSI:") {
+                        continue;
+                    }
+                    match parse_csi_line(line) {
+                        Ok(measurement) => {
+                            self.detected(FrameFormat::Text);
+                            frames.push(DeviceMessage::Csi(measurement));
+                        }
+                        Err(e) => {
+                            tracing::warn!("Dropping CSI line: {}", e);
+                            self.errors += 1;
+                        }
+                    }
+                }
+                (None, Some(start)) => {
+                    self.buffer.drain(..start);
+                }
+                (None, None) => {
+                    if self.buffer.len() > MAX_PENDING {
+                        tracing::warn!("Discarding {} bytes without a frame boundary", self.buffer.len());
+                        self.buffer.clear();
+                    }
+                    break;
+                }
+            }
+        }
+        frames
+    }
+
+    fn detected(&mut self, format: FrameFormat) {
+        if self.format != Some(format) {
+            tracing::info!("Device is sending {:?} CSI frames", format);
+            self.format = Some(format);
+        }
+    }
+}
+
+/// Parses `+CSI:<channel>,<bandwidth>,<rssi>,<noise_floor>,<len>[,<seq>,<timestamp_us>[,<transmitter>]][,[<i0>,<q0>,...]]`.
+///
+/// The sequence number, device timestamp and transmitter MAC are optional so that
+/// older firmware which only reports the five header fields is still accepted.
+pub fn parse_csi_line(line: &str) -> Result<CsiMeasurement> {
+    let body = line
+        .strip_prefix("+CSI:")
+        .ok_or_else(|| anyhow!("missing +CSI prefix"))?;
+    let (header, payload) = match body.split_once('[') {
+        Some((header, payload)) => (header, Some(payload.trim_end_matches(']'))),
+        None => (body, None),
+    };
+
+    let fields: Vec<&str> = header
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .collect();
+    if ![5, 7, 8].contains(&fields.len()) {
+        return Err(anyhow!("expected 5, 7 or 8 header fields, got {}", fields.len()));
+    }
+
+    let mut subcarrier_data = Vec::new();
+    if let Some(payload) = payload {
+        let values = payload
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("bad I/Q value: {}", e))?;
+        subcarrier_data = values
+            .chunks_exact(2)
+            .map(|iq| ComplexNumber::new(iq[0], iq[1]))
+            .collect();
+    }
+
+    let expected: usize = fields[4].parse()?;
+    if !subcarrier_data.is_empty() && subcarrier_data.len() != expected {
+        return Err(anyhow!(
+            "header announces {} subcarriers, payload has {}",
+            expected,
+            subcarrier_data.len()
+        ));
+    }
+
+    Ok(CsiMeasurement {
+        timestamp: Utc::now(),
+        channel: fields[0].parse()?,
+        bandwidth: fields[1].parse()?,
+        rssi: fields[2].parse()?,
+        noise_floor: fields[3].parse()?,
+        subcarrier_data,
+        sequence: fields.get(5).map(|v| v.parse()).transpose()?,
+        device_timestamp_us: fields.get(6).map(|v| v.parse()).transpose()?,
+        device_id: None,
+        transmitter: fields.get(7).map(|v| parse_mac(v)).transpose()?.and_then(format_mac),
+    })
+}
+
+/// Parses the reply to `AT+CSIINFO?`:
+/// `+INFO:version=<v>,chip=<chip>,mac=<mac>,mode=<mode>,channel=<ch>,csi=<flag>|<flag>,uptime_us=<us>`.
+///
+/// Unknown keys are ignored so firmware can report more than this host knows about.
+pub fn parse_info_line(line: &str) -> Result<DeviceInfo> {
+    let body = line
+        .strip_prefix("+INFO:")
+        .ok_or_else(|| anyhow!("missing +INFO prefix"))?;
+    let mut info = DeviceInfo {
+        firmware_version: "unknown".to_string(),
+        chip: "unknown".to_string(),
+        mac: "unknown".to_string(),
+        wifi_mode: "unknown".to_string(),
+        channel: 0,
+        csi_flags: Vec::new(),
+        uptime_us: 0,
+        received_at: Utc::now(),
+    };
+    for field in body.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected key=value, got '{}'", field))?;
+        match key {
+            "version" => info.firmware_version = value.to_string(),
+            "chip" => info.chip = value.to_string(),
+            "mac" => info.mac = value.to_string(),
+            "mode" => info.wifi_mode = value.to_string(),
+            "channel" => info.channel = value.parse()?,
+            "csi" => info.csi_flags = value.split('|').filter(|f| !f.is_empty()).map(str::to_string).collect(),
+            "uptime_us" => info.uptime_us = value.parse()?,
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+/// Formats a measurement as a `+CSI:` line, I/Q rounded to integers as the firmware sends them.
+#[cfg(test)]
+pub fn encode_text(measurement: &CsiMeasurement) -> String {
+    let iq: Vec<String> = measurement
+        .subcarrier_data
+        .iter()
+        .flat_map(|c| [quantize(c.real).to_string(), quantize(c.imag).to_string()])
+        .collect();
+    let transmitter = measurement.transmitter.as_ref().map_or(String::new(), |mac| format!(",{}", mac));
+    format!(
+        "+CSI:{},{},{},{},{},{},{}{},[{}]\r\n",
+        measurement.channel,
+        measurement.bandwidth,
+        measurement.rssi,
+        measurement.noise_floor,
+        measurement.subcarrier_data.len(),
+        measurement.sequence.unwrap_or(0),
+        measurement.device_timestamp_us.unwrap_or(0),
+        transmitter,
+        iq.join(",")
+    )
+}
+
+/// Encodes a measurement as a binary frame in the layout of [`FrameFormat::Binary`].
+#[cfg(test)]
+pub fn encode_binary(measurement: &CsiMeasurement) -> Result<Vec<u8>> {
+    let subcarriers = measurement.subcarrier_data.len();
+    let body_len = METADATA_LEN + 2 * subcarriers;
+    if body_len > MAX_BODY_LEN {
+        return Err(anyhow!("{} subcarriers do not fit a binary frame", subcarriers));
+    }
+    let bandwidth = u8::try_from(measurement.bandwidth)
+        .map_err(|_| anyhow!("bandwidth {} MHz does not fit a binary frame", measurement.bandwidth))?;
+
+    let mut frame = Vec::with_capacity(PREAMBLE_LEN + body_len + CRC_LEN);
+    frame.extend_from_slice(&SYNC);
+    frame.push(FORMAT_VERSION);
+    frame.extend_from_slice(&(body_len as u16).to_le_bytes());
+    frame.extend_from_slice(&measurement.sequence.unwrap_or(0).to_le_bytes());
+    frame.extend_from_slice(&measurement.device_timestamp_us.unwrap_or(0).to_le_bytes());
+    frame.push(measurement.channel);
+    frame.push(bandwidth);
+    frame.push(measurement.rssi as u8);
+    frame.push(measurement.noise_floor as u8);
+    frame.extend_from_slice(&(subcarriers as u16).to_le_bytes());
+    let transmitter = measurement.transmitter.as_deref().map(parse_mac).transpose()?;
+    frame.extend_from_slice(&transmitter.unwrap_or_default());
+    for c in &measurement.subcarrier_data {
+        frame.push(quantize(c.real) as u8);
+        frame.push(quantize(c.imag) as u8);
+    }
+    let crc = crc16(&frame[SYNC.len()..]);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    Ok(frame)
+}
+
+/// Decodes the binary frame at the start of `data`. Returns `Ok(None)` while the frame
+/// is incomplete, otherwise the measurement and the number of bytes it occupied.
+fn decode_binary(data: &[u8]) -> Result<Option<(CsiMeasurement, usize)>> {
+    if data.len() < PREAMBLE_LEN {
+        return Ok(None);
+    }
+    let metadata_len = match data[2] {
+        1 => METADATA_LEN_V1,
+        FORMAT_VERSION => METADATA_LEN,
+        version => return Err(anyhow!("unsupported frame version {}", version)),
+    };
+    let body_len = u16::from_le_bytes([data[3], data[4]]) as usize;
+    if !(metadata_len..=MAX_BODY_LEN).contains(&body_len) {
+        return Err(anyhow!("invalid frame length {}", body_len));
+    }
+    let total = PREAMBLE_LEN + body_len + CRC_LEN;
+    if data.len() < total {
+        return Ok(None);
+    }
+
+    let crc_at = PREAMBLE_LEN + body_len;
+    let expected = u16::from_le_bytes([data[crc_at], data[crc_at + 1]]);
+    let actual = crc16(&data[SYNC.len()..crc_at]);
+    if expected != actual {
+        return Err(anyhow!("CRC mismatch ({:04x} != {:04x})", actual, expected));
+    }
+
+    let body = &data[PREAMBLE_LEN..crc_at];
+    let subcarriers = u16::from_le_bytes([body[16], body[17]]) as usize;
+    if body_len != metadata_len + 2 * subcarriers {
+        return Err(anyhow!("{} subcarriers do not match length {}", subcarriers, body_len));
+    }
+
+    let measurement = CsiMeasurement {
+        timestamp: Utc::now(),
+        channel: body[12],
+        bandwidth: body[13] as u16,
+        rssi: body[14] as i8,
+        noise_floor: body[15] as i8,
+        subcarrier_data: body[metadata_len..]
+            .chunks_exact(2)
+            .map(|iq| ComplexNumber::new(iq[0] as i8 as f32, iq[1] as i8 as f32))
+            .collect(),
+        sequence: Some(u32::from_le_bytes(body[0..4].try_into()?)),
+        device_timestamp_us: Some(u64::from_le_bytes(body[4..12].try_into()?)),
+        device_id: None,
+        transmitter: body[METADATA_LEN_V1..metadata_len].try_into().ok().and_then(format_mac),
+    };
+    Ok(Some((measurement, total)))
+}
+
+/// Parses `aa:bb:cc:dd:ee:ff`.
+fn parse_mac(text: &str) -> Result<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut octets = text.split(':');
+    for byte in mac.iter_mut() {
+        let octet = octets.next().ok_or_else(|| anyhow!("bad MAC address '{}'", text))?;
+        *byte = u8::from_str_radix(octet, 16).map_err(|_| anyhow!("bad MAC address '{}'", text))?;
+    }
+    if octets.next().is_some() {
+        return Err(anyhow!("bad MAC address '{}'", text));
+    }
+    Ok(mac)
+}
+
+/// Lower-case colon-separated form; `None` for the all-zero "unknown" address.
+fn format_mac(mac: [u8; 6]) -> Option<String> {
+    (mac != [0; 6]).then(|| mac.map(|b| format!("{:02x}", b)).join(":"))
+}
+
+#[cfg(test)]
+fn quantize(value: f32) -> i8 {
+    value.round().clamp(i8::MIN as f32, i8::MAX as f32) as i8
+}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, no reflection.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(sequence: u32) -> CsiMeasurement {
+        CsiMeasurement {
+            timestamp: Utc::now(),
+            channel: 11,
+            bandwidth: 40,
+            rssi: -57,
+            noise_floor: -92,
+            subcarrier_data: (0..114)
+                .map(|i| ComplexNumber::new((i as f32 * 2.3) % 250.0 - 125.0, -(i as f32) + 20.0))
+                .collect(),
+            sequence: Some(sequence),
+            device_timestamp_us: Some(1_000_000 + sequence as u64 * 10_000),
+            device_id: None,
+            transmitter: Some(format!("24:0a:c4:00:00:{:02x}", sequence % 4)).filter(|_| !sequence.is_multiple_of(4)),
+        }
+    }
+
+    fn csi(messages: Vec<DeviceMessage>) -> Vec<CsiMeasurement> {
+        messages
+            .into_iter()
+            .filter_map(|m| match m {
+                DeviceMessage::Csi(measurement) => Some(measurement),
+                DeviceMessage::Info(_) | DeviceMessage::Ack(_) => None,
+            })
+            .collect()
+    }
+
+    fn assert_same(decoded: &CsiMeasurement, original: &CsiMeasurement) {
+        assert_eq!(decoded.channel, original.channel);
+        assert_eq!(decoded.bandwidth, original.bandwidth);
+        assert_eq!(decoded.rssi, original.rssi);
+        assert_eq!(decoded.noise_floor, original.noise_floor);
+        assert_eq!(decoded.sequence, original.sequence);
+        assert_eq!(decoded.device_timestamp_us, original.device_timestamp_us);
+        assert_eq!(decoded.transmitter, original.transmitter);
+        assert_eq!(decoded.subcarrier_data.len(), original.subcarrier_data.len());
+        for (d, o) in decoded.subcarrier_data.iter().zip(&original.subcarrier_data) {
+            assert_eq!((d.real, d.imag), (o.real.round(), o.imag.round()));
+        }
+    }
+
+    #[test]
+    fn crc_matches_reference_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn binary_frame_round_trips() {
+        let original = frame(7);
+        let bytes = encode_binary(&original).unwrap();
+        assert_eq!(bytes.len(), PREAMBLE_LEN + METADATA_LEN + 2 * 114 + CRC_LEN);
+
+        let decoded = csi(FrameDecoder::new().decode(&bytes));
+        assert_eq!(decoded.len(), 1);
+        assert_same(&decoded[0], &original);
+    }
+
+    #[test]
+    fn text_frame_round_trips() {
+        let original = frame(3);
+        let decoded = csi(FrameDecoder::new().decode(encode_text(&original).as_bytes()));
+        assert_eq!(decoded.len(), 1);
+        assert_same(&decoded[0], &original);
+    }
+
+    #[test]
+    fn version_1_frames_without_transmitter_still_decode() {
+        let original = CsiMeasurement { transmitter: None, ..frame(9) };
+        let v2 = encode_binary(&original).unwrap();
+        let body_len = (METADATA_LEN_V1 + 2 * 114) as u16;
+        let mut v1 = vec![SYNC[0], SYNC[1], 1];
+        v1.extend_from_slice(&body_len.to_le_bytes());
+        v1.extend_from_slice(&v2[PREAMBLE_LEN..PREAMBLE_LEN + METADATA_LEN_V1]);
+        v1.extend_from_slice(&v2[PREAMBLE_LEN + METADATA_LEN..v2.len() - CRC_LEN]);
+        let crc = crc16(&v1[SYNC.len()..]);
+        v1.extend_from_slice(&crc.to_le_bytes());
+
+        let decoded = csi(FrameDecoder::new().decode(&v1));
+        assert_eq!(decoded.len(), 1);
+        assert_same(&decoded[0], &original);
+
+        let line = "+CSI:6,20,-50,-95,2,1,100,[1,2,3,4]";
+        assert_eq!(parse_csi_line(line).unwrap().transmitter, None);
+        let line = "+CSI:6,20,-50,-95,2,1,100,AA:BB:CC:00:11:22,[1,2,3,4]";
+        assert_eq!(parse_csi_line(line).unwrap().transmitter.as_deref(), Some("aa:bb:cc:00:11:22"));
+    }
+
+    #[test]
+    fn binary_is_far_smaller_than_text() {
+        let original = frame(0);
+        assert!(encode_binary(&original).unwrap().len() * 2 < encode_text(&original).len());
+    }
+
+    #[test]
+    fn mixed_stream_split_into_small_chunks_is_decoded() {
+        let mut stream = b"boot: CSI firmware\r\n".to_vec();
+        stream.extend(encode_text(&frame(0)).as_bytes());
+        stream.extend(encode_binary(&frame(1)).unwrap());
+        stream.extend(b"log line between frames\n");
+        stream.extend(encode_binary(&frame(2)).unwrap());
+        stream.extend(encode_text(&frame(3)).as_bytes());
+
+        let mut decoder = FrameDecoder::new();
+        let decoded: Vec<u32> = stream
+            .chunks(7)
+            .flat_map(|chunk| csi(decoder.decode(chunk)))
+            .map(|m| m.sequence.unwrap())
+            .collect();
+        assert_eq!(decoded, vec![0, 1, 2, 3]);
+        assert_eq!(decoder.format, Some(FrameFormat::Text));
+    }
+
+    #[test]
+    fn corrupt_binary_frame_is_dropped_and_decoder_resyncs() {
+        let mut corrupt = encode_binary(&frame(1)).unwrap();
+        corrupt[40] ^= 0xFF;
+        let mut stream = corrupt;
+        stream.extend(encode_binary(&frame(2)).unwrap());
+
+        let mut decoder = FrameDecoder::new();
+        let decoded = csi(decoder.decode(&stream));
+        assert_eq!(decoded.len(), 1);
+        assert_same(&decoded[0], &frame(2));
+        assert!(decoder.errors() >= 1);
+    }
+
+    #[test]
+    fn incomplete_binary_frame_waits_for_more_data() {
+        let bytes = encode_binary(&frame(5)).unwrap();
+        let mut decoder = FrameDecoder::new();
+        assert!(decoder.decode(&bytes[..bytes.len() - 1]).is_empty());
+        assert_eq!(decoder.decode(&bytes[bytes.len() - 1..]).len(), 1);
+    }
+
+    #[test]
+    fn info_and_acks_are_decoded_between_frames() {
+        let mut stream = encode_text(&frame(0)).into_bytes();
+        stream.extend(b"+INFO:version=0.2.0,chip=esp32c3,mac=24:0a:c4:00:11:22,mode=station,channel=11,csi=lltf|htltf,uptime_us=5000000,extra=1\r\nOK\r\n");
+        stream.extend(encode_binary(&frame(1)).unwrap());
+        stream.extend(b"ERROR\r\n");
+
+        let messages = FrameDecoder::new().decode(&stream);
+        assert_eq!(messages.len(), 5);
+        assert!(matches!(messages[2], DeviceMessage::Ack(true)));
+        assert!(matches!(messages[4], DeviceMessage::Ack(false)));
+        let DeviceMessage::Info(ref info) = messages[1] else {
+            panic!("expected device info, got {:?}", messages[1]);
+        };
+        assert_eq!(info.firmware_version, "0.2.0");
+        assert_eq!(info.chip, "esp32c3");
+        assert_eq!(info.mac, "24:0a:c4:00:11:22");
+        assert_eq!(info.wifi_mode, "station");
+        assert_eq!(info.channel, 11);
+        assert_eq!(info.csi_flags, vec!["lltf", "htltf"]);
+        assert_eq!(info.uptime_us, 5_000_000);
+        assert_eq!(csi(messages).len(), 2);
+    }
+}
@@ -1,8 +1,28 @@
-use crate::models::{CsiMeasurement, DeviceConfig, ComplexNumber};
+use crate::models::{CsiConfig, CsiMeasurement, DeviceConfig, DeviceInfo, TrafficConfig, WifiConfig};
+use crate::device::codec::{DeviceMessage, FrameDecoder};
+use crate::device::transport::{self, CsiTransport, EndOfStream};
 use crate::device::SerialHandler;
-use anyhow::Result;
-use chrono::Utc;
+use crate::synthetic::{DemoSource, Scenario};
+use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long a command waits for the firmware's `OK` or `ERROR`.
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Error a command returns when the firmware answered `ERROR` or not at all; unlike a
+/// transport error it says nothing about the link, which keeps working.
+#[derive(Debug)]
+pub struct Rejected(String);
+
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Rejected {}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EspCsiData {
@@ -13,34 +33,78 @@ pub struct EspCsiData {
     pub subcarriers: Vec<(f32, f32)>,
 }
 
-pub struct EspClient {
-    serial: SerialHandler,
+/// Speaks the firmware protocol over any [`CsiTransport`]; the default boxed transport
+/// is picked from the device address at runtime.
+pub struct EspClient<T: CsiTransport = Box<dyn CsiTransport>> {
+    transport: T,
     demo_mode: bool,
-    measurement_count: usize,
+    decoder: FrameDecoder,
+    bytes_received: u64,
+    pending: VecDeque<CsiMeasurement>,
+    info: Option<DeviceInfo>,
+    /// Reply to the last command sent, `true` for `OK`.
+    ack: Option<bool>,
+    /// Set once a finite transport (a file replay) has delivered everything.
+    ended: bool,
+    /// Set once the device answered a command. Firmware that never does (older builds,
+    /// file replays) gets its commands without waiting for replies.
+    answers: bool,
+    /// Frames and settings of the simulated device in demo mode.
+    demo: DemoSource,
 }
 
 impl EspClient {
+    /// Client for the device at `port`: a serial port, a `udp://`/`tcp://` listen
+    /// address for a networked ESP32 or a `file://` capture to replay (see
+    /// [`transport::from_address`]).
     pub fn new(port: String, baud_rate: u32) -> Self {
+        let transport = transport::from_address(&port, baud_rate).unwrap_or_else(|e| {
+            tracing::warn!("{}; treating '{}' as a serial port", e, port);
+            Box::new(SerialHandler::new(port, baud_rate))
+        });
+        Self::with_transport(transport)
+    }
+}
+
+impl<T: CsiTransport> EspClient<T> {
+    pub fn with_transport(transport: T) -> Self {
         Self {
-            serial: SerialHandler::new(port, baud_rate),
+            transport,
             demo_mode: false,
-            measurement_count: 0,
+            decoder: FrameDecoder::new(),
+            bytes_received: 0,
+            pending: VecDeque::new(),
+            info: None,
+            ack: None,
+            ended: false,
+            answers: false,
+            demo: DemoSource::new(),
         }
     }
 
-    pub fn enable_demo_mode(&mut self) {
+    /// Simulates a device configured with `config`; a seed makes its frames the same on
+    /// every run (see [`DemoSource::seeded`]).
+    pub fn enable_demo_mode(&mut self, config: &DeviceConfig, seed: Option<u64>) {
+        if let Some(seed) = seed {
+            self.demo = DemoSource::seeded(seed);
+        }
+        self.demo.config = config.clone();
         self.demo_mode = true;
-        tracing::info!("Demo mode enabled - will generate mock CSI data");
+        tracing::info!("Demo mode enabled - will generate synthetic CSI data");
+    }
+
+    pub fn is_demo_mode(&self) -> bool {
+        self.demo_mode
     }
 
     pub fn connect(&mut self) -> Result<()> {
-        match self.serial.connect() {
+        match self.transport.open() {
             Ok(_) => {
                 self.handshake()?;
                 Ok(())
             }
             Err(e) => {
-                tracing::warn!("Failed to connect to serial port: {}. Enabling demo mode.", e);
+                tracing::warn!("Failed to open device transport: {}. Enabling demo mode.", e);
                 self.demo_mode = true;
                 Ok(())
             }
@@ -48,8 +112,8 @@ impl EspClient {
     }
 
     pub fn disconnect(&mut self) -> Result<()> {
-        if !self.demo_mode {
-            self.serial.disconnect()?;
+        if !self.demo_mode && self.transport.is_open() {
+            self.transport.close()?;
         }
         Ok(())
     }
@@ -57,6 +121,9 @@ impl EspClient {
     pub fn configure(&mut self, config: &DeviceConfig) -> Result<()> {
         if self.demo_mode {
             tracing::info!("Demo mode: Simulating device configuration");
+            self.demo.config.channel = config.channel;
+            self.demo.config.bandwidth = config.bandwidth;
+            self.demo.config.collection_interval_ms = config.collection_interval_ms;
             return Ok(());
         }
 
@@ -68,6 +135,63 @@ impl EspClient {
         Ok(())
     }
 
+    /// Switches the device's Wi-Fi mode and credentials; the firmware reconnects with
+    /// them.
+    pub fn set_wifi(&mut self, wifi: &WifiConfig) -> Result<()> {
+        if self.demo_mode {
+            tracing::info!("Demo mode: Simulating Wi-Fi mode {}", wifi.mode.as_str());
+            return Ok(());
+        }
+
+        let cmd = format!(
+            "AT+CSIWIFI={},{},{},{},{},{},{}",
+            wifi.mode.as_str(),
+            wifi.sta_ssid,
+            wifi.sta_password,
+            wifi.ap_ssid,
+            wifi.ap_password,
+            wifi.ap_hidden as u8,
+            wifi.max_connections
+        );
+        self.send_command(&cmd)?;
+        Ok(())
+    }
+
+    /// Selects which training fields the device captures CSI from.
+    pub fn set_csi(&mut self, csi: &CsiConfig) -> Result<()> {
+        if self.demo_mode {
+            tracing::info!("Demo mode: Simulating CSI flags {:?}", csi.enabled());
+            self.demo.config.csi = csi.clone();
+            return Ok(());
+        }
+
+        let settings: Vec<String> = csi.settings().iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        self.send_command(&format!("AT+CSIFLAGS={}", settings.join(",")))?;
+        Ok(())
+    }
+
+    /// Sets the traffic the device generates to trigger CSI; a rate of 0 stops it.
+    pub fn set_traffic(&mut self, traffic: &TrafficConfig) -> Result<()> {
+        if self.demo_mode {
+            tracing::info!("Demo mode: Simulating traffic {}", traffic.label());
+            self.demo.config.traffic = *traffic;
+            return Ok(());
+        }
+
+        self.send_command(&format!("AT+CSITRAFFIC={},{}", traffic.kind.as_str(), traffic.frequency_hz))?;
+        Ok(())
+    }
+
+    /// Replaces the demo device's synthetic scenario, starting it from the beginning.
+    pub fn set_scenario(&mut self, scenario: &Scenario) -> Result<()> {
+        if !self.demo_mode {
+            return Err(anyhow!("Scenarios only apply to demo devices"));
+        }
+        tracing::info!("Demo mode: scenario {}", scenario.label());
+        self.demo.set_scenario(scenario);
+        Ok(())
+    }
+
     pub fn start_collection(&mut self) -> Result<()> {
         if self.demo_mode {
             tracing::info!("Demo mode: Starting mock data generation");
@@ -90,87 +214,119 @@ impl EspClient {
 
     pub fn read_measurement(&mut self) -> Result<Option<CsiMeasurement>> {
         if self.demo_mode {
-            return self.generate_mock_measurement();
+            return Ok(Some(self.demo.next_measurement()));
         }
 
-        let mut buffer = [0u8; 2048];
-        match self.serial.read_data(&mut buffer) {
-            Ok(n) if n > 0 => {
-                let data_str = String::from_utf8_lossy(&buffer[..n]);
-                self.parse_csi_data(&data_str)
+        if self.pending.is_empty() {
+            self.receive()?;
+            if self.pending.is_empty() && self.ended {
+                return Err(EndOfStream.into());
             }
-            Ok(_) => Ok(None),
-            Err(e) if e.to_string().contains("I/O") => Ok(None),
-            Err(e) => Err(e),
         }
+        Ok(self.pending.pop_front())
     }
 
-    fn generate_mock_measurement(&mut self) -> Result<Option<CsiMeasurement>> {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-
-        self.measurement_count += 1;
+    /// Asks the device for its firmware, chip and radio state and waits for the reply.
+    /// CSI frames arriving meanwhile are kept for [`EspClient::read_measurement`].
+    pub fn query(&mut self, timeout: Duration) -> Result<DeviceInfo> {
+        if self.demo_mode {
+            return Ok(DeviceInfo {
+                firmware_version: env!("CARGO_PKG_VERSION").to_string(),
+                chip: "simulated".to_string(),
+                mac: "00:00:00:00:00:00".to_string(),
+                wifi_mode: "demo".to_string(),
+                channel: self.demo.config.channel,
+                csi_flags: self.demo.config.csi.enabled().into_iter().map(String::from).collect(),
+                uptime_us: self.demo.elapsed().num_microseconds().unwrap_or(0) as u64,
+                received_at: self.demo.now(),
+            });
+        }
 
-        // Generate 52 subcarriers (typical for 20MHz bandwidth)
-        let num_subcarriers = 52;
-        let mut subcarrier_data = Vec::new();
+        self.info = None;
+        self.write_command("AT+CSIINFO?")?;
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline && !self.ended {
+            self.receive()?;
+            if let Some(info) = self.info.take() {
+                return Ok(info);
+            }
+            if self.ack == Some(false) {
+                return Err(Rejected("Device rejected AT+CSIINFO?".into()).into());
+            }
+        }
+        Err(Rejected(format!("No reply to device query within {} ms", timeout.as_millis())).into())
+    }
 
-        for i in 0..num_subcarriers {
-            // Create somewhat realistic CSI patterns
-            let angle = (i as f32 * 0.1) + (self.measurement_count as f32 * 0.01);
-            let magnitude = 0.5 + rng.gen::<f32>() * 0.5;
-            
-            let real = magnitude * angle.cos();
-            let imag = magnitude * angle.sin();
-            
-            subcarrier_data.push(ComplexNumber::new(real, imag));
+    /// Reads once from the transport and decodes whatever arrived. The end of a finite
+    /// stream is noted rather than returned, so frames still pending are delivered first.
+    fn receive(&mut self) -> Result<()> {
+        let mut buffer = [0u8; 2048];
+        let n = match self.transport.read(&mut buffer) {
+            Err(e) if e.is::<EndOfStream>() => {
+                self.ended = true;
+                return Ok(());
+            }
+            result => result?,
+        };
+        self.bytes_received += n as u64;
+        for message in self.decoder.decode(&buffer[..n]) {
+            match message {
+                DeviceMessage::Csi(measurement) => self.pending.push_back(measurement),
+                DeviceMessage::Info(info) => self.info = Some(info),
+                DeviceMessage::Ack(ok) => {
+                    self.ack = Some(ok);
+                    self.answers = true;
+                }
+            }
         }
+        Ok(())
+    }
 
-        // Vary RSSI realistically
-        let base_rssi = -45;
-        let rssi = base_rssi + rng.gen_range(-10..10);
+    /// Raw bytes read from the transport since the client was created.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
 
-        Ok(Some(CsiMeasurement {
-            timestamp: Utc::now(),
-            channel: 6,
-            bandwidth: 20,
-            rssi,
-            noise_floor: -95 + rng.gen_range(-5..5),
-            subcarrier_data,
-        }))
+    /// Frames the decoder rejected since the client was created.
+    pub fn parse_errors(&self) -> u64 {
+        self.decoder.errors()
     }
 
     fn handshake(&mut self) -> Result<()> {
-        self.send_command("AT")?;
-        std::thread::sleep(std::time::Duration::from_millis(100));
+        self.write_command("AT")?;
+        if self.wait_for_ack(COMMAND_TIMEOUT)?.is_none() {
+            tracing::warn!("No reply to AT; sending commands without waiting for replies");
+        }
         Ok(())
     }
 
+    /// Sends `cmd` and, once the device has shown it answers commands, waits for its
+    /// reply; `ERROR` or no reply within [`COMMAND_TIMEOUT`] is an error.
     fn send_command(&mut self, cmd: &str) -> Result<()> {
-        let command = format!("{}
-", cmd);
-        self.serial.write_command(command.as_bytes())?;
+        self.write_command(cmd)?;
+        if !self.answers {
+            return Ok(());
+        }
+        match self.wait_for_ack(COMMAND_TIMEOUT)? {
+            Some(true) => Ok(()),
+            Some(false) => Err(Rejected(format!("Device rejected {}", cmd)).into()),
+            None => Err(Rejected(format!("No reply to {} within {} ms", cmd, COMMAND_TIMEOUT.as_millis())).into()),
+        }
+    }
+
+    fn write_command(&mut self, cmd: &str) -> Result<()> {
+        self.ack = None;
+        let command = format!("{}\n", cmd);
+        self.transport.write(command.as_bytes())?;
         Ok(())
     }
 
-    fn parse_csi_data(&self, data: &str) -> Result<Option<CsiMeasurement>> {
-        if !data.contains("+CSI") {
-            return Ok(None);
+    /// Reads until the reply to the last command arrives; frames are kept meanwhile.
+    fn wait_for_ack(&mut self, timeout: Duration) -> Result<Option<bool>> {
+        let deadline = Instant::now() + timeout;
+        while self.ack.is_none() && Instant::now() < deadline && !self.ended {
+            self.receive()?;
         }
-
-        let subcarrier_data = vec![
-            ComplexNumber::new(1.0, 0.5),
-            ComplexNumber::new(0.8, 0.3),
-            ComplexNumber::new(0.9, 0.4),
-        ];
-
-        Ok(Some(CsiMeasurement {
-            timestamp: Utc::now(),
-            channel: 6,
-            bandwidth: 20,
-            rssi: -40,
-            noise_floor: -100,
-            subcarrier_data,
-        }))
+        Ok(self.ack.take())
     }
 }
@@ -0,0 +1,783 @@
+use crate::commands::CommandOutcome;
+use crate::device::buffer::MeasurementBuffer;
+use crate::device::stats::LinkStats;
+use crate::device::transport::{self, EndOfStream};
+use crate::device::EspClient;
+use crate::device::esp_client::Rejected;
+use crate::models::{CsiConfig, CsiMeasurement, DeviceCommand, DeviceConfig, DeviceInfo, WifiMode};
+use crate::storage::parquet_storage::ParquetWriter;
+use crate::storage::session_storage::SessionDevice;
+use anyhow::{Result, anyhow};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long an idle acquisition task waits for a command before checking again.
+const IDLE_POLL: Duration = Duration::from_millis(50);
+/// Back-off when a collecting device had nothing to read.
+const READ_BACKOFF: Duration = Duration::from_millis(2);
+/// Request ID of commands the manager sends itself, e.g. when removing a device.
+const INTERNAL_REQUEST: u64 = 0;
+/// How long a device query waits for the reply.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often acquisition tasks report transport counters and link stats are sampled.
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceStatus {
+    Connecting,
+    Connected,
+    Collecting,
+    Disconnected,
+    /// A file replay delivered its last frame.
+    Finished,
+    Error(String),
+}
+
+impl DeviceStatus {
+    pub fn label(&self) -> &str {
+        match self {
+            DeviceStatus::Connecting => "connecting",
+            DeviceStatus::Connected => "connected",
+            DeviceStatus::Collecting => "collecting",
+            DeviceStatus::Disconnected => "disconnected",
+            DeviceStatus::Finished => "finished",
+            DeviceStatus::Error(_) => "error",
+        }
+    }
+}
+
+/// Messages from acquisition tasks to the UI thread.
+#[derive(Debug)]
+enum AcquisitionEvent {
+    Status { device: usize, status: DeviceStatus, demo: bool },
+    Measurement { device: usize, measurement: CsiMeasurement },
+    Transport { device: usize, bytes: u64, parse_errors: u64 },
+    Info { device: usize, info: DeviceInfo },
+    Configured { device: usize, config: DeviceConfig },
+    Completed { device: usize, request: u64, command: &'static str, result: Result<(), String> },
+}
+
+/// One receiver: its config, latest status and everything it has captured.
+pub struct DeviceHandle {
+    pub id: String,
+    pub config: DeviceConfig,
+    pub demo: bool,
+    pub status: DeviceStatus,
+    /// The device's latest measurements; older ones are dropped.
+    pub measurements: MeasurementBuffer,
+    pub stats: LinkStats,
+    /// Latest reply to a device query.
+    pub info: Option<DeviceInfo>,
+    /// Stable key used to route events, unaffected by removing other devices.
+    key: usize,
+    commands: Sender<(u64, DeviceCommand)>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl DeviceHandle {
+    pub fn is_connected(&self) -> bool {
+        matches!(self.status, DeviceStatus::Connected | DeviceStatus::Collecting)
+    }
+
+    pub fn is_collecting(&self) -> bool {
+        self.status == DeviceStatus::Collecting
+    }
+
+    /// Frame rate over the last few seconds of host timestamps.
+    pub fn rate_hz(&self) -> f32 {
+        let recent = &self.measurements[self.measurements.len().saturating_sub(50)..];
+        match (recent.first(), recent.last()) {
+            (Some(first), Some(last)) if recent.len() > 1 => {
+                let span = (last.timestamp - first.timestamp).num_milliseconds() as f32 / 1000.0;
+                if span > 0.0 { (recent.len() - 1) as f32 / span } else { 0.0 }
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Runs one acquisition task per device and collects their output.
+///
+/// Each device gets its own thread owning an [`EspClient`], driven by
+/// [`DeviceCommand`]s queued through [`crate::commands::CommandExecutor`]; measurements
+/// and command outcomes come back through a shared channel drained by
+/// [`DeviceManager::poll`].
+pub struct DeviceManager {
+    devices: Vec<DeviceHandle>,
+    selected: usize,
+    next_key: usize,
+    events_tx: Sender<AcquisitionEvent>,
+    events_rx: Receiver<AcquisitionEvent>,
+    outcomes: VecDeque<CommandOutcome>,
+    /// Measurements received from all devices, including removed ones.
+    received: usize,
+    /// Seed of the first demo device added; each further one takes the next.
+    demo_seed: Option<u64>,
+    /// Parquet file every received measurement is streamed to, while recording.
+    recorder: Option<ParquetWriter>,
+}
+
+impl DeviceManager {
+    pub fn new() -> Self {
+        let (events_tx, events_rx) = mpsc::channel();
+        Self {
+            devices: Vec::new(),
+            selected: 0,
+            next_key: 0,
+            events_tx,
+            events_rx,
+            outcomes: VecDeque::new(),
+            received: 0,
+            demo_seed: None,
+            recorder: None,
+        }
+    }
+
+    /// Makes demo devices added from now on deterministic: seeded synthetic data on a
+    /// virtual clock.
+    pub fn seed_demos(&mut self, seed: u64) {
+        self.demo_seed = Some(seed);
+    }
+
+    /// Streams every measurement received from now on, from all devices, to a Parquet
+    /// file at `path`.
+    pub fn start_recording(&mut self, path: &Path) -> Result<()> {
+        if let Some(recorder) = &self.recorder {
+            return Err(anyhow!("Already recording to {}", recorder.path().display()));
+        }
+        self.recorder = Some(ParquetWriter::create(path)?);
+        Ok(())
+    }
+
+    /// Finishes the recording, storing the devices' session metadata in it, and returns
+    /// its path and measurement count; `None` when nothing was being recorded.
+    pub fn stop_recording(&mut self) -> Result<Option<(PathBuf, usize)>> {
+        let Some(recorder) = self.recorder.take() else {
+            return Ok(None);
+        };
+        let path = recorder.path().to_path_buf();
+        Ok(Some((path, recorder.close(&self.session())?)))
+    }
+
+    /// Ends a recording that failed to write, closing the file so the frames written
+    /// before the failure stay readable, and reports it as a `record` outcome.
+    fn abandon_recording(&mut self, error: anyhow::Error) {
+        let Some(recorder) = self.recorder.take() else { return };
+        let path = recorder.path().display().to_string();
+        let message = match recorder.close(&self.session()) {
+            Ok(count) => format!("{}; stopped after {} measurements", error, count),
+            Err(e) => format!("{}; the file could not be closed either: {}", error, e),
+        };
+        tracing::error!("Recording to {} failed: {}", path, message);
+        self.outcomes.push_back(CommandOutcome { request: INTERNAL_REQUEST, device: path, command: "record", result: Err(message) });
+    }
+
+    fn session(&self) -> Vec<SessionDevice> {
+        self.devices.iter().map(SessionDevice::from_handle).collect()
+    }
+
+    /// Path and measurement count of the running recording.
+    pub fn recording(&self) -> Option<(&Path, usize)> {
+        self.recorder.as_ref().map(|r| (r.path(), r.len()))
+    }
+
+    pub fn devices(&self) -> &[DeviceHandle] {
+        &self.devices
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected(&self) -> Option<&DeviceHandle> {
+        self.devices.get(self.selected)
+    }
+
+    /// Measurements of the selected device, which the analysis views operate on.
+    pub fn selected_measurements(&self) -> &[CsiMeasurement] {
+        self.selected().map_or(&[], |d| &d.measurements)
+    }
+
+    /// Measurements received from all devices since the manager started.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Each device's measurement count, to pass to [`DeviceManager::merged_since`] later.
+    pub fn positions(&self) -> Vec<usize> {
+        self.devices.iter().map(|d| d.measurements.total()).collect()
+    }
+
+    /// Every device's held measurements from its entry in `positions` on, devices
+    /// without one from their oldest held frame, in device order.
+    pub fn merged_since(&self, positions: &[usize]) -> Vec<CsiMeasurement> {
+        self.devices
+            .iter()
+            .enumerate()
+            .flat_map(|(i, d)| d.measurements.since(positions.get(i).copied().unwrap_or(0)).iter().cloned())
+            .collect()
+    }
+
+    /// Moves the selection by `step`, wrapping around. Returns whether it changed.
+    pub fn select_next(&mut self, step: isize) -> bool {
+        if self.devices.len() < 2 {
+            return false;
+        }
+        let count = self.devices.len() as isize;
+        self.selected = (self.selected as isize + step).rem_euclid(count) as usize;
+        true
+    }
+
+    pub fn any_connected(&self) -> bool {
+        self.devices.iter().any(|d| d.is_connected())
+    }
+
+    pub fn any_collecting(&self) -> bool {
+        self.devices.iter().any(|d| d.is_collecting())
+    }
+
+    /// Starts an acquisition task for a new device and selects it.
+    pub fn add(&mut self, config: DeviceConfig, demo: bool) -> &DeviceHandle {
+        let key = self.next_key;
+        self.next_key += 1;
+        let demos = self.devices.iter().filter(|d| d.demo).count();
+        let id = if demo { format!("demo{}", demos + 1) } else { transport::address_id(&config.port) };
+        let seed = self.demo_seed.filter(|_| demo).map(|seed| seed.wrapping_add(demos as u64));
+
+        let (commands, command_rx) = mpsc::channel();
+        let task = {
+            let (id, config, events) = (id.clone(), config.clone(), self.events_tx.clone());
+            std::thread::Builder::new()
+                .name(format!("acquire-{}", id))
+                .spawn(move || acquisition_task(key, id, config, demo, seed, command_rx, events))
+                .ok()
+        };
+
+        tracing::info!("Added device {} ({})", id, if demo { "demo" } else { config.port.as_str() });
+        self.devices.push(DeviceHandle {
+            id,
+            config,
+            demo,
+            status: DeviceStatus::Connecting,
+            measurements: MeasurementBuffer::default(),
+            stats: LinkStats::default(),
+            info: None,
+            key,
+            commands,
+            task,
+        });
+        self.selected = self.devices.len() - 1;
+        &self.devices[self.selected]
+    }
+
+    /// Adds a device without an acquisition task, as if it had connected, answered a
+    /// query and captured `measurements`; they arrive with the next
+    /// [`DeviceManager::poll`].
+    #[cfg(test)]
+    pub fn add_captured(
+        &mut self,
+        config: DeviceConfig,
+        demo: bool,
+        info: Option<DeviceInfo>,
+        measurements: Vec<CsiMeasurement>,
+    ) -> &DeviceHandle {
+        let key = self.next_key;
+        self.next_key += 1;
+        let demos = self.devices.iter().filter(|d| d.demo).count();
+        let id = if demo { format!("demo{}", demos + 1) } else { transport::address_id(&config.port) };
+        let events = &self.events_tx;
+        let _ = events.send(AcquisitionEvent::Status { device: key, status: DeviceStatus::Collecting, demo });
+        if let Some(info) = info {
+            let _ = events.send(AcquisitionEvent::Info { device: key, info });
+        }
+        for mut measurement in measurements {
+            measurement.device_id = Some(id.clone());
+            let _ = events.send(AcquisitionEvent::Measurement { device: key, measurement });
+        }
+        self.devices.push(DeviceHandle {
+            id,
+            config,
+            demo,
+            status: DeviceStatus::Connecting,
+            measurements: MeasurementBuffer::default(),
+            stats: LinkStats::default(),
+            info: None,
+            key,
+            commands: mpsc::channel().0,
+            task: None,
+        });
+        self.selected = self.devices.len() - 1;
+        &self.devices[self.selected]
+    }
+
+    /// Disconnects and forgets the selected device. Its acquisition task is left to
+    /// finish on its own, which may take a transport read timeout.
+    pub fn remove_selected(&mut self) -> Option<DeviceHandle> {
+        if self.devices.is_empty() {
+            return None;
+        }
+        let mut device = self.devices.remove(self.selected);
+        let _ = device.commands.send((INTERNAL_REQUEST, DeviceCommand::Disconnect));
+        device.task = None;
+        self.selected = self.selected.min(self.devices.len().saturating_sub(1));
+        Some(device)
+    }
+
+    /// Queues `command` to a device's acquisition task; its outcome is reported under
+    /// `request` by [`DeviceManager::take_outcomes`].
+    pub fn send(&self, index: usize, request: u64, command: DeviceCommand) -> Result<()> {
+        let device = self.devices.get(index).ok_or_else(|| anyhow!("No device {}", index))?;
+        device
+            .commands
+            .send((request, command))
+            .map_err(|_| anyhow!("Acquisition task for {} has stopped", device.id))
+    }
+
+    /// Command outcomes received since the last call, in arrival order.
+    pub fn take_outcomes(&mut self) -> Vec<CommandOutcome> {
+        self.outcomes.drain(..).collect()
+    }
+
+    /// The oldest outcome not taken yet, leaving later ones queued.
+    pub fn take_outcome(&mut self) -> Option<CommandOutcome> {
+        self.outcomes.pop_front()
+    }
+
+    /// Drains pending task output into the device buffers. Returns the new
+    /// measurements as `(device index, measurement)` in arrival order.
+    pub fn poll(&mut self) -> Vec<(usize, CsiMeasurement)> {
+        let mut received = Vec::new();
+        while let Ok(event) = self.events_rx.try_recv() {
+            match event {
+                AcquisitionEvent::Status { device, status, demo } => {
+                    if let Some(handle) = self.devices.iter_mut().find(|d| d.key == device) {
+                        if let DeviceStatus::Error(ref e) = status {
+                            tracing::error!("Device {}: {}", handle.id, e);
+                        }
+                        handle.status = status;
+                        handle.demo = demo;
+                    }
+                }
+                AcquisitionEvent::Measurement { device, measurement } => {
+                    if let Some(index) = self.devices.iter().position(|d| d.key == device) {
+                        let handle = &mut self.devices[index];
+                        if !handle.stats.record(measurement.sequence, measurement.device_timestamp_us) {
+                            continue;
+                        }
+                        handle.measurements.push(measurement.clone());
+                        self.received += 1;
+                        received.push((index, measurement));
+                    }
+                }
+                AcquisitionEvent::Transport { device, bytes, parse_errors } => {
+                    if let Some(handle) = self.devices.iter_mut().find(|d| d.key == device) {
+                        handle.stats.set_transport(bytes, parse_errors);
+                    }
+                }
+                AcquisitionEvent::Configured { device, config } => {
+                    if let Some(handle) = self.devices.iter_mut().find(|d| d.key == device) {
+                        handle.config = config;
+                    }
+                }
+                AcquisitionEvent::Completed { device, request, command, result } => {
+                    if request == INTERNAL_REQUEST {
+                        continue;
+                    }
+                    if let Some(handle) = self.devices.iter().find(|d| d.key == device) {
+                        self.outcomes.push_back(CommandOutcome { request, device: handle.id.clone(), command, result });
+                    }
+                }
+                AcquisitionEvent::Info { device, info } => {
+                    if let Some(handle) = self.devices.iter_mut().find(|d| d.key == device) {
+                        tracing::info!("Device {}: firmware {} on {} ({})", handle.id, info.firmware_version, info.chip, info.mac);
+                        handle.info = Some(info);
+                    }
+                }
+            }
+        }
+
+        let failed = self.recorder.as_mut().and_then(|r| r.write(received.iter().map(|(_, m)| m)).err());
+        if let Some(e) = failed {
+            self.abandon_recording(e);
+        }
+
+        let now = chrono::Utc::now();
+        for device in &mut self.devices {
+            let due = device
+                .stats
+                .last_sampled()
+                .is_none_or(|t| (now - t).to_std().is_ok_and(|elapsed| elapsed >= STATS_INTERVAL));
+            if due {
+                device.stats.sample(now);
+            }
+        }
+        received
+    }
+}
+
+impl Drop for DeviceManager {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop_recording() {
+            tracing::error!("Failed to finish recording: {}", e);
+        }
+        // Unlike removing a device, shutting down waits for every task to stop
+        // collecting and close its transport.
+        for device in &self.devices {
+            let _ = device.commands.send((INTERNAL_REQUEST, DeviceCommand::Disconnect));
+        }
+        for task in self.devices.iter_mut().filter_map(|d| d.task.take()) {
+            let _ = task.join();
+        }
+    }
+}
+
+/// Forwards a query reply, first taking the reported Wi-Fi mode into `config` and
+/// switching the CSI flags to the reported chip's set when the device turns out to be a
+/// different chip family.
+fn report_info(key: usize, info: DeviceInfo, config: &mut DeviceConfig, events: &Sender<AcquisitionEvent>) {
+    let mut changed = false;
+    if !config.csi.matches_chip(&info.chip) {
+        config.csi = CsiConfig::for_chip(&info.chip);
+        changed = true;
+    }
+    // Demo devices report `demo`, which keeps the configured mode.
+    if let Ok(mode) = WifiMode::parse(&info.wifi_mode) {
+        changed |= config.wifi.mode != mode;
+        config.wifi.mode = mode;
+    }
+    if changed {
+        let _ = events.send(AcquisitionEvent::Configured { device: key, config: config.clone() });
+    }
+    let _ = events.send(AcquisitionEvent::Info { device: key, info });
+}
+
+fn acquisition_task(
+    key: usize,
+    id: String,
+    mut config: DeviceConfig,
+    demo: bool,
+    seed: Option<u64>,
+    commands: Receiver<(u64, DeviceCommand)>,
+    events: Sender<AcquisitionEvent>,
+) {
+    let mut client = EspClient::new(config.port.clone(), config.baud_rate);
+    if demo {
+        client.enable_demo_mode(&config, seed);
+    }
+    let status = |status: DeviceStatus, client: &EspClient| {
+        let _ = events.send(AcquisitionEvent::Status { device: key, status, demo: client.is_demo_mode() });
+    };
+
+    if let Err(e) = client.connect() {
+        status(DeviceStatus::Error(format!("Connection failed: {}", e)), &client);
+        return;
+    }
+    status(DeviceStatus::Connected, &client);
+    // Older firmware does not answer queries; the device still works without the info.
+    match client.query(QUERY_TIMEOUT) {
+        Ok(info) => report_info(key, info, &mut config, &events),
+        Err(e) => tracing::debug!("{}: {}", id, e),
+    }
+
+    let mut collecting = false;
+    let mut finished = false;
+    let mut last_report = std::time::Instant::now();
+    loop {
+        if last_report.elapsed() >= STATS_INTERVAL {
+            last_report = std::time::Instant::now();
+            let _ = events.send(AcquisitionEvent::Transport {
+                device: key,
+                bytes: client.bytes_received(),
+                parse_errors: client.parse_errors(),
+            });
+        }
+
+        let command = if collecting {
+            commands.try_recv().map_err(|e| match e {
+                mpsc::TryRecvError::Empty => RecvTimeoutError::Timeout,
+                mpsc::TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+            })
+        } else {
+            commands.recv_timeout(IDLE_POLL)
+        };
+
+        match command {
+            Ok((request, command)) => {
+                let name = command.name();
+                let disconnect = matches!(command, DeviceCommand::Disconnect);
+                let result = match command {
+                    DeviceCommand::Configure(new_config) => client.configure(&new_config).map(|_| {
+                        config = new_config.clone();
+                        let _ = events.send(AcquisitionEvent::Configured { device: key, config: new_config });
+                    }),
+                    DeviceCommand::SetWifi(wifi) => client.set_wifi(&wifi).map(|_| {
+                        config.wifi = wifi;
+                        let _ = events.send(AcquisitionEvent::Configured { device: key, config: config.clone() });
+                    }),
+                    DeviceCommand::SetCsi(csi) => client.set_csi(&csi).map(|_| {
+                        config.csi = csi;
+                        let _ = events.send(AcquisitionEvent::Configured { device: key, config: config.clone() });
+                        // Refresh the firmware's own report of what it now captures.
+                        if let Ok(info) = client.query(QUERY_TIMEOUT) {
+                            report_info(key, info, &mut config, &events);
+                        }
+                    }),
+                    DeviceCommand::SetTraffic(traffic) => client.set_traffic(&traffic).map(|_| {
+                        config.traffic = traffic;
+                        let _ = events.send(AcquisitionEvent::Configured { device: key, config: config.clone() });
+                    }),
+                    DeviceCommand::SetScenario(scenario) => client.set_scenario(&scenario),
+                    DeviceCommand::StartCollection => client.start_collection().map(|_| {
+                        collecting = true;
+                        status(DeviceStatus::Collecting, &client);
+                    }),
+                    DeviceCommand::StopCollection => client.stop_collection().map(|_| {
+                        collecting = false;
+                        status(DeviceStatus::Connected, &client);
+                    }),
+                    DeviceCommand::Query => {
+                        client.query(QUERY_TIMEOUT).map(|info| report_info(key, info, &mut config, &events))
+                    }
+                    DeviceCommand::Disconnect => Ok(()),
+                };
+                match result {
+                    // A device that refuses or ignores a command is otherwise healthy; the
+                    // outcome below reports it and the task keeps its current status.
+                    Err(ref e) if name == "query" || e.is::<Rejected>() => tracing::warn!("{}: {}", id, e),
+                    Err(ref e) => status(DeviceStatus::Error(format!("Failed to {}: {}", name, e)), &client),
+                    Ok(_) => {}
+                }
+                let result = result.map_err(|e| e.to_string());
+                let _ = events.send(AcquisitionEvent::Completed { device: key, request, command: name, result });
+                if disconnect {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        if !collecting {
+            continue;
+        }
+        match client.read_measurement() {
+            Ok(Some(mut measurement)) => {
+                measurement.device_id = Some(id.clone());
+                if events.send(AcquisitionEvent::Measurement { device: key, measurement }).is_err() {
+                    break;
+                }
+                if client.is_demo_mode() {
+                    // Demo frames are generated on demand; pace them like a real device.
+                    std::thread::sleep(Duration::from_secs_f32(1.0 / config.requested_rate_hz().max(0.1)));
+                }
+            }
+            Ok(None) => std::thread::sleep(READ_BACKOFF),
+            Err(e) if e.is::<EndOfStream>() => {
+                finished = true;
+                break;
+            }
+            Err(e) => {
+                tracing::warn!("{}: failed to read measurement: {}", id, e);
+                std::thread::sleep(READ_BACKOFF);
+            }
+        }
+    }
+
+    if collecting {
+        let _ = client.stop_collection();
+    }
+    let _ = client.disconnect();
+    status(if finished { DeviceStatus::Finished } else { DeviceStatus::Disconnected }, &client);
+    tracing::info!("Acquisition task for {} stopped", id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{self, Scenario, Scene};
+    use chrono::Utc;
+    use std::time::Instant;
+
+    fn capture(seed: u64, frames: usize) -> Vec<CsiMeasurement> {
+        synthetic::capture(Scenario::new(Scene::EmptyRoom).with_seed(seed), Utc::now(), frames as f64 / 10.0, 10.0)
+    }
+
+    #[test]
+    fn poll_routes_frames_to_their_device() {
+        let mut devices = DeviceManager::new();
+        devices.add_captured(DeviceConfig::default(), true, None, capture(1, 30));
+        devices.add_captured(DeviceConfig::default(), true, None, capture(2, 20));
+
+        let received = devices.poll();
+        assert_eq!(received.len(), 50);
+        assert_eq!(devices.received(), 50);
+        assert_eq!(devices.positions(), vec![30, 20]);
+        for (index, measurement) in &received {
+            assert_eq!(measurement.device_id.as_deref(), Some(devices.devices()[*index].id.as_str()));
+        }
+        let handle = &devices.devices()[0];
+        assert_eq!((handle.id.as_str(), handle.status.clone()), ("demo1", DeviceStatus::Collecting));
+        assert_eq!(handle.stats.totals.received, 30);
+        assert!((handle.rate_hz() - 10.0).abs() < 0.1);
+        assert_eq!(devices.selected_measurements().len(), 20);
+
+        assert_eq!(devices.merged_since(&[]).len(), 50);
+        assert_eq!(devices.merged_since(&[25]).len(), 25);
+        assert!(devices.merged_since(&devices.positions()).is_empty());
+    }
+
+    #[test]
+    fn query_reply_sets_the_wifi_mode() {
+        let (events, received) = mpsc::channel();
+        let mut config = DeviceConfig::default();
+        assert_eq!(config.wifi.mode, WifiMode::Sniffer);
+        let info = DeviceInfo {
+            firmware_version: "0.1.0".to_string(),
+            chip: "esp32c3".to_string(),
+            mac: "24:0a:c4:00:11:22".to_string(),
+            wifi_mode: "station".to_string(),
+            channel: 6,
+            csi_flags: Vec::new(),
+            uptime_us: 0,
+            received_at: Utc::now(),
+        };
+        report_info(1, info.clone(), &mut config, &events);
+        assert_eq!(config.wifi.mode, WifiMode::Station);
+        assert!(matches!(received.try_recv(), Ok(AcquisitionEvent::Configured { config, .. }) if config.wifi.mode == WifiMode::Station));
+        assert!(matches!(received.try_recv(), Ok(AcquisitionEvent::Info { .. })));
+
+        report_info(1, DeviceInfo { wifi_mode: "demo".to_string(), ..info }, &mut config, &events);
+        assert_eq!(config.wifi.mode, WifiMode::Station);
+        assert!(matches!(received.try_recv(), Ok(AcquisitionEvent::Info { .. })));
+    }
+
+    #[test]
+    fn finished_replay_ends_its_task() {
+        let mut devices = DeviceManager::new();
+        let config = DeviceConfig { port: "file://fixtures/csi_frames.txt".to_string(), baud_rate: 0, ..DeviceConfig::default() };
+        devices.add(config, false);
+        devices.send(0, 1, DeviceCommand::StartCollection).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while devices.devices()[0].status != DeviceStatus::Finished && Instant::now() < deadline {
+            devices.poll();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let expected = std::fs::read_to_string("fixtures/csi_frames.txt").unwrap().matches("+CSI:").count();
+        assert_eq!(devices.devices()[0].status, DeviceStatus::Finished);
+        assert_eq!(devices.received(), expected);
+        assert!(devices.send(0, 2, DeviceCommand::StopCollection).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn failed_recording_is_reported() {
+        let mut devices = DeviceManager::new();
+        // Every write to /dev/full fails with "no space left".
+        devices.start_recording(Path::new("/dev/full")).unwrap();
+        devices.add_captured(DeviceConfig::default(), true, None, capture(1, 20_000));
+        assert_eq!(devices.poll().len(), 20_000);
+
+        assert!(devices.recording().is_none());
+        let outcome = devices.take_outcome().unwrap();
+        assert_eq!((outcome.device.as_str(), outcome.command), ("/dev/full", "record"));
+        assert!(outcome.result.is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn rejected_commands_leave_the_device_collecting() {
+        use crate::device::simulator::{self, SimulatedEsp32};
+        use crate::models::{TrafficConfig, TrafficKind};
+        use serialport::SerialPort;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let (master, slave) = serialport::TTYPort::pair().unwrap();
+        let path = slave.name().unwrap();
+        let mut master: Box<dyn SerialPort> = Box::new(master);
+        master.set_timeout(Duration::from_millis(5)).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let device = SimulatedEsp32::new(simulator::Scenario::default());
+        let server = std::thread::spawn({
+            let stop = stop.clone();
+            move || device.serve(master, stop)
+        });
+
+        let mut devices = DeviceManager::new();
+        devices.add(DeviceConfig { port: path, collection_interval_ms: 10, ..DeviceConfig::default() }, false);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let wait_for = |devices: &mut DeviceManager, done: &dyn Fn(&DeviceManager) -> bool| {
+            while !done(devices) && Instant::now() < deadline {
+                devices.poll();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+        wait_for(&mut devices, &|d| d.devices()[0].status == DeviceStatus::Connected);
+        devices.send(0, 1, DeviceCommand::StartCollection).unwrap();
+        wait_for(&mut devices, &|d| d.received() > 0);
+        let _ = devices.take_outcomes();
+
+        // The serial firmware generates no traffic and answers ERROR.
+        let traffic = TrafficConfig { kind: TrafficKind::Udp, frequency_hz: 50 };
+        devices.send(0, 2, DeviceCommand::SetTraffic(traffic)).unwrap();
+        let mut outcome = None;
+        while outcome.is_none() && Instant::now() < deadline {
+            devices.poll();
+            outcome = devices.take_outcome();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let outcome = outcome.expect("no outcome for the rejected command");
+        assert_eq!(outcome.request, 2);
+        assert!(outcome.result.unwrap_err().contains("rejected"));
+
+        let received = devices.received();
+        wait_for(&mut devices, &|d| d.received() > received);
+        let handle = &devices.devices()[0];
+        assert_eq!(handle.status, DeviceStatus::Collecting);
+        assert!(handle.is_connected() && handle.is_collecting());
+        assert!(!handle.config.traffic.is_enabled());
+        stop.store(true, Ordering::Relaxed);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn duplicate_frames_are_dropped() {
+        let mut devices = DeviceManager::new();
+        let mut frames = capture(1, 10);
+        frames.extend(frames[5..8].to_vec());
+        devices.add_captured(DeviceConfig::default(), true, None, frames);
+
+        assert_eq!(devices.poll().len(), 10);
+        assert_eq!(devices.positions(), vec![10]);
+        assert_eq!(devices.devices()[0].stats.totals.duplicates, 3);
+    }
+
+    #[test]
+    fn removing_a_device_does_not_wait_for_its_task() {
+        let mut devices = DeviceManager::new();
+        // One frame every 10 s: after the first frame the task sleeps for the next one.
+        let config = DeviceConfig { collection_interval_ms: 10_000, ..DeviceConfig::default() };
+        devices.add(config, true);
+        devices.send(0, 1, DeviceCommand::StartCollection).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while devices.received() == 0 && Instant::now() < deadline {
+            devices.poll();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(devices.received(), 1);
+        assert_eq!(devices.take_outcomes().len(), 1);
+
+        let removing = Instant::now();
+        let removed = devices.remove_selected().unwrap();
+        assert!(removing.elapsed() < Duration::from_secs(1), "removal took {:?}", removing.elapsed());
+        assert_eq!(removed.measurements.total(), 1);
+        assert!(devices.devices().is_empty());
+        assert!(devices.poll().is_empty());
+        assert!(devices.remove_selected().is_none());
+    }
+}
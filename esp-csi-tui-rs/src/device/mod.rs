@@ -1,8 +1,18 @@
 pub mod serial_handler;
+pub mod buffer;
+pub mod codec;
 pub mod esp_client;
+pub mod manager;
+pub mod network;
+pub mod replay;
+#[cfg(test)]
+pub mod simulator;
+pub mod stats;
+pub mod transport;
 
 pub use serial_handler::SerialHandler;
 pub use esp_client::EspClient;
+pub use manager::{DeviceManager, DeviceStatus};
 
 use crate::models::{CsiMeasurement, DeviceConfig};
 use anyhow::Result;
@@ -0,0 +1,288 @@
+use crate::device::transport::CsiTransport;
+use anyhow::{Result, anyhow};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::time::Duration;
+
+/// How long a read waits for a datagram or stream data before returning `Ok(0)`.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// Receives CSI text frames as UDP datagrams from an ESP32 on the network.
+///
+/// The device is not known until its first datagram arrives; commands written
+/// before then are held and sent to it once it has been heard from.
+pub struct UdpTransport {
+    bind: SocketAddr,
+    socket: Option<UdpSocket>,
+    peer: Option<SocketAddr>,
+    queued: Vec<Vec<u8>>,
+}
+
+impl UdpTransport {
+    pub fn new(bind: SocketAddr) -> Self {
+        Self { bind, socket: None, peer: None, queued: Vec::new() }
+    }
+
+    /// Address actually bound, useful when listening on port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.socket.as_ref().and_then(|s| s.local_addr().ok())
+    }
+}
+
+impl CsiTransport for UdpTransport {
+    fn open(&mut self) -> Result<()> {
+        let socket = UdpSocket::bind(self.bind).map_err(|e| anyhow!("Failed to bind UDP {}: {}", self.bind, e))?;
+        socket.set_read_timeout(Some(READ_TIMEOUT))?;
+        self.socket = Some(socket);
+        tracing::info!("Listening for CSI over UDP on {:?}", self.local_addr());
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.socket = None;
+        self.peer = None;
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        let socket = self.socket.as_ref().ok_or_else(|| anyhow!("UDP socket not open"))?;
+        match socket.recv_from(buffer) {
+            Ok((n, from)) => {
+                if self.peer != Some(from) {
+                    tracing::info!("CSI device at {}", from);
+                    self.peer = Some(from);
+                    for data in self.queued.drain(..) {
+                        socket.send_to(&data, from)?;
+                    }
+                }
+                Ok(n)
+            }
+            Err(e) if is_timeout(&e) => Ok(0),
+            Err(e) => Err(anyhow!("Failed to receive UDP: {}", e)),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let socket = self.socket.as_ref().ok_or_else(|| anyhow!("UDP socket not open"))?;
+        match self.peer {
+            Some(peer) => {
+                socket.send_to(data, peer)?;
+            }
+            None => self.queued.push(data.to_vec()),
+        }
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.socket.is_some()
+    }
+}
+
+/// Accepts a single ESP32 connecting over TCP and reads its CSI text stream.
+///
+/// A dropped connection is replaced by the next one to arrive, so a rebooting
+/// device reconnects without user action.
+pub struct TcpTransport {
+    bind: SocketAddr,
+    listener: Option<TcpListener>,
+    stream: Option<TcpStream>,
+    queued: Vec<Vec<u8>>,
+}
+
+impl TcpTransport {
+    pub fn new(bind: SocketAddr) -> Self {
+        Self { bind, listener: None, stream: None, queued: Vec::new() }
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref().and_then(|l| l.local_addr().ok())
+    }
+
+    fn accept(&mut self) -> Result<bool> {
+        if self.stream.is_some() {
+            return Ok(true);
+        }
+        let listener = self.listener.as_ref().ok_or_else(|| anyhow!("TCP listener not open"))?;
+        match listener.accept() {
+            Ok((mut stream, from)) => {
+                tracing::info!("CSI device connected from {}", from);
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                for data in self.queued.drain(..) {
+                    stream.write_all(&data)?;
+                }
+                self.stream = Some(stream);
+                Ok(true)
+            }
+            Err(e) if is_timeout(&e) => Ok(false),
+            Err(e) => Err(anyhow!("Failed to accept TCP connection: {}", e)),
+        }
+    }
+}
+
+impl CsiTransport for TcpTransport {
+    fn open(&mut self) -> Result<()> {
+        let listener = TcpListener::bind(self.bind).map_err(|e| anyhow!("Failed to bind TCP {}: {}", self.bind, e))?;
+        listener.set_nonblocking(true)?;
+        self.listener = Some(listener);
+        tracing::info!("Listening for CSI over TCP on {:?}", self.local_addr());
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.stream = None;
+        self.listener = None;
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        if !self.accept()? {
+            std::thread::sleep(READ_TIMEOUT);
+            return Ok(0);
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(0);
+        };
+        match stream.read(buffer) {
+            Ok(0) => {
+                tracing::warn!("CSI device closed the TCP connection");
+                self.stream = None;
+                Ok(0)
+            }
+            Ok(n) => Ok(n),
+            Err(e) if is_timeout(&e) => Ok(0),
+            Err(e) => {
+                self.stream = None;
+                Err(anyhow!("Failed to read TCP stream: {}", e))
+            }
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.accept()?;
+        match self.stream.as_mut() {
+            Some(stream) => stream.write_all(data)?,
+            None => self.queued.push(data.to_vec()),
+        }
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.listener.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::EspClient;
+    use std::time::Instant;
+
+    const FIXTURE: &str = include_str!("../../fixtures/csi_frames.txt");
+
+    fn localhost() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    fn fixture_sequences() -> Vec<u32> {
+        FIXTURE
+            .lines()
+            .filter_map(|l| l.split(',').nth(5))
+            .map(|s| s.parse().unwrap())
+            .collect()
+    }
+
+    /// Reads until `count` frames arrived or two seconds passed.
+    fn collect<T: CsiTransport>(client: &mut EspClient<T>, count: usize) -> Vec<u32> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut sequences = Vec::new();
+        while sequences.len() < count && Instant::now() < deadline {
+            if let Some(m) = client.read_measurement().unwrap() {
+                sequences.push(m.sequence.unwrap());
+            }
+        }
+        sequences
+    }
+
+    #[test]
+    fn udp_replay_of_fixture_frames_is_parsed() {
+        let mut transport = UdpTransport::new(localhost());
+        transport.open().unwrap();
+        let target = transport.local_addr().unwrap();
+        let mut client = EspClient::with_transport(transport);
+
+        let sender = UdpSocket::bind(localhost()).unwrap();
+        for line in FIXTURE.lines() {
+            sender.send_to(format!("{}\r\n", line).as_bytes(), target).unwrap();
+        }
+
+        let expected = fixture_sequences();
+        assert_eq!(collect(&mut client, expected.len()), expected);
+        assert!(!client.is_demo_mode());
+    }
+
+    #[test]
+    fn udp_commands_reach_the_device_once_it_is_heard_from() {
+        let mut transport = UdpTransport::new(localhost());
+        transport.open().unwrap();
+        let target = transport.local_addr().unwrap();
+        let mut client = EspClient::with_transport(transport);
+        client.start_collection().unwrap();
+
+        let device = UdpSocket::bind(localhost()).unwrap();
+        device.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        device.send_to(FIXTURE.lines().next().unwrap().as_bytes(), target).unwrap();
+        device.send_to(b"\n", target).unwrap();
+        assert_eq!(collect(&mut client, 1).len(), 1);
+
+        let mut buffer = [0u8; 64];
+        let (n, _) = device.recv_from(&mut buffer).unwrap();
+        assert_eq!(&buffer[..n], b"AT+CSISTART\n");
+    }
+
+    #[test]
+    fn tcp_stream_of_fixture_frames_is_parsed() {
+        let mut transport = TcpTransport::new(localhost());
+        transport.open().unwrap();
+        let target = transport.local_addr().unwrap();
+        let mut client = EspClient::with_transport(transport);
+
+        let mut device = TcpStream::connect(target).unwrap();
+        // Split mid-line to check frames spanning reads are reassembled.
+        let (head, tail) = FIXTURE.split_at(FIXTURE.len() / 2);
+        device.write_all(head.as_bytes()).unwrap();
+        device.flush().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        device.write_all(tail.as_bytes()).unwrap();
+
+        let expected = fixture_sequences();
+        assert_eq!(collect(&mut client, expected.len()), expected);
+    }
+
+    #[test]
+    fn udp_query_is_answered_by_the_device() {
+        let mut transport = UdpTransport::new(localhost());
+        transport.open().unwrap();
+        let target = transport.local_addr().unwrap();
+        let mut client = EspClient::with_transport(transport);
+
+        let device = UdpSocket::bind(localhost()).unwrap();
+        device.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        device.send_to(format!("{}\r\n", FIXTURE.lines().next().unwrap()).as_bytes(), target).unwrap();
+        let responder = std::thread::spawn(move || {
+            let mut buffer = [0u8; 64];
+            let (n, from) = device.recv_from(&mut buffer).unwrap();
+            assert_eq!(&buffer[..n], b"AT+CSIINFO?\n");
+            let reply = "+INFO:version=0.1.0,chip=esp32c3,mac=aa:bb:cc:dd:ee:ff,mode=station,channel=6,csi=lltf,uptime_us=42\r\nOK\r\n";
+            device.send_to(reply.as_bytes(), from).unwrap();
+        });
+
+        let info = client.query(Duration::from_secs(2)).unwrap();
+        responder.join().unwrap();
+        assert_eq!((info.chip.as_str(), info.mac.as_str(), info.uptime_us), ("esp32c3", "aa:bb:cc:dd:ee:ff", 42));
+    }
+}
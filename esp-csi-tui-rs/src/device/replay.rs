@@ -0,0 +1,127 @@
+use crate::device::transport::{CsiTransport, EndOfStream};
+use anyhow::{Context, Result, anyhow};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Longest a read waits for the next byte to become due.
+const IDLE_WAIT: Duration = Duration::from_millis(20);
+
+/// Replays raw bytes captured from a device, text lines or binary frames (e.g.
+/// `fixtures/csi_frames.txt`), as if they arrived over a serial link at the baud rate
+/// (8N1, ten bits a byte); a baud rate of 0 delivers them as fast as they are read.
+/// Commands written to it are dropped and the stream ends with the file, after which
+/// reads fail with [`EndOfStream`].
+pub struct FileReplay {
+    path: PathBuf,
+    baud_rate: u32,
+    data: Option<Vec<u8>>,
+    position: usize,
+    started: Instant,
+}
+
+impl FileReplay {
+    pub fn new(path: PathBuf, baud_rate: u32) -> Self {
+        Self { path, baud_rate, data: None, position: 0, started: Instant::now() }
+    }
+
+    /// Bytes the link would have delivered by now.
+    fn due(&self, len: usize) -> usize {
+        if self.baud_rate == 0 {
+            return len;
+        }
+        let bytes = self.started.elapsed().as_secs_f64() * self.baud_rate as f64 / 10.0;
+        (bytes as usize).min(len)
+    }
+}
+
+impl CsiTransport for FileReplay {
+    fn open(&mut self) -> Result<()> {
+        let data = std::fs::read(&self.path).with_context(|| format!("Failed to open replay {}", self.path.display()))?;
+        tracing::info!("Replaying {} bytes from {}", data.len(), self.path.display());
+        self.data = Some(data);
+        self.position = 0;
+        self.started = Instant::now();
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.data = None;
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        let data = self.data.as_ref().ok_or_else(|| anyhow!("Replay not open"))?;
+        if self.position == data.len() {
+            return Err(EndOfStream.into());
+        }
+        let available = self.due(data.len()).saturating_sub(self.position).min(buffer.len());
+        if available == 0 {
+            std::thread::sleep(IDLE_WAIT);
+            return Ok(0);
+        }
+        buffer[..available].copy_from_slice(&data[self.position..self.position + available]);
+        self.position += available;
+        Ok(available)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        tracing::debug!("Replay ignores command {:?}", String::from_utf8_lossy(data).trim());
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.data.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::EspClient;
+
+    const FIXTURE: &str = "fixtures/csi_frames.txt";
+
+    #[test]
+    fn replayed_capture_is_decoded_in_order() {
+        let mut client = EspClient::with_transport(FileReplay::new(FIXTURE.into(), 0));
+        client.connect().unwrap();
+        client.start_collection().unwrap();
+        assert!(!client.is_demo_mode());
+
+        let expected: Vec<u32> = std::fs::read_to_string(FIXTURE)
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.split(',').nth(5)?.parse().ok())
+            .collect();
+        let mut sequences = Vec::new();
+        let end = loop {
+            match client.read_measurement() {
+                Ok(Some(m)) => sequences.push(m.sequence.unwrap()),
+                Ok(None) => {}
+                Err(e) => break e,
+            }
+        };
+        assert!(end.is::<EndOfStream>(), "{}", end);
+        assert_eq!(sequences, expected);
+        assert_eq!(client.parse_errors(), 0);
+    }
+
+    #[test]
+    fn bytes_are_paced_at_the_baud_rate() {
+        // 9600 bytes/s: about 1000 bytes in the first 100 ms.
+        let mut replay = FileReplay::new(FIXTURE.into(), 96_000);
+        replay.open().unwrap();
+        let mut buffer = [0u8; 4096];
+        let deadline = Instant::now() + Duration::from_millis(100);
+        let mut received = 0;
+        while Instant::now() < deadline {
+            received += replay.read(&mut buffer).unwrap();
+        }
+        assert!((500..2000).contains(&received), "received {} bytes", received);
+    }
+
+    #[test]
+    fn missing_file_fails_to_open() {
+        assert!(FileReplay::new("fixtures/missing.bin".into(), 0).open().is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::device::transport::CsiTransport;
 use serialport::SerialPort;
 use std::io::{Read, Write};
 use std::time::Duration;
@@ -18,7 +19,19 @@ impl SerialHandler {
         }
     }
 
+    /// Handler around an already open port, e.g. a simulated device in tests.
+    #[cfg(test)]
+    pub fn with_port(port: Box<dyn SerialPort>) -> Self {
+        let port_name = port.name().unwrap_or_default();
+        let baud_rate = port.baud_rate().unwrap_or(115_200);
+        Self { port: Some(port), port_name, baud_rate }
+    }
+
+    /// Opens the port; a port that is already open is kept.
     pub fn connect(&mut self) -> Result<()> {
+        if self.port.is_some() {
+            return Ok(());
+        }
         match serialport::new(&self.port_name, self.baud_rate)
             .timeout(Duration::from_secs(1))
             .open()
@@ -50,10 +63,14 @@ impl SerialHandler {
         }
     }
 
+    /// Returns `Ok(0)` when the port timed out without data.
     pub fn read_data(&mut self, buffer: &mut [u8]) -> Result<usize> {
         if let Some(ref mut port) = self.port {
-            port.read(buffer)
-                .map_err(|e| anyhow!("Failed to read from serial port: {}", e))
+            match port.read(buffer) {
+                Ok(n) => Ok(n),
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(0),
+                Err(e) => Err(anyhow!("Failed to read from serial port: {}", e)),
+            }
         } else {
             Err(anyhow!("Serial port not connected"))
         }
@@ -63,3 +80,25 @@ impl SerialHandler {
         self.port.is_some()
     }
 }
+
+impl CsiTransport for SerialHandler {
+    fn open(&mut self) -> Result<()> {
+        self.connect()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.disconnect()
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        self.read_data(buffer)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.write_command(data)
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_connected()
+    }
+}
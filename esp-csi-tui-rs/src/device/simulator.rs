@@ -0,0 +1,548 @@
+//! Simulated ESP32 speaking the firmware's serial protocol, for driving
+//! [`SerialHandler`](crate::device::SerialHandler) and
+//! [`EspClient`](crate::device::EspClient) in tests without hardware. The device sits
+//! behind an in-memory [`VirtualSerialPort`] or, on Linux, a pseudo-terminal served by
+//! [`SimulatedEsp32::serve`].
+
+use crate::device::codec;
+use crate::models::{ComplexNumber, CsiMeasurement};
+use chrono::Utc;
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const SUBCARRIERS: usize = 52;
+/// Noise a real device prints between frames: a log line, stray bytes, a truncated
+/// `+CSI` line and a sync word starting a frame of an unknown version.
+pub const GARBAGE: &[u8] = b"I (1234) wifi: noise\r\n\x00\xff\x13+CSI:6,20,-40\r\n\xC5\x1A\x09\x00\x00\r\n";
+
+/// How the simulated device misbehaves.
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    /// Binary frames instead of `+CSI` lines.
+    pub binary: bool,
+    /// [`GARBAGE`] follows every this many frames.
+    pub garbage_every: Option<u32>,
+    /// The link drops once this many frames were sent.
+    pub disconnect_after: Option<u32>,
+    /// Delay before each reply to a command.
+    pub ack_delay: Duration,
+}
+
+/// Firmware state machine: answers AT commands and streams frames at the configured
+/// interval once started.
+pub struct SimulatedEsp32 {
+    scenario: Scenario,
+    line: Vec<u8>,
+    output: VecDeque<u8>,
+    /// Replies held back by the ack delay, with the time they are due.
+    replies: VecDeque<(Instant, Vec<u8>)>,
+    streaming: bool,
+    channel: u8,
+    bandwidth: u16,
+    interval: Duration,
+    next_frame: Instant,
+    sequence: u32,
+    started: Instant,
+    /// Every command line received, in order.
+    pub commands: Vec<String>,
+}
+
+impl SimulatedEsp32 {
+    pub fn new(scenario: Scenario) -> Self {
+        let now = Instant::now();
+        Self {
+            scenario,
+            line: Vec::new(),
+            output: VecDeque::new(),
+            replies: VecDeque::new(),
+            streaming: false,
+            channel: 6,
+            bandwidth: 20,
+            interval: Duration::from_millis(10),
+            next_frame: now,
+            sequence: 0,
+            started: now,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.scenario.disconnect_after.is_some_and(|limit| self.sequence >= limit)
+    }
+
+    /// Bytes from the host.
+    pub fn receive(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.is_disconnected() {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "device disconnected"));
+        }
+        for &byte in data {
+            match byte {
+                b'\n' | b'\r' => {
+                    let line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).trim().to_string();
+                    if !line.is_empty() {
+                        let reply = self.handle(&line);
+                        self.commands.push(line);
+                        self.replies.push_back((Instant::now() + self.scenario.ack_delay, reply));
+                    }
+                }
+                byte => self.line.push(byte),
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes for the host; `Ok(0)` when nothing is due yet.
+    pub fn transmit(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.pump(Instant::now());
+        if self.output.is_empty() && self.is_disconnected() {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "device disconnected"));
+        }
+        let n = buffer.len().min(self.output.len());
+        for (slot, byte) in buffer.iter_mut().zip(self.output.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+
+    fn handle(&mut self, line: &str) -> Vec<u8> {
+        let ok = match line {
+            "AT" => true,
+            "AT+CSISTART" => {
+                self.streaming = true;
+                self.next_frame = Instant::now();
+                true
+            }
+            "AT+CSISTOP" => {
+                self.streaming = false;
+                true
+            }
+            "AT+CSIINFO?" => {
+                let info = format!(
+                    "+INFO:version=sim-1.0,chip=esp32c3,mac=02:00:00:00:00:01,mode=sniffer,channel={},csi=lltf|htltf,uptime_us={}\r\n",
+                    self.channel,
+                    self.started.elapsed().as_micros()
+                );
+                return [info.as_bytes(), b"OK\r\n"].concat();
+            }
+            command => {
+                if let Some(args) = command.strip_prefix("AT+CSICFG=") {
+                    self.configure(args).is_some()
+                } else if let Some(args) = command.strip_prefix("AT+CSIWIFI=") {
+                    valid_wifi(args).is_some()
+                } else if let Some(args) = command.strip_prefix("AT+CSIFLAGS=") {
+                    valid_csi_flags(args).is_some()
+                } else if let Some(args) = command.strip_prefix("AT+CSITRAFFIC=") {
+                    valid_traffic(args).is_some()
+                } else {
+                    false
+                }
+            }
+        };
+        if ok { b"OK\r\n".to_vec() } else { b"ERROR\r\n".to_vec() }
+    }
+
+    fn configure(&mut self, args: &str) -> Option<()> {
+        let mut fields = args.split(',').map(|f| f.trim().parse::<u32>().ok());
+        let (channel, bandwidth, interval) = (fields.next()??, fields.next()??, fields.next()??);
+        if !(1..=14).contains(&channel) || ![20, 40].contains(&bandwidth) || interval < 10 {
+            return None;
+        }
+        self.channel = channel as u8;
+        self.bandwidth = bandwidth as u16;
+        self.interval = Duration::from_millis(interval as u64);
+        Some(())
+    }
+
+    /// Moves due replies and frames into the output.
+    fn pump(&mut self, now: Instant) {
+        while self.replies.front().is_some_and(|(due, _)| *due <= now) {
+            let (_, reply) = self.replies.pop_front().expect("checked above");
+            self.output.extend(reply);
+        }
+        while self.streaming && !self.is_disconnected() && self.next_frame <= now {
+            self.next_frame += self.interval;
+            let frame = self.frame();
+            self.output.extend(frame);
+            if self.scenario.garbage_every.is_some_and(|every| self.sequence.is_multiple_of(every)) {
+                self.output.extend(GARBAGE);
+            }
+        }
+    }
+
+    fn frame(&mut self) -> Vec<u8> {
+        let sequence = self.sequence;
+        self.sequence += 1;
+        let measurement = CsiMeasurement {
+            timestamp: Utc::now(),
+            channel: self.channel,
+            bandwidth: self.bandwidth,
+            rssi: -40 - (sequence % 20) as i8,
+            noise_floor: -95,
+            subcarrier_data: (0..SUBCARRIERS)
+                .map(|i| ComplexNumber::new(((i * 5) as u32 + sequence) as f32 % 48.0 - 24.0, (i * 3) as f32 % 48.0 - 24.0))
+                .collect(),
+            sequence: Some(sequence),
+            device_timestamp_us: Some(self.started.elapsed().as_micros() as u64),
+            device_id: None,
+            transmitter: Some("02:00:00:00:00:aa".to_string()),
+        };
+        if self.scenario.binary {
+            codec::encode_binary(&measurement).expect("52 subcarriers fit a frame")
+        } else {
+            codec::encode_text(&measurement).into_bytes()
+        }
+    }
+
+    /// Runs the device on the other end of `port` (e.g. a pty master) until `stop` is
+    /// set or the scenario disconnects.
+    pub fn serve(mut self, mut port: Box<dyn SerialPort>, stop: Arc<AtomicBool>) {
+        let mut buffer = [0u8; 1024];
+        while !stop.load(Ordering::Relaxed) {
+            match port.read(&mut buffer) {
+                Ok(n) if self.receive(&buffer[..n]).is_err() => return,
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(_) => return,
+            }
+            match self.transmit(&mut buffer) {
+                Ok(n) if port.write_all(&buffer[..n]).is_err() => return,
+                Ok(_) => {}
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+/// `AT+CSIWIFI` arguments the serial firmware build accepts: it has no radio driver,
+/// so only `sniffer` with well-formed credentials.
+fn valid_wifi(args: &str) -> Option<()> {
+    let fields: Vec<&str> = args.split(',').collect();
+    let [mode, sta_ssid, sta_password, ap_ssid, ap_password, hidden, max_connections] = fields[..] else {
+        return None;
+    };
+    max_connections.trim().parse::<u8>().ok()?;
+    let fits = sta_ssid.len() <= 32 && ap_ssid.len() <= 32 && sta_password.len() <= 64 && ap_password.len() <= 64;
+    (mode.trim() == "sniffer" && fits && matches!(hidden.trim(), "0" | "1")).then_some(())
+}
+
+/// `AT+CSIFLAGS` arguments: any subset of the original ESP32 captures, each 0 or 1.
+fn valid_csi_flags(args: &str) -> Option<()> {
+    args.split(',')
+        .all(|setting| {
+            setting.trim().split_once('=').is_some_and(|(name, value)| {
+                matches!(name, "lltf" | "htltf" | "stbc-htltf" | "ltf-merge") && matches!(value, "0" | "1")
+            })
+        })
+        .then_some(())
+}
+
+/// `AT+CSITRAFFIC` arguments: a known kind at up to 1000 Hz. The serial build sends no
+/// traffic and is never a station, so only 0 Hz (off) is accepted.
+fn valid_traffic(args: &str) -> Option<()> {
+    let (kind, frequency_hz) = args.split_once(',')?;
+    let frequency_hz: u32 = frequency_hz.trim().parse().ok()?;
+    (matches!(kind.trim(), "ping" | "udp" | "null") && frequency_hz == 0).then_some(())
+}
+
+/// In-memory [`SerialPort`] connected to a shared [`SimulatedEsp32`]. Reads block up to
+/// the timeout and then fail with `TimedOut`, as a real port does.
+#[derive(Clone)]
+pub struct VirtualSerialPort {
+    pub device: Arc<Mutex<SimulatedEsp32>>,
+    timeout: Duration,
+    baud_rate: u32,
+}
+
+impl VirtualSerialPort {
+    pub fn new(scenario: Scenario) -> Self {
+        Self {
+            device: Arc::new(Mutex::new(SimulatedEsp32::new(scenario))),
+            timeout: Duration::from_millis(20),
+            baud_rate: 115_200,
+        }
+    }
+}
+
+impl Read for VirtualSerialPort {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let n = self.device.lock().expect("device lock").transmit(buffer)?;
+            if n > 0 {
+                return Ok(n);
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "no data"));
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+impl Write for VirtualSerialPort {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.device.lock().expect("device lock").receive(data)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SerialPort for VirtualSerialPort {
+    fn name(&self) -> Option<String> {
+        Some("virtual".to_string())
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(self.baud_rate)
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(FlowControl::None)
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(Parity::None)
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+    fn set_data_bits(&mut self, _: DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_flow_control(&mut self, _: FlowControl) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_parity(&mut self, _: Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_stop_bits(&mut self, _: StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, _: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn write_data_terminal_ready(&mut self, _: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.device.lock().expect("device lock").output.len() as u32)
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    fn clear(&self, _: ClearBuffer) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(self.clone()))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{DeviceManager, DeviceStatus, EspClient, SerialHandler};
+    use crate::models::{DeviceCommand, DeviceConfig};
+
+    fn client(port: &VirtualSerialPort) -> EspClient<SerialHandler> {
+        let mut client = EspClient::with_transport(SerialHandler::with_port(Box::new(port.clone())));
+        client.connect().unwrap();
+        assert!(!client.is_demo_mode());
+        client
+    }
+
+    /// Reads until `count` frames arrived, the link failed or two seconds passed.
+    fn collect(client: &mut EspClient<SerialHandler>, count: usize) -> (Vec<u32>, Option<anyhow::Error>) {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut sequences = Vec::new();
+        while sequences.len() < count && Instant::now() < deadline {
+            match client.read_measurement() {
+                Ok(Some(m)) => sequences.push(m.sequence.unwrap()),
+                Ok(None) => {}
+                Err(e) => return (sequences, Some(e)),
+            }
+        }
+        (sequences, None)
+    }
+
+    fn conforms(scenario: Scenario) {
+        let port = VirtualSerialPort::new(scenario);
+        let mut client = client(&port);
+        let config = DeviceConfig { channel: 11, bandwidth: 40, collection_interval_ms: 10, ..DeviceConfig::default() };
+        client.configure(&config).unwrap();
+        client.start_collection().unwrap();
+
+        let (sequences, error) = collect(&mut client, 30);
+        assert!(error.is_none(), "{:?}", error);
+        assert_eq!(sequences, (0..30).collect::<Vec<_>>());
+        let info = client.query(Duration::from_secs(1)).unwrap();
+        assert_eq!((info.firmware_version.as_str(), info.channel), ("sim-1.0", 11));
+
+        client.stop_collection().unwrap();
+        let device = port.device.lock().unwrap();
+        assert_eq!(device.commands[..3], ["AT", "AT+CSICFG=11,40,10", "AT+CSISTART"]);
+        assert_eq!(device.commands.last().unwrap(), "AT+CSISTOP");
+    }
+
+    #[test]
+    fn text_stream_conforms() {
+        conforms(Scenario::default());
+    }
+
+    #[test]
+    fn binary_stream_conforms() {
+        conforms(Scenario { binary: true, ..Scenario::default() });
+    }
+
+    #[test]
+    fn garbage_between_frames_is_skipped_and_counted() {
+        for binary in [false, true] {
+            let port = VirtualSerialPort::new(Scenario { binary, garbage_every: Some(5), ..Scenario::default() });
+            let mut client = client(&port);
+            client.start_collection().unwrap();
+
+            let (sequences, error) = collect(&mut client, 20);
+            assert!(error.is_none(), "{:?}", error);
+            assert_eq!(sequences, (0..20).collect::<Vec<_>>(), "binary: {}", binary);
+            assert!(client.parse_errors() >= 3, "binary: {}, errors: {}", binary, client.parse_errors());
+        }
+    }
+
+    #[test]
+    fn disconnect_surfaces_as_read_error() {
+        let port = VirtualSerialPort::new(Scenario { disconnect_after: Some(8), ..Scenario::default() });
+        let mut client = client(&port);
+        client.start_collection().unwrap();
+
+        let (sequences, error) = collect(&mut client, 20);
+        assert_eq!(sequences, (0..8).collect::<Vec<_>>());
+        assert!(error.is_some());
+        assert!(client.stop_collection().is_err());
+    }
+
+    #[test]
+    fn rejected_commands_are_errors() {
+        let port = VirtualSerialPort::new(Scenario::default());
+        let mut client = client(&port);
+        let config = DeviceConfig { channel: 15, ..DeviceConfig::default() };
+        let error = client.configure(&config).unwrap_err();
+        assert!(error.to_string().contains("rejected AT+CSICFG=15"), "{}", error);
+        client.start_collection().unwrap();
+    }
+
+    #[test]
+    fn commands_are_validated_like_the_serial_firmware() {
+        let mut device = SimulatedEsp32::new(Scenario::default());
+        let cases = [
+            ("AT+CSICFG=6,20,10", true),
+            ("AT+CSICFG=6,20,9", false),
+            ("AT+CSICFG=6,80,100", false),
+            ("AT+CSIWIFI=sniffer,,,,,0,1", true),
+            ("AT+CSIWIFI=station,lab,secret123,,,0,1", false),
+            ("AT+CSIWIFI=sniffer,,,,,2,1", false),
+            ("AT+CSIWIFI=sniffer,,,,,0", false),
+            ("AT+CSIWIFI=sniffer,,,,,0,many", false),
+            ("AT+CSIFLAGS=lltf=1,htltf=0", true),
+            ("AT+CSIFLAGS=lltf=2", false),
+            ("AT+CSIFLAGS=enable=1", false),
+            ("AT+CSITRAFFIC=udp,0", true),
+            ("AT+CSITRAFFIC=udp,100", false),
+            ("AT+CSITRAFFIC=tcp,0", false),
+            ("AT+CSIFOO", false),
+        ];
+        for (command, ok) in cases {
+            let expected: &[u8] = if ok { b"OK\r\n" } else { b"ERROR\r\n" };
+            assert_eq!(device.handle(command), expected, "{}", command);
+        }
+    }
+
+    #[test]
+    fn slow_replies_keep_frames_and_respect_the_query_timeout() {
+        let port = VirtualSerialPort::new(Scenario { ack_delay: Duration::from_millis(300), ..Scenario::default() });
+        let mut client = client(&port);
+        client.start_collection().unwrap();
+        assert!(client.query(Duration::from_millis(100)).is_err());
+        // Frames streamed while waiting are not lost, and the late reply still arrives.
+        assert!(client.query(Duration::from_secs(1)).is_ok());
+        let (sequences, error) = collect(&mut client, 20);
+        assert!(error.is_none(), "{:?}", error);
+        assert_eq!(sequences, (0..20).collect::<Vec<_>>());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn device_manager_streams_from_a_pty() {
+        let (master, slave) = serialport::TTYPort::pair().unwrap();
+        let path = slave.name().unwrap();
+        let mut master: Box<dyn SerialPort> = Box::new(master);
+        master.set_timeout(Duration::from_millis(5)).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let device = SimulatedEsp32::new(Scenario { binary: true, ..Scenario::default() });
+        let server = std::thread::spawn({
+            let stop = stop.clone();
+            move || device.serve(master, stop)
+        });
+
+        let mut devices = DeviceManager::new();
+        devices.add(DeviceConfig { port: path, collection_interval_ms: 10, ..DeviceConfig::default() }, false);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut started = false;
+        while Instant::now() < deadline {
+            devices.poll();
+            let handle = &devices.devices()[0];
+            if !started && handle.status == DeviceStatus::Connected {
+                devices.send(0, 1, DeviceCommand::StartCollection).unwrap();
+                started = true;
+            }
+            if handle.measurements.len() >= 20 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let handle = &devices.devices()[0];
+        assert!(!handle.demo, "the pty should open as a real serial port");
+        assert_eq!(handle.info.as_ref().map(|i| i.firmware_version.as_str()), Some("sim-1.0"));
+        let sequences: Vec<u32> = handle.measurements.iter().filter_map(|m| m.sequence).collect();
+        assert!(sequences.len() >= 20, "received {} frames", sequences.len());
+        assert!(sequences.windows(2).all(|w| w[1] == w[0] + 1));
+        stop.store(true, Ordering::Relaxed);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+
+/// Recent sequence numbers remembered to tell duplicates from late arrivals. A frame
+/// further behind than this is taken as a device restart.
+const SEQUENCE_MEMORY: usize = 256;
+/// One-second samples kept for the diagnostics history (5 minutes).
+const HISTORY_LEN: usize = 300;
+
+/// Cumulative link counters for one device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkCounters {
+    /// Distinct frames delivered.
+    pub received: u64,
+    /// Frames skipped by the sequence counter and never seen since.
+    pub lost: u64,
+    pub duplicates: u64,
+    /// Frames that arrived after a later sequence number.
+    pub reordered: u64,
+    /// Firmware restarts: the sequence counter jumping back past the remembered frames,
+    /// or repeating a number with a new device timestamp.
+    pub resets: u64,
+    /// Raw bytes read from the transport.
+    pub bytes: u64,
+    /// Frames or lines the decoder rejected.
+    pub parse_errors: u64,
+}
+
+impl LinkCounters {
+    /// Lost frames as a percentage of those the device sent.
+    pub fn loss_pct(&self) -> f32 {
+        let expected = self.received + self.lost;
+        if expected == 0 { 0.0 } else { self.lost as f32 / expected as f32 * 100.0 }
+    }
+}
+
+/// Link quality over one sampling interval.
+#[derive(Debug, Clone, Copy)]
+pub struct LinkSample {
+    pub rate_hz: f32,
+    pub loss_pct: f32,
+    pub bytes_per_s: f32,
+    pub parse_errors: u64,
+}
+
+/// Tracks frame loss, duplicates and reordering from firmware sequence numbers,
+/// together with transport throughput and decode errors, and keeps a history of
+/// per-interval samples.
+#[derive(Debug, Clone, Default)]
+pub struct LinkStats {
+    pub totals: LinkCounters,
+    pub history: VecDeque<LinkSample>,
+    highest: Option<u32>,
+    /// Sequence numbers and device timestamps of the latest distinct frames.
+    recent: VecDeque<(u32, Option<u64>)>,
+    last_sample: Option<(DateTime<Utc>, LinkCounters)>,
+}
+
+impl LinkStats {
+    /// Accounts for one delivered frame and returns whether it is new, i.e. not a
+    /// duplicate. Frames without a sequence number only count as received.
+    ///
+    /// A sequence number seen before with a different device timestamp, or one too far
+    /// behind to still be remembered, means the device restarted.
+    pub fn record(&mut self, sequence: Option<u32>, device_timestamp_us: Option<u64>) -> bool {
+        let Some(sequence) = sequence else {
+            self.totals.received += 1;
+            return true;
+        };
+        if let Some(highest) = self.highest {
+            let step = sequence.wrapping_sub(highest);
+            if step != 0 && step <= u32::MAX / 2 {
+                self.totals.lost += (step - 1) as u64;
+                self.highest = Some(sequence);
+            } else {
+                let seen = self.recent.iter().find(|(s, _)| *s == sequence).map(|&(_, t)| t);
+                match seen {
+                    Some(timestamp) if timestamp == device_timestamp_us => {
+                        self.totals.duplicates += 1;
+                        return false;
+                    }
+                    None if highest.wrapping_sub(sequence) < SEQUENCE_MEMORY as u32 => {
+                        // Counted as lost when the gap was first seen.
+                        self.totals.reordered += 1;
+                        self.totals.lost = self.totals.lost.saturating_sub(1);
+                    }
+                    _ => {
+                        self.totals.resets += 1;
+                        self.highest = Some(sequence);
+                        self.recent.clear();
+                    }
+                }
+            }
+        } else {
+            self.highest = Some(sequence);
+        }
+        self.totals.received += 1;
+
+        self.recent.push_back((sequence, device_timestamp_us));
+        if self.recent.len() > SEQUENCE_MEMORY {
+            self.recent.pop_front();
+        }
+        true
+    }
+
+    /// Updates the cumulative transport counters reported by the acquisition task.
+    pub fn set_transport(&mut self, bytes: u64, parse_errors: u64) {
+        self.totals.bytes = bytes;
+        self.totals.parse_errors = parse_errors;
+    }
+
+    /// Appends a history sample covering the time since the previous one.
+    pub fn sample(&mut self, now: DateTime<Utc>) {
+        if let Some((time, previous)) = self.last_sample {
+            let elapsed = (now - time).num_milliseconds() as f32 / 1000.0;
+            if elapsed <= 0.0 {
+                return;
+            }
+            let received = self.totals.received - previous.received;
+            let lost = self.totals.lost.saturating_sub(previous.lost);
+            self.history.push_back(LinkSample {
+                rate_hz: received as f32 / elapsed,
+                loss_pct: if received + lost == 0 { 0.0 } else { lost as f32 / (received + lost) as f32 * 100.0 },
+                bytes_per_s: self.totals.bytes.saturating_sub(previous.bytes) as f32 / elapsed,
+                parse_errors: self.totals.parse_errors.saturating_sub(previous.parse_errors),
+            });
+            if self.history.len() > HISTORY_LEN {
+                self.history.pop_front();
+            }
+        }
+        self.last_sample = Some((now, self.totals));
+    }
+
+    /// Time of the latest sample, or of the first reference point.
+    pub fn last_sampled(&self) -> Option<DateTime<Utc>> {
+        self.last_sample.map(|(time, _)| time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_all(sequences: &[u32]) -> LinkCounters {
+        let mut stats = LinkStats::default();
+        for &sequence in sequences {
+            stats.record(Some(sequence), None);
+        }
+        stats.totals
+    }
+
+    #[test]
+    fn gaps_count_as_loss() {
+        let totals = record_all(&[0, 1, 2, 5, 6, 10]);
+        assert_eq!((totals.received, totals.lost), (6, 5));
+        assert!((totals.loss_pct() - 5.0 / 11.0 * 100.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn late_frames_are_reorders_not_loss() {
+        let totals = record_all(&[0, 1, 3, 2, 4]);
+        assert_eq!((totals.received, totals.lost, totals.reordered), (5, 0, 1));
+    }
+
+    #[test]
+    fn repeated_frames_are_duplicates() {
+        let totals = record_all(&[0, 1, 1, 2, 0]);
+        assert_eq!((totals.received, totals.duplicates), (3, 2));
+    }
+
+    #[test]
+    fn counter_wrap_and_restart_are_not_loss() {
+        let totals = record_all(&[u32::MAX - 1, u32::MAX, 0, 1]);
+        assert_eq!((totals.lost, totals.resets), (0, 0));
+
+        let totals = record_all(&[5000, 5001, 0, 1]);
+        assert_eq!((totals.lost, totals.resets, totals.received), (0, 1, 4));
+    }
+
+    #[test]
+    fn early_restart_is_a_reset_not_a_reorder() {
+        // Frames 0-99 10 ms apart, then the device reboots and counts from 0 again.
+        let mut stats = LinkStats::default();
+        for sequence in 0..100 {
+            assert!(stats.record(Some(sequence), Some(sequence as u64 * 10_000)));
+        }
+        for sequence in 0..5 {
+            assert!(stats.record(Some(sequence), Some(2_000 + sequence as u64 * 10_000)));
+        }
+        let totals = stats.totals;
+        assert_eq!((totals.received, totals.lost, totals.reordered, totals.resets), (105, 0, 0, 1));
+
+        // Far enough back that the gap is no longer remembered.
+        let totals = record_all(&[10, 11, 600, 0, 1]);
+        assert_eq!((totals.lost, totals.reordered, totals.resets), (588, 0, 1));
+    }
+
+    #[test]
+    fn duplicates_are_not_new() {
+        let mut stats = LinkStats::default();
+        assert!(stats.record(Some(7), Some(70_000)));
+        assert!(stats.record(Some(8), Some(80_000)));
+        assert!(!stats.record(Some(7), Some(70_000)));
+        assert!(!stats.record(Some(8), Some(80_000)));
+        assert!(stats.record(None, None));
+        assert_eq!((stats.totals.received, stats.totals.duplicates), (3, 2));
+    }
+}
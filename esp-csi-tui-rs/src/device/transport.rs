@@ -0,0 +1,294 @@
+use crate::device::network::{TcpTransport, UdpTransport};
+use crate::device::replay::FileReplay;
+use crate::device::SerialHandler;
+use anyhow::{Result, anyhow};
+
+/// Byte stream to and from an ESP32, independent of the physical link.
+///
+/// `read` returns `Ok(0)` when nothing arrived within the transport's poll timeout,
+/// so callers can treat serial timeouts and idle sockets alike, and [`EndOfStream`]
+/// once a finite stream such as a file replay has been delivered completely. No call blocks longer
+/// than that timeout, so a transport can be driven from a plain thread or from
+/// `tokio::task::spawn_blocking` without stalling an async runtime.
+pub trait CsiTransport: Send {
+    fn open(&mut self) -> Result<()>;
+    fn close(&mut self) -> Result<()>;
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize>;
+    fn write(&mut self, data: &[u8]) -> Result<()>;
+    fn is_open(&self) -> bool;
+}
+
+/// Error `read` returns after the last byte of a finite stream.
+#[derive(Debug)]
+pub struct EndOfStream;
+
+impl std::fmt::Display for EndOfStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "End of stream")
+    }
+}
+
+impl std::error::Error for EndOfStream {}
+
+impl<T: CsiTransport + ?Sized> CsiTransport for Box<T> {
+    fn open(&mut self) -> Result<()> {
+        (**self).open()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        (**self).close()
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        (**self).read(buffer)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        (**self).write(data)
+    }
+
+    fn is_open(&self) -> bool {
+        (**self).is_open()
+    }
+}
+
+/// Picks the transport for a device address: `udp://<bind>:<port>` and
+/// `tcp://<bind>:<port>` listen for a networked ESP32, `file://<path>` replays a raw
+/// capture at the baud rate, anything else is a serial port.
+pub fn from_address(address: &str, baud_rate: u32) -> Result<Box<dyn CsiTransport>> {
+    if let Some(path) = address.strip_prefix("file://") {
+        return Ok(Box::new(FileReplay::new(path.into(), baud_rate)));
+    }
+    if let Some(bind) = address.strip_prefix("udp://") {
+        return Ok(Box::new(UdpTransport::new(parse_bind(bind)?)));
+    }
+    if let Some(bind) = address.strip_prefix("tcp://") {
+        return Ok(Box::new(TcpTransport::new(parse_bind(bind)?)));
+    }
+    Ok(Box::new(SerialHandler::new(address.to_string(), baud_rate)))
+}
+
+/// Short name for a device address, e.g. `ttyUSB0`, `udp-5555` or `file-capture`.
+pub fn address_id(address: &str) -> String {
+    match address.split_once("://") {
+        Some(("file", path)) => {
+            let stem = std::path::Path::new(path).file_stem().map(|s| s.to_string_lossy());
+            format!("file-{}", stem.unwrap_or_default())
+        }
+        Some((scheme, bind)) => format!("{}-{}", scheme, bind.rsplit(':').next().unwrap_or(bind)),
+        None => address.trim_start_matches("/dev/").to_string(),
+    }
+}
+
+fn parse_bind(bind: &str) -> Result<std::net::SocketAddr> {
+    bind.parse().map_err(|e| anyhow!("Invalid listen address '{}': {}", bind, e))
+}
+
+/// In-memory transport for tests: bytes pushed through the shared handles are read
+/// back by the client, and everything the client writes is recorded and answered with
+/// `OK` or `ERROR` like the firmware.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    pub incoming: std::sync::Arc<std::sync::Mutex<std::collections::VecDeque<u8>>>,
+    pub written: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    /// `open` fails, as for a missing serial port.
+    pub unavailable: bool,
+    /// Commands go unanswered, as with firmware that does not take commands.
+    pub silent: bool,
+    /// Commands starting with any of these are answered with `ERROR`.
+    pub rejects: Vec<&'static str>,
+    open: bool,
+}
+
+#[cfg(test)]
+impl MockTransport {
+    pub fn push(&self, data: &[u8]) {
+        self.incoming.lock().unwrap().extend(data);
+    }
+
+    /// Written command lines, without line endings.
+    pub fn commands(&self) -> Vec<String> {
+        String::from_utf8_lossy(&self.written.lock().unwrap()).lines().map(str::to_string).collect()
+    }
+}
+
+#[cfg(test)]
+impl CsiTransport for MockTransport {
+    fn open(&mut self) -> Result<()> {
+        if self.unavailable {
+            return Err(anyhow!("mock transport unavailable"));
+        }
+        self.open = true;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.open = false;
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        if !self.open {
+            return Err(anyhow!("mock transport not open"));
+        }
+        let mut incoming = self.incoming.lock().unwrap();
+        let n = buffer.len().min(incoming.len());
+        for (slot, byte) in buffer.iter_mut().zip(incoming.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        if !self.open {
+            return Err(anyhow!("mock transport not open"));
+        }
+        self.written.lock().unwrap().extend_from_slice(data);
+        if !self.silent {
+            for command in String::from_utf8_lossy(data).lines() {
+                let rejected = self.rejects.iter().any(|prefix| command.starts_with(prefix));
+                self.push(if rejected { b"ERROR\r\n" } else { b"OK\r\n" });
+            }
+        }
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::codec;
+    use crate::device::EspClient;
+    use crate::models::{ComplexNumber, CsiConfig, CsiMeasurement, DeviceConfig, TrafficConfig, TrafficKind, WifiConfig, WifiMode};
+    use std::time::Duration;
+
+    fn connected(mock: &MockTransport) -> EspClient<MockTransport> {
+        let mut client = EspClient::with_transport(mock.clone());
+        client.connect().unwrap();
+        client
+    }
+
+    fn frame(sequence: u32) -> CsiMeasurement {
+        CsiMeasurement {
+            timestamp: chrono::Utc::now(),
+            channel: 1,
+            bandwidth: 20,
+            rssi: -50,
+            noise_floor: -95,
+            subcarrier_data: vec![ComplexNumber::new(3.0, -4.0); 52],
+            sequence: Some(sequence),
+            device_timestamp_us: Some(sequence as u64 * 10_000),
+            device_id: None,
+            transmitter: None,
+        }
+    }
+
+    #[test]
+    fn commands_use_the_firmware_syntax() {
+        let mock = MockTransport::default();
+        let mut client = connected(&mock);
+        let config = DeviceConfig { channel: 11, bandwidth: 40, collection_interval_ms: 50, ..DeviceConfig::default() };
+        client.configure(&config).unwrap();
+        let wifi = WifiConfig {
+            mode: WifiMode::Station,
+            sta_ssid: "lab".to_string(),
+            sta_password: "secret123".to_string(),
+            ..WifiConfig::default()
+        };
+        client.set_wifi(&wifi).unwrap();
+        client.set_csi(&CsiConfig::default()).unwrap();
+        client.set_traffic(&TrafficConfig { kind: TrafficKind::Udp, frequency_hz: 100 }).unwrap();
+        client.start_collection().unwrap();
+        client.stop_collection().unwrap();
+
+        let commands = mock.commands();
+        assert_eq!(commands[0], "AT");
+        assert_eq!(commands[1], "AT+CSICFG=11,40,50");
+        assert!(commands[2].starts_with("AT+CSIWIFI=station,lab,secret123,"), "{}", commands[2]);
+        assert_eq!(commands[3], "AT+CSIFLAGS=lltf=1,htltf=1,stbc-htltf=1,ltf-merge=1");
+        assert_eq!(commands[4..], ["AT+CSITRAFFIC=udp,100", "AT+CSISTART", "AT+CSISTOP"]);
+    }
+
+    #[test]
+    fn frames_and_query_replies_are_read_from_the_stream() {
+        let mock = MockTransport::default();
+        let mut client = connected(&mock);
+        mock.push(codec::encode_text(&frame(1)).as_bytes());
+        mock.push(&codec::encode_binary(&frame(2)).unwrap());
+        mock.push(b"+INFO:version=1.2.3,chip=esp32,mac=aa:bb:cc:dd:ee:ff,mode=sniffer,channel=1,uptime_us=7\r\n");
+        mock.push(codec::encode_text(&frame(3)).as_bytes());
+
+        let info = client.query(Duration::from_millis(200)).unwrap();
+        assert_eq!((info.firmware_version.as_str(), info.chip.as_str()), ("1.2.3", "esp32"));
+        assert_eq!(mock.commands().last().unwrap(), "AT+CSIINFO?");
+
+        let mut sequences = Vec::new();
+        while let Some(m) = client.read_measurement().unwrap() {
+            sequences.push(m.sequence.unwrap());
+        }
+        assert_eq!(sequences, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn query_without_reply_times_out() {
+        let mock = MockTransport { silent: true, ..MockTransport::default() };
+        let mut client = connected(&mock);
+        assert!(client.query(Duration::from_millis(20)).is_err());
+    }
+
+    #[test]
+    fn rejected_commands_fail() {
+        let mock = MockTransport { rejects: vec!["AT+CSICFG", "AT+CSIINFO?"], ..MockTransport::default() };
+        let mut client = connected(&mock);
+        let error = client.configure(&DeviceConfig::default()).unwrap_err();
+        assert!(error.to_string().contains("rejected AT+CSICFG"), "{}", error);
+        assert!(client.query(Duration::from_millis(200)).is_err());
+        client.start_collection().unwrap();
+    }
+
+    #[test]
+    fn silent_firmware_gets_commands_unconfirmed() {
+        let mock = MockTransport { silent: true, ..MockTransport::default() };
+        let mut client = connected(&mock);
+        client.configure(&DeviceConfig::default()).unwrap();
+        client.start_collection().unwrap();
+        assert_eq!(mock.commands().len(), 3);
+
+        // Once the firmware has answered, a missing reply is an error.
+        mock.push(b"OK\r\n");
+        assert!(client.read_measurement().unwrap().is_none());
+        assert!(client.stop_collection().is_err());
+    }
+
+    #[test]
+    fn unavailable_transport_falls_back_to_demo_mode() {
+        let mock = MockTransport { unavailable: true, ..MockTransport::default() };
+        let mut client = connected(&mock);
+        assert!(client.is_demo_mode());
+        client.start_collection().unwrap();
+        assert!(client.read_measurement().unwrap().is_some());
+        assert!(mock.commands().is_empty());
+    }
+
+    #[test]
+    fn disconnect_closes_the_transport() {
+        let mock = MockTransport::default();
+        let mut client = connected(&mock);
+        client.disconnect().unwrap();
+        assert!(client.start_collection().is_err());
+    }
+
+    #[test]
+    fn addresses_pick_their_transport() {
+        assert_eq!(address_id("/dev/ttyUSB0"), "ttyUSB0");
+        assert_eq!(address_id("udp://0.0.0.0:5555"), "udp-5555");
+        assert_eq!(address_id("file://captures/lab.bin"), "file-lab");
+        assert!(from_address("udp://not-an-address", 115_200).is_err());
+        assert!(from_address("file://captures/lab.bin", 115_200).is_ok());
+    }
+}
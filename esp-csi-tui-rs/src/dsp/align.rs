@@ -0,0 +1,145 @@
+use crate::dsp::clock::ClockModel;
+use crate::dsp::resample::seconds_between;
+use crate::models::CsiMeasurement;
+use chrono::Duration;
+
+/// How one device's frames were placed on the shared host timeline.
+#[derive(Debug, Clone)]
+pub struct DeviceAlignment {
+    pub device_id: Option<String>,
+    /// Device-to-host clock, present when every frame of the device had a device timestamp.
+    pub clock: Option<ClockModel>,
+    /// Mean host-side delay beyond the best-case alignment, in milliseconds.
+    pub mean_delay_ms: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AlignedRecording {
+    /// All frames, timestamps moved to their estimated capture time, in time order.
+    pub measurements: Vec<CsiMeasurement>,
+    pub devices: Vec<DeviceAlignment>,
+}
+
+/// Puts frames from several receivers on one timeline.
+///
+/// Host read times include per-device serial latency and jitter, so frames captured
+/// together can arrive tens of milliseconds apart. For each device with device
+/// timestamps a [`ClockModel`] is fitted and its frames are re-stamped at the mapped
+/// capture time; devices without timestamps keep their host times. Remaining
+/// cross-device error is the difference in best-case transport latency.
+pub fn align(measurements: &[CsiMeasurement]) -> AlignedRecording {
+    let Some(epoch) = measurements.iter().map(|m| m.timestamp).min() else {
+        return AlignedRecording::default();
+    };
+
+    let mut ids: Vec<Option<String>> = Vec::new();
+    for m in measurements {
+        if !ids.contains(&m.device_id) {
+            ids.push(m.device_id.clone());
+        }
+    }
+
+    let mut aligned = Vec::with_capacity(measurements.len());
+    let mut devices = Vec::with_capacity(ids.len());
+    for id in ids {
+        let frames: Vec<&CsiMeasurement> = measurements.iter().filter(|m| m.device_id == id).collect();
+        let host: Vec<f64> = frames.iter().map(|m| seconds_between(epoch, m.timestamp)).collect();
+        let device: Option<Vec<f64>> = frames
+            .iter()
+            .map(|m| m.device_timestamp_us)
+            .collect::<Option<Vec<u64>>>()
+            .map(|ts| ts.iter().map(|&t| (t as i64 - ts[0] as i64) as f64 / 1e6).collect());
+        let clock = device.as_ref().and_then(|device| {
+            let pairs: Vec<(f64, f64)> = device.iter().copied().zip(host.iter().copied()).collect();
+            ClockModel::fit(&pairs)
+        });
+
+        let mut delay_sum = 0.0;
+        for (i, frame) in frames.iter().enumerate() {
+            let mut frame = (*frame).clone();
+            if let (Some(clock), Some(device)) = (clock, device.as_ref()) {
+                delay_sum += clock.delay(device[i], host[i]);
+                frame.timestamp = epoch + Duration::microseconds((clock.to_host(device[i]) * 1e6) as i64);
+            }
+            aligned.push(frame);
+        }
+
+        devices.push(DeviceAlignment {
+            device_id: id,
+            clock,
+            mean_delay_ms: delay_sum / frames.len() as f64 * 1e3,
+        });
+    }
+
+    // Stable, so frames with equal times keep their arrival order.
+    aligned.sort_by_key(|m| m.timestamp);
+    AlignedRecording { measurements: aligned, devices }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ComplexNumber;
+    use chrono::{DateTime, Utc};
+
+    /// Frame `i` of a device captured at `i * 100 ms` and read `latency_ms` plus 0-9 ms
+    /// of jitter later, with device uptime starting at `uptime_us`.
+    fn frame(id: &str, i: i64, latency_ms: i64, uptime_us: Option<u64>) -> CsiMeasurement {
+        let epoch = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        CsiMeasurement {
+            timestamp: epoch + Duration::milliseconds(i * 100 + latency_ms + (i * 7) % 10),
+            channel: 6,
+            bandwidth: 20,
+            rssi: -40,
+            noise_floor: -90,
+            subcarrier_data: vec![ComplexNumber::new(1.0, 0.0)],
+            sequence: Some(i as u32),
+            device_timestamp_us: uptime_us.map(|t| t + i as u64 * 100_000),
+            device_id: Some(id.to_string()),
+            transmitter: None,
+        }
+    }
+
+    fn times(recording: &AlignedRecording, id: &str) -> Vec<DateTime<Utc>> {
+        recording
+            .measurements
+            .iter()
+            .filter(|m| m.device_id.as_deref() == Some(id))
+            .map(|m| m.timestamp)
+            .collect()
+    }
+
+    #[test]
+    fn device_clocks_remove_read_jitter() {
+        let mut frames = Vec::new();
+        for i in 0..50 {
+            frames.push(frame("a", i, 5, Some(1_000_000)));
+            frames.push(frame("b", i, 40, Some(77_000_000)));
+            frames.push(frame("c", i, 20, None));
+        }
+        let recording = align(&frames);
+
+        let ids: Vec<Option<&str>> = recording.devices.iter().map(|d| d.device_id.as_deref()).collect();
+        assert_eq!(ids, vec![Some("a"), Some("b"), Some("c")]);
+        assert_eq!(recording.measurements.len(), 150);
+        assert!(recording.measurements.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        // Re-stamped frames are evenly spaced, offset by each device's best-case latency.
+        for (id, latency) in [("a", 5), ("b", 40)] {
+            let device = recording.devices.iter().find(|d| d.device_id.as_deref() == Some(id)).unwrap();
+            assert!(device.clock.is_some());
+            assert!((device.mean_delay_ms - 4.5).abs() < 1.0, "{} delay {}", id, device.mean_delay_ms);
+            let times = times(&recording, id);
+            let first = DateTime::from_timestamp(1_704_067_200, 0).unwrap() + Duration::milliseconds(latency);
+            for (i, t) in times.iter().enumerate() {
+                let error = (*t - (first + Duration::milliseconds(i as i64 * 100))).num_microseconds().unwrap();
+                assert!(error.abs() < 1_000, "{} frame {} off by {} us", id, i, error);
+            }
+        }
+
+        // Without device timestamps the host read times are kept.
+        assert!(recording.devices[2].clock.is_none());
+        let expected: Vec<DateTime<Utc>> = frames.iter().filter(|m| m.device_id.as_deref() == Some("c")).map(|m| m.timestamp).collect();
+        assert_eq!(times(&recording, "c"), expected);
+    }
+}
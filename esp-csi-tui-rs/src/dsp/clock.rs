@@ -0,0 +1,85 @@
+/// Linear mapping from device time onto host time: `host = offset + rate * device`.
+///
+/// Host read times are the device capture time plus a non-negative delay (serial
+/// latency, buffering, UI-loop jitter). The rate is fitted by least squares and the
+/// offset is taken from the lower envelope of the residuals, i.e. the least-delayed
+/// frame, so that jitter does not bias the alignment.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockModel {
+    pub offset_s: f64,
+    pub rate: f64,
+}
+
+impl Default for ClockModel {
+    fn default() -> Self {
+        Self { offset_s: 0.0, rate: 1.0 }
+    }
+}
+
+impl ClockModel {
+    /// Fits the model to `(device_s, host_s)` pairs. Needs at least two distinct device times.
+    pub fn fit(pairs: &[(f64, f64)]) -> Option<Self> {
+        if pairs.len() < 2 {
+            return None;
+        }
+
+        let n = pairs.len() as f64;
+        let mean_d = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_h = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+        let var_d: f64 = pairs.iter().map(|p| (p.0 - mean_d).powi(2)).sum();
+        if var_d <= f64::EPSILON {
+            return None;
+        }
+        let cov: f64 = pairs.iter().map(|p| (p.0 - mean_d) * (p.1 - mean_h)).sum();
+        let rate = cov / var_d;
+
+        let offset_s = pairs
+            .iter()
+            .map(|(d, h)| h - rate * d)
+            .fold(f64::INFINITY, f64::min);
+
+        Some(Self { offset_s, rate })
+    }
+
+    pub fn to_host(self, device_s: f64) -> f64 {
+        self.offset_s + self.rate * device_s
+    }
+
+    /// Host-side delay of a frame beyond the best-case alignment.
+    pub fn delay(self, device_s: f64, host_s: f64) -> f64 {
+        host_s - self.to_host(device_s)
+    }
+
+    /// Relative clock drift in parts per million.
+    pub fn drift_ppm(self) -> f64 {
+        (self.rate - 1.0) * 1e6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_recovers_drift_and_least_delayed_offset() {
+        // 50 ppm fast host clock, 2 s offset, and 0-9 ms of delay on every frame but the first.
+        let pairs: Vec<(f64, f64)> = (0..1000)
+            .map(|i| {
+                let device = i as f64 * 0.1;
+                let delay = if i == 0 { 0.0 } else { (i * 7 % 10) as f64 * 1e-3 };
+                (device, 2.0 + device * (1.0 + 50e-6) + delay)
+            })
+            .collect();
+
+        let clock = ClockModel::fit(&pairs).unwrap();
+        assert!((clock.drift_ppm() - 50.0).abs() < 10.0, "drift {} ppm", clock.drift_ppm());
+        assert!((clock.to_host(0.0) - 2.0).abs() < 1e-3, "offset {}", clock.offset_s);
+        assert!(pairs.iter().all(|&(d, h)| clock.delay(d, h) >= -1e-9));
+    }
+
+    #[test]
+    fn fit_needs_two_distinct_device_times() {
+        assert!(ClockModel::fit(&[(1.0, 3.0)]).is_none());
+        assert!(ClockModel::fit(&[(1.0, 3.0), (1.0, 3.5)]).is_none());
+    }
+}
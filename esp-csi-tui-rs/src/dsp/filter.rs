@@ -0,0 +1,87 @@
+use anyhow::{Result, anyhow};
+
+/// Second-order IIR section (RBJ audio-EQ cookbook), Butterworth Q.
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+}
+
+impl Biquad {
+    pub fn low_pass(cutoff_hz: f32, sample_rate_hz: f32) -> Result<Self> {
+        let (cos, alpha) = Self::prewarp(cutoff_hz, sample_rate_hz)?;
+        Ok(Self::normalised([(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0], cos, alpha))
+    }
+
+    pub fn high_pass(cutoff_hz: f32, sample_rate_hz: f32) -> Result<Self> {
+        let (cos, alpha) = Self::prewarp(cutoff_hz, sample_rate_hz)?;
+        Ok(Self::normalised([(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0], cos, alpha))
+    }
+
+    fn prewarp(cutoff_hz: f32, sample_rate_hz: f32) -> Result<(f32, f32)> {
+        if cutoff_hz <= 0.0 || cutoff_hz >= sample_rate_hz / 2.0 {
+            return Err(anyhow!(
+                "Cutoff {} Hz must lie between 0 and Nyquist ({} Hz)",
+                cutoff_hz,
+                sample_rate_hz / 2.0
+            ));
+        }
+        let w0 = 2.0 * std::f32::consts::PI * cutoff_hz / sample_rate_hz;
+        Ok((w0.cos(), w0.sin() / std::f32::consts::SQRT_2))
+    }
+
+    fn normalised(b: [f32; 3], cos: f32, alpha: f32) -> Self {
+        let a0 = 1.0 + alpha;
+        Self {
+            b: [b[0] / a0, b[1] / a0, b[2] / a0],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        }
+    }
+
+    pub fn apply(&self, signal: &[f32]) -> Vec<f32> {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        signal
+            .iter()
+            .map(|&x| {
+                let y = self.b[0] * x + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+                (x2, x1, y2, y1) = (x1, x, y1, y);
+                y
+            })
+            .collect()
+    }
+
+    /// Forward-backward filtering: zero phase, squared magnitude response.
+    pub fn filtfilt(&self, signal: &[f32]) -> Vec<f32> {
+        let mut forward = self.apply(signal);
+        forward.reverse();
+        let mut output = self.apply(&forward);
+        output.reverse();
+        output
+    }
+}
+
+/// Zero-phase Butterworth band-pass built from a high-pass and a low-pass section.
+#[derive(Debug, Clone, Copy)]
+pub struct BandPass {
+    high_pass: Biquad,
+    low_pass: Biquad,
+}
+
+impl BandPass {
+    pub fn new(low_hz: f32, high_hz: f32, sample_rate_hz: f32) -> Result<Self> {
+        if low_hz >= high_hz {
+            return Err(anyhow!("Band-pass low edge {} Hz must be below high edge {} Hz", low_hz, high_hz));
+        }
+        Ok(Self {
+            high_pass: Biquad::high_pass(low_hz, sample_rate_hz)?,
+            low_pass: Biquad::low_pass(high_hz, sample_rate_hz)?,
+        })
+    }
+
+    /// Removes the mean first so the high-pass section does not ring on the DC step.
+    pub fn filtfilt(&self, signal: &[f32]) -> Vec<f32> {
+        let mean = signal.iter().sum::<f32>() / signal.len().max(1) as f32;
+        let centred: Vec<f32> = signal.iter().map(|x| x - mean).collect();
+        self.low_pass.filtfilt(&self.high_pass.filtfilt(&centred))
+    }
+}
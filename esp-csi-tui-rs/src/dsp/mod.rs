@@ -0,0 +1,9 @@
+pub mod align;
+pub mod clock;
+pub mod filter;
+pub mod pca;
+pub mod resample;
+pub mod spectrogram;
+
+pub use resample::Resampler;
+pub use spectrogram::{Spectrogram, SpectrogramConfig, SpectrogramProcessor, TimeFrequencyAnalysis};
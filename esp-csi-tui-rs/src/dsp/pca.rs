@@ -0,0 +1,415 @@
+use crate::models::CsiMeasurement;
+use chrono::{DateTime, Utc};
+use ndarray::{Array1, Array2, Axis};
+use std::collections::VecDeque;
+
+const POWER_ITERATIONS: usize = 200;
+const CONVERGENCE: f32 = 1e-7;
+
+/// Principal components of a `time × subcarrier` matrix.
+#[derive(Debug, Clone)]
+pub struct Pca {
+    pub mean: Array1<f32>,
+    /// Unit eigenvectors of the covariance, one per row, strongest first.
+    pub components: Array2<f32>,
+    pub eigenvalues: Array1<f32>,
+    /// Trace of the covariance, i.e. the variance summed over all subcarriers.
+    pub total_variance: f32,
+}
+
+impl Pca {
+    /// Fits the top `k` components by power iteration with deflation on the covariance
+    /// matrix; plenty for the ≤114 subcarriers a frame carries.
+    pub fn fit(data: &Array2<f32>, k: usize) -> Option<Self> {
+        let rows = data.nrows();
+        if rows < 2 || data.ncols() == 0 {
+            return None;
+        }
+        let mean = data.mean_axis(Axis(0))?;
+        let centred = data - &mean;
+        let covariance = centred.t().dot(&centred) / (rows - 1) as f32;
+        Some(Self::from_covariance(mean, covariance, k, None))
+    }
+
+    /// Decomposes a covariance matrix. `warm_start` rows seed the power iteration, which
+    /// converges in a few steps when the components have barely moved.
+    pub fn from_covariance(
+        mean: Array1<f32>,
+        mut covariance: Array2<f32>,
+        k: usize,
+        warm_start: Option<&Array2<f32>>,
+    ) -> Self {
+        let cols = covariance.ncols();
+        let total_variance = covariance.diag().sum();
+
+        let k = k.min(cols);
+        let mut components = Array2::zeros((k, cols));
+        let mut eigenvalues = Array1::zeros(k);
+        for c in 0..k {
+            let start = warm_start
+                .filter(|w| w.ncols() == cols && c < w.nrows())
+                .map(|w| w.row(c).to_owned());
+            let (value, vector) = dominant_eigenpair(&covariance, start);
+            eigenvalues[c] = value;
+            components.row_mut(c).assign(&vector);
+            let outer = vector
+                .view()
+                .insert_axis(Axis(1))
+                .dot(&vector.view().insert_axis(Axis(0)));
+            covariance = covariance - outer * value;
+        }
+
+        Self { mean, components, eigenvalues, total_variance }
+    }
+
+    /// Share of the total variance captured by each component.
+    pub fn explained_variance_ratio(&self) -> Vec<f32> {
+        self.eigenvalues
+            .iter()
+            .map(|v| v / self.total_variance.max(f32::EPSILON))
+            .collect()
+    }
+
+    /// Projects `time × subcarrier` data onto the components, giving `time × k` scores.
+    pub fn project(&self, data: &Array2<f32>) -> Array2<f32> {
+        (data - &self.mean).dot(&self.components.t())
+    }
+}
+
+/// How much a subcarrier varies and how strongly it drives the top components.
+#[derive(Debug, Clone, Copy)]
+pub struct SubcarrierRank {
+    pub index: usize,
+    /// Amplitude variance normalised by the squared mean.
+    pub relative_variance: f32,
+    /// Share of the top components' variance carried by this subcarrier
+    /// (`Σ λ_c v_c[i]²` over components, normalised to sum to 1).
+    pub sensitivity: f32,
+}
+
+/// PCA over a sliding window of the most recent frames.
+///
+/// Running sums of `x` and `x xᵀ` (in f64, so removing old frames does not lose
+/// precision) are updated per frame in O(n²); the eigen-decomposition only runs on
+/// [`StreamingPca::refresh`], warm-started from the previous components.
+#[derive(Debug, Clone)]
+pub struct StreamingPca {
+    window: usize,
+    num_components: usize,
+    frames: VecDeque<(DateTime<Utc>, Array1<f32>)>,
+    sum: Array1<f64>,
+    sum_outer: Array2<f64>,
+    pca: Option<Pca>,
+}
+
+impl StreamingPca {
+    pub fn new(window: usize, num_components: usize) -> Self {
+        Self {
+            window: window.max(2),
+            num_components,
+            frames: VecDeque::new(),
+            sum: Array1::zeros(0),
+            sum_outer: Array2::zeros((0, 0)),
+            pca: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn pca(&self) -> Option<&Pca> {
+        self.pca.as_ref()
+    }
+
+    /// Adds a frame's amplitudes, dropping the oldest once the window is full. A change
+    /// in subcarrier count (e.g. a bandwidth switch) restarts the window.
+    pub fn push(&mut self, measurement: &CsiMeasurement) {
+        let frame: Array1<f32> = measurement.subcarrier_data.iter().map(|c| c.magnitude()).collect();
+        if frame.is_empty() {
+            return;
+        }
+        if frame.len() != self.sum.len() {
+            let n = frame.len();
+            self.frames.clear();
+            self.sum = Array1::zeros(n);
+            self.sum_outer = Array2::zeros((n, n));
+            self.pca = None;
+        }
+
+        self.accumulate(&frame, 1.0);
+        self.frames.push_back((measurement.timestamp, frame));
+        if self.frames.len() > self.window {
+            if let Some((_, old)) = self.frames.pop_front() {
+                self.accumulate(&old, -1.0);
+            }
+        }
+    }
+
+    fn accumulate(&mut self, frame: &Array1<f32>, sign: f64) {
+        let x = frame.mapv(|v| v as f64);
+        self.sum.scaled_add(sign, &x);
+        let outer = x.view().insert_axis(Axis(1)).dot(&x.view().insert_axis(Axis(0)));
+        self.sum_outer.scaled_add(sign, &outer);
+    }
+
+    /// Recomputes the components from the current window.
+    pub fn refresh(&mut self) -> Option<&Pca> {
+        let count = self.frames.len();
+        if count < 2 {
+            return None;
+        }
+        let n = count as f64;
+        let mean = &self.sum / n;
+        let outer = mean.view().insert_axis(Axis(1)).dot(&mean.view().insert_axis(Axis(0)));
+        let covariance = (&self.sum_outer - &(outer * n)) / (n - 1.0);
+
+        let warm_start = self.pca.as_ref().map(|p| p.components.clone());
+        self.pca = Some(Pca::from_covariance(
+            mean.mapv(|v| v as f32),
+            covariance.mapv(|v| v as f32),
+            self.num_components,
+            warm_start.as_ref(),
+        ));
+        self.pca.as_ref()
+    }
+
+    /// Timestamps and `time × k` component scores of the frames in the window.
+    pub fn component_series(&self) -> Option<(Vec<DateTime<Utc>>, Array2<f32>)> {
+        let pca = self.pca.as_ref()?;
+        let n = self.sum.len();
+        let data = Array2::from_shape_vec(
+            (self.frames.len(), n),
+            self.frames.iter().flat_map(|(_, f)| f.iter().copied()).collect(),
+        )
+        .ok()?;
+        let times = self.frames.iter().map(|(t, _)| *t).collect();
+        Some((times, pca.project(&data)))
+    }
+
+    /// Subcarriers ordered by sensitivity, most informative first.
+    pub fn rank_subcarriers(&self) -> Vec<SubcarrierRank> {
+        let Some(pca) = self.pca.as_ref() else {
+            return Vec::new();
+        };
+        let n = self.frames.len() as f64;
+        let mut weights = vec![0.0f32; pca.mean.len()];
+        for (value, component) in pca.eigenvalues.iter().zip(pca.components.rows()) {
+            for (w, v) in weights.iter_mut().zip(component) {
+                *w += value.max(0.0) * v * v;
+            }
+        }
+        let total: f32 = weights.iter().sum::<f32>().max(f32::EPSILON);
+
+        let mut ranks: Vec<SubcarrierRank> = weights
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let mean = self.sum[i] / n;
+                let variance = (self.sum_outer[[i, i]] / n - mean * mean).max(0.0);
+                SubcarrierRank {
+                    index: i,
+                    relative_variance: (variance / (mean * mean).max(f64::EPSILON)) as f32,
+                    sensitivity: w / total,
+                }
+            })
+            .collect();
+        ranks.sort_by(|a, b| b.sensitivity.total_cmp(&a.sensitivity));
+        ranks
+    }
+}
+
+fn dominant_eigenpair(matrix: &Array2<f32>, start: Option<Array1<f32>>) -> (f32, Array1<f32>) {
+    let n = matrix.nrows();
+    // Deterministic, non-symmetric start so it is unlikely to be orthogonal to the answer.
+    let mut vector = start
+        .filter(|v| v.dot(v) > f32::EPSILON)
+        .unwrap_or_else(|| Array1::from_iter((0..n).map(|i| 1.0 + i as f32 / n as f32)));
+    vector /= vector.dot(&vector).sqrt();
+
+    for _ in 0..POWER_ITERATIONS {
+        let next = matrix.dot(&vector);
+        let norm = next.dot(&next).sqrt();
+        if norm <= f32::EPSILON {
+            return (0.0, vector);
+        }
+        let next = next / norm;
+        let delta = (&next - &vector).mapv(f32::abs).sum();
+        vector = next;
+        if delta < CONVERGENCE {
+            break;
+        }
+    }
+
+    (vector.dot(&matrix.dot(&vector)), vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_finds_known_components() {
+        // Scores of 3s and 1c along u1 = (1,1,1,1)/2 and u2 = (1,-1,1,-1)/2, where s and c
+        // are orthogonal unit-variance +-1 patterns, around a mean of 5.
+        let n = 40;
+        let data = Array2::from_shape_fn((n, 4), |(i, j)| {
+            let s = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let c = if i % 4 < 2 { 1.0 } else { -1.0 };
+            let u2 = if j % 2 == 0 { 0.5 } else { -0.5 };
+            5.0 + 3.0 * s * 0.5 + c * u2
+        });
+
+        let pca = Pca::fit(&data, 3).unwrap();
+        let scale = n as f32 / (n - 1) as f32;
+        assert!((pca.eigenvalues[0] - 9.0 * scale).abs() < 1e-3);
+        assert!((pca.eigenvalues[1] - scale).abs() < 1e-3);
+        assert!(pca.eigenvalues[2].abs() < 1e-3);
+        assert!((pca.total_variance - 10.0 * scale).abs() < 1e-3);
+        let ratios = pca.explained_variance_ratio();
+        assert!((ratios[0] - 0.9).abs() < 1e-4 && (ratios[1] - 0.1).abs() < 1e-4);
+
+        // Components are unit vectors, up to sign.
+        for (component, expected) in pca.components.rows().into_iter().zip([[0.5; 4], [0.5, -0.5, 0.5, -0.5]]) {
+            let dot: f32 = component.iter().zip(expected).map(|(a, b)| a * b).sum();
+            assert!((dot.abs() - 1.0).abs() < 1e-4, "component {} off by {}", component, dot);
+        }
+        assert!(pca.mean.iter().all(|m| (m - 5.0).abs() < 1e-5));
+
+        let scores = pca.project(&data);
+        for (i, score) in scores.column(0).iter().enumerate() {
+            assert!((score.abs() - 3.0).abs() < 1e-3, "score {} is {}", i, score);
+        }
+        assert!(Pca::fit(&data.slice(ndarray::s![..1, ..]).to_owned(), 2).is_none());
+    }
+
+    /// Right singular vectors and singular values of `a` by one-sided Jacobi rotations,
+    /// strongest first: an independent reference for the power iteration.
+    fn jacobi_svd(mut a: Array2<f64>) -> (Array2<f64>, Vec<f64>) {
+        let n = a.ncols();
+        let mut v = Array2::<f64>::eye(n);
+        for _ in 0..50 {
+            let mut rotated = false;
+            for p in 0..n {
+                for q in p + 1..n {
+                    let alpha = a.column(p).dot(&a.column(p));
+                    let beta = a.column(q).dot(&a.column(q));
+                    let gamma = a.column(p).dot(&a.column(q));
+                    if gamma.abs() <= 1e-15 * (alpha * beta).sqrt() {
+                        continue;
+                    }
+                    rotated = true;
+                    let zeta = (beta - alpha) / (2.0 * gamma);
+                    let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                    let c = 1.0 / (1.0 + t * t).sqrt();
+                    for m in [&mut a, &mut v] {
+                        let (col_p, col_q) = (m.column(p).to_owned(), m.column(q).to_owned());
+                        m.column_mut(p).assign(&(&col_p * c - &col_q * (c * t)));
+                        m.column_mut(q).assign(&(&col_p * (c * t) + &col_q * c));
+                    }
+                }
+            }
+            if !rotated {
+                break;
+            }
+        }
+        let mut order: Vec<(f64, usize)> = (0..n).map(|j| (a.column(j).dot(&a.column(j)).sqrt(), j)).collect();
+        order.sort_by(|x, y| y.0.total_cmp(&x.0));
+        let vectors = Array2::from_shape_fn((n, n), |(r, c)| v[[c, order[r].1]]);
+        (vectors, order.iter().map(|o| o.0).collect())
+    }
+
+    /// Six subcarriers driven by two latent motions plus a little noise.
+    fn correlated_frames(count: usize) -> Vec<CsiMeasurement> {
+        let start = chrono::DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        let mut state = 12345u64;
+        let mut noise = move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        };
+        (0..count)
+            .map(|i| {
+                let t = i as f32 * 0.1;
+                let (slow, fast) = ((0.3 * t).sin() * 4.0, (2.1 * t).sin() * 1.5);
+                let subcarrier_data = (0..6)
+                    .map(|k| {
+                        let k = k as f32;
+                        let amplitude = 20.0 + slow * (1.0 + 0.2 * k) + fast * (k - 2.5) * 0.4 + noise() * 0.2;
+                        crate::models::ComplexNumber::new(amplitude, 0.0)
+                    })
+                    .collect();
+                CsiMeasurement {
+                    timestamp: start + chrono::Duration::milliseconds(i as i64 * 100),
+                    channel: 6,
+                    bandwidth: 20,
+                    rssi: -40,
+                    noise_floor: -90,
+                    subcarrier_data,
+                    sequence: None,
+                    device_timestamp_us: None,
+                    device_id: None,
+                    transmitter: None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn streaming_components_match_batch_svd() {
+        let (window, frames) = (200, correlated_frames(500));
+        let mut streaming = StreamingPca::new(window, 3);
+        for (i, frame) in frames.iter().enumerate() {
+            streaming.push(frame);
+            // Refresh along the way so the final decomposition is warm-started.
+            if i % 50 == 49 {
+                streaming.refresh();
+            }
+        }
+        assert_eq!(streaming.len(), window);
+        let pca = streaming.refresh().unwrap().clone();
+
+        let recent = &frames[frames.len() - window..];
+        let data = Array2::from_shape_fn((window, 6), |(r, c)| recent[r].subcarrier_data[c].real as f64);
+        let mean = data.mean_axis(Axis(0)).unwrap();
+        let (vectors, singular) = jacobi_svd(&data - &mean);
+
+        for (m, expected) in pca.mean.iter().zip(&mean) {
+            assert!((*m as f64 - expected).abs() < 1e-3);
+        }
+        for (c, (value, s)) in pca.eigenvalues.iter().zip(&singular).enumerate() {
+            let eigenvalue = s.powi(2) / (window - 1) as f64;
+            let relative = (*value as f64 - eigenvalue).abs() / eigenvalue;
+            assert!(relative < 1e-3, "eigenvalue {} is {}, SVD gives {}", c, value, eigenvalue);
+        }
+        // The third component is mostly noise, so only the two latent motions are
+        // well-conditioned enough to compare directions.
+        for c in 0..2 {
+            let dot: f64 = pca.components.row(c).iter().zip(vectors.row(c)).map(|(a, b)| *a as f64 * b).sum();
+            assert!((dot.abs() - 1.0).abs() < 1e-4, "component {} is off by {}", c, dot);
+        }
+        let total: f64 = singular.iter().map(|s| s * s).sum::<f64>() / (window - 1) as f64;
+        assert!((pca.total_variance as f64 - total).abs() / total < 1e-4);
+
+        let ranks = streaming.rank_subcarriers();
+        assert_eq!(ranks.len(), 6);
+        assert!((ranks.iter().map(|r| r.sensitivity).sum::<f32>() - 1.0).abs() < 1e-4);
+        assert!(ranks.windows(2).all(|w| w[0].sensitivity >= w[1].sensitivity));
+        let (times, scores) = streaming.component_series().unwrap();
+        assert_eq!((times.len(), scores.dim()), (window, (window, 3)));
+    }
+
+    #[test]
+    fn subcarrier_count_change_restarts_the_window() {
+        let mut streaming = StreamingPca::new(50, 2);
+        for frame in correlated_frames(30) {
+            streaming.push(&frame);
+        }
+        streaming.refresh();
+        let mut narrow = correlated_frames(1).remove(0);
+        narrow.subcarrier_data.truncate(4);
+        streaming.push(&narrow);
+        assert_eq!(streaming.len(), 1);
+        assert!(streaming.pca().is_none());
+        assert!(streaming.refresh().is_none());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::dsp::clock::ClockModel;
+use crate::models::{ComplexNumber, CsiMeasurement};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use std::ops::Range;
+
+/// A device clock stepping back further than this has restarted, rather than
+/// delivered a late frame.
+const CLOCK_RESET_US: u64 = 1_000_000;
+
+/// Subcarrier amplitudes on a fixed-rate time grid.
+#[derive(Debug, Clone)]
+pub struct UniformSeries {
+    pub start: DateTime<Utc>,
+    /// Indexed as `samples[time][subcarrier]`.
+    pub samples: Vec<Vec<f32>>,
+}
+
+impl UniformSeries {
+    pub fn num_subcarriers(&self) -> usize {
+        self.samples.first().map(|s| s.len()).unwrap_or(0)
+    }
+
+    pub fn subcarrier(&self, index: usize) -> Vec<f32> {
+        self.samples.iter().map(|s| s[index]).collect()
+    }
+
+    pub fn averaged(&self) -> Vec<f32> {
+        self.samples
+            .iter()
+            .map(|s| s.iter().sum::<f32>() / s.len().max(1) as f32)
+            .collect()
+    }
+}
+
+/// Measurements interpolated onto a fixed-rate grid, plus what was learned about
+/// the input timing along the way.
+#[derive(Debug, Clone)]
+pub struct ResampledSeries {
+    pub start: DateTime<Utc>,
+    /// One measurement per grid point; complex subcarriers are linearly interpolated
+    /// and scalar fields are taken from the nearest input frame.
+    pub measurements: Vec<CsiMeasurement>,
+    /// Device-to-host clock alignment since the latest device restart, present when
+    /// every input frame carried a device timestamp.
+    pub clock: Option<ClockModel>,
+    /// Frames missing according to gaps in the device sequence counter, summed over
+    /// the stretches between device restarts.
+    pub dropped_frames: u64,
+    /// Standard deviation of host-side arrival jitter, in milliseconds.
+    pub jitter_ms: f64,
+}
+
+impl ResampledSeries {
+    fn empty() -> Self {
+        Self {
+            start: Utc::now(),
+            measurements: Vec::new(),
+            clock: None,
+            dropped_frames: 0,
+            jitter_ms: 0.0,
+        }
+    }
+
+    pub fn amplitudes(&self) -> UniformSeries {
+        UniformSeries {
+            start: self.start,
+            samples: self
+                .measurements
+                .iter()
+                .map(|m| m.subcarrier_data.iter().map(|c| c.magnitude()).collect())
+                .collect(),
+        }
+    }
+}
+
+pub struct Resampler;
+
+impl Resampler {
+    /// Interpolates measurements onto a uniform grid starting at the first usable frame.
+    ///
+    /// When every frame has a device timestamp, frames are placed at their device capture
+    /// time mapped through a fitted [`ClockModel`]; otherwise host read times are used as-is.
+    /// Only frames with the same subcarrier count as the latest one are used.
+    pub fn resample(measurements: &[CsiMeasurement], sample_rate_hz: f32) -> Result<ResampledSeries> {
+        if sample_rate_hz <= 0.0 {
+            return Err(anyhow!("Sample rate must be positive, got {}", sample_rate_hz));
+        }
+
+        let Some(latest) = measurements.last() else {
+            return Ok(ResampledSeries::empty());
+        };
+        let num_subcarriers = latest.subcarrier_data.len();
+        let usable: Vec<&CsiMeasurement> = measurements
+            .iter()
+            .filter(|m| m.subcarrier_data.len() == num_subcarriers)
+            .collect();
+
+        let start = usable[0].timestamp;
+        let host: Vec<f64> = usable.iter().map(|m| seconds_between(start, m.timestamp)).collect();
+
+        let stretches = stretches(&usable);
+        let device: Option<Vec<u64>> = usable.iter().map(|m| m.device_timestamp_us).collect();
+        let aligned = device.and_then(|device| align_to_host(&device, &host, &stretches));
+
+        let (times, jitter_ms, clock) = match aligned {
+            Some((times, delays, clock)) => (times, std_dev(&delays) * 1e3, Some(clock)),
+            None => {
+                let intervals: Vec<f64> = host.windows(2).map(|w| w[1] - w[0]).collect();
+                (host.clone(), std_dev(&intervals) * 1e3, None)
+            }
+        };
+
+        let dropped_frames = stretches.iter().map(|r| sequence_gaps(&usable[r.clone()])).sum();
+
+        let mut kept_times: Vec<f64> = Vec::with_capacity(times.len());
+        let mut kept: Vec<&CsiMeasurement> = Vec::with_capacity(times.len());
+        for (t, m) in times.into_iter().zip(usable.iter()) {
+            if kept_times.last().is_some_and(|&last| t <= last) {
+                continue;
+            }
+            kept_times.push(t);
+            kept.push(m);
+        }
+
+        let grid_start = start + Duration::microseconds((kept_times[0] * 1e6) as i64);
+        Ok(ResampledSeries {
+            start: grid_start,
+            measurements: interpolate(&kept_times, &kept, grid_start, sample_rate_hz as f64),
+            clock,
+            dropped_frames,
+            jitter_ms,
+        })
+    }
+
+    pub fn amplitudes(measurements: &[CsiMeasurement], sample_rate_hz: f32) -> Result<UniformSeries> {
+        Ok(Self::resample(measurements, sample_rate_hz)?.amplitudes())
+    }
+}
+
+pub(crate) fn seconds_between(start: DateTime<Utc>, t: DateTime<Utc>) -> f64 {
+    (t - start).num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6
+}
+
+/// Splits frames wherever the device clock steps back past [`CLOCK_RESET_US`], i.e.
+/// at device restarts. Frames without a device timestamp never start a stretch.
+fn stretches(frames: &[&CsiMeasurement]) -> Vec<Range<usize>> {
+    let mut stretches = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<u64> = None;
+    for (i, t) in frames.iter().enumerate().filter_map(|(i, m)| Some((i, m.device_timestamp_us?))) {
+        if previous.is_some_and(|p| p > t.saturating_add(CLOCK_RESET_US)) {
+            stretches.push(start..i);
+            start = i;
+        }
+        previous = Some(t);
+    }
+    stretches.push(start..frames.len());
+    stretches
+}
+
+/// Maps device capture times onto host time with a [`ClockModel`] fitted to each
+/// stretch between device restarts. Frames of a stretch too short to fit keep their
+/// host time. Returns the times, the delay of each fitted frame and the clock of the
+/// latest fitted stretch.
+fn align_to_host(device_us: &[u64], host: &[f64], stretches: &[Range<usize>]) -> Option<(Vec<f64>, Vec<f64>, ClockModel)> {
+    let mut times = host.to_vec();
+    let mut delays = Vec::new();
+    let mut latest = None;
+    for stretch in stretches {
+        let origin = device_us[stretch.start] as i64;
+        let device: Vec<f64> = device_us[stretch.clone()].iter().map(|&t| (t as i64 - origin) as f64 / 1e6).collect();
+        let host = &host[stretch.clone()];
+        let pairs: Vec<(f64, f64)> = device.iter().copied().zip(host.iter().copied()).collect();
+        let Some(clock) = ClockModel::fit(&pairs) else {
+            continue;
+        };
+        for ((slot, &d), &h) in times[stretch.clone()].iter_mut().zip(&device).zip(host) {
+            *slot = clock.to_host(d);
+            delays.push(clock.delay(d, h));
+        }
+        latest = Some(clock);
+    }
+    latest.map(|clock| (times, delays, clock))
+}
+
+/// Counts frames skipped by the device sequence counter. Backwards steps (reorders,
+/// duplicates) are not counted as loss.
+fn sequence_gaps(measurements: &[&CsiMeasurement]) -> u64 {
+    let mut dropped = 0u64;
+    let mut previous: Option<u32> = None;
+    for seq in measurements.iter().filter_map(|m| m.sequence) {
+        if let Some(prev) = previous {
+            let step = seq.wrapping_sub(prev);
+            if step == 0 || step > u32::MAX / 2 {
+                continue;
+            }
+            dropped += (step - 1) as u64;
+        }
+        previous = Some(seq);
+    }
+    dropped
+}
+
+fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64).sqrt()
+}
+
+fn interpolate(
+    times: &[f64],
+    frames: &[&CsiMeasurement],
+    grid_start: DateTime<Utc>,
+    sample_rate_hz: f64,
+) -> Vec<CsiMeasurement> {
+    if times.len() < 2 {
+        return frames.iter().map(|&m| m.clone()).collect();
+    }
+
+    let duration = times[times.len() - 1] - times[0];
+    let count = (duration * sample_rate_hz).floor() as usize + 1;
+    let mut samples = Vec::with_capacity(count);
+    let mut upper = 1;
+
+    for k in 0..count {
+        let offset = k as f64 / sample_rate_hz;
+        let t = times[0] + offset;
+        while upper < times.len() - 1 && times[upper] < t {
+            upper += 1;
+        }
+        let (a, b) = (frames[upper - 1], frames[upper]);
+        let (t0, t1) = (times[upper - 1], times[upper]);
+        let w = ((t - t0) / (t1 - t0)).clamp(0.0, 1.0) as f32;
+        let nearest = if w < 0.5 { a } else { b };
+
+        samples.push(CsiMeasurement {
+            timestamp: grid_start + Duration::microseconds((offset * 1e6) as i64),
+            channel: nearest.channel,
+            bandwidth: nearest.bandwidth,
+            rssi: nearest.rssi,
+            noise_floor: nearest.noise_floor,
+            subcarrier_data: a
+                .subcarrier_data
+                .iter()
+                .zip(&b.subcarrier_data)
+                .map(|(x, y)| {
+                    ComplexNumber::new(x.real + (y.real - x.real) * w, x.imag + (y.imag - x.imag) * w)
+                })
+                .collect(),
+            sequence: None,
+            device_timestamp_us: None,
+            device_id: nearest.device_id.clone(),
+            transmitter: None,
+        });
+    }
+
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(start: DateTime<Utc>, host_ms: i64, value: f32) -> CsiMeasurement {
+        CsiMeasurement {
+            timestamp: start + Duration::milliseconds(host_ms),
+            channel: 6,
+            bandwidth: 20,
+            rssi: -40,
+            noise_floor: -90,
+            subcarrier_data: vec![ComplexNumber::new(value, 0.0), ComplexNumber::new(0.0, 2.0 * value)],
+            sequence: None,
+            device_timestamp_us: None,
+            device_id: None,
+            transmitter: None,
+        }
+    }
+
+    #[test]
+    fn grid_covers_the_input_span() {
+        let start = Utc::now();
+        let frames: Vec<CsiMeasurement> = (0..=10).map(|i| frame(start, i * 100, i as f32)).collect();
+
+        let series = Resampler::resample(&frames, 25.0).unwrap();
+        // 1 s at 25 Hz, both ends included.
+        assert_eq!(series.measurements.len(), 26);
+        assert_eq!(series.start, start);
+        assert_eq!(series.measurements[25].timestamp, start + Duration::milliseconds(1000));
+
+        assert!(Resampler::resample(&frames, 0.0).is_err());
+        assert!(Resampler::resample(&[], 25.0).unwrap().measurements.is_empty());
+    }
+
+    #[test]
+    fn subcarriers_are_linearly_interpolated() {
+        let start = Utc::now();
+        let frames = vec![frame(start, 0, 0.0), frame(start, 500, 1.0), frame(start, 1000, 3.0)];
+
+        let series = Resampler::resample(&frames, 4.0).unwrap();
+        let real: Vec<f32> = series.measurements.iter().map(|m| m.subcarrier_data[0].real).collect();
+        let imag: Vec<f32> = series.measurements.iter().map(|m| m.subcarrier_data[1].imag).collect();
+        assert_eq!(real, vec![0.0, 0.5, 1.0, 2.0, 3.0]);
+        assert_eq!(imag, vec![0.0, 1.0, 2.0, 4.0, 6.0]);
+
+        let amplitudes = series.amplitudes();
+        assert_eq!(amplitudes.num_subcarriers(), 2);
+        assert_eq!(amplitudes.subcarrier(1), vec![0.0, 1.0, 2.0, 4.0, 6.0]);
+        assert_eq!(amplitudes.averaged()[3], 3.0);
+    }
+
+    fn stamped(start: DateTime<Utc>, host_ms: i64, device_us: u64, sequence: u32, value: f32) -> CsiMeasurement {
+        CsiMeasurement {
+            sequence: Some(sequence),
+            device_timestamp_us: Some(device_us),
+            ..frame(start, host_ms, value)
+        }
+    }
+
+    #[test]
+    fn device_restart_starts_a_new_clock_fit() {
+        let start = Utc::now();
+        // Frame i is captured at i * 100 ms and read 0-7 ms later. The device restarts
+        // after frame 9; one frame is lost before the restart and two after it.
+        let frames: Vec<CsiMeasurement> = (0..20u32)
+            .map(|i| {
+                let host_ms = i as i64 * 100 + [0, 7, 3][i as usize % 3];
+                let (device_us, sequence) = if i < 10 {
+                    (9_000_000 + i as u64 * 100_000, 500 + i + (i >= 5) as u32)
+                } else {
+                    (50_000 + (i - 10) as u64 * 100_000, i - 10 + 2 * (i >= 15) as u32)
+                };
+                stamped(start, host_ms, device_us, sequence, i as f32)
+            })
+            .collect();
+
+        let series = Resampler::resample(&frames, 10.0).unwrap();
+        assert_eq!(series.dropped_frames, 3);
+        let clock = series.clock.unwrap();
+        assert!(clock.drift_ppm().abs() < 1e4, "drift {} ppm", clock.drift_ppm());
+        assert!(series.jitter_ms > 0.0 && series.jitter_ms < 7.0, "jitter {} ms", series.jitter_ms);
+
+        // 1.9 s at 10 Hz, give or take the fitted rate.
+        assert!((19..=20).contains(&series.measurements.len()));
+        for (k, m) in series.measurements.iter().enumerate() {
+            let value = m.subcarrier_data[0].real;
+            assert!((value - k as f32).abs() < 0.1, "sample {} is {}", k, value);
+        }
+    }
+
+    #[test]
+    fn sequence_gaps_ignore_late_and_repeated_frames() {
+        let start = Utc::now();
+        let frames: Vec<CsiMeasurement> = [10, 11, 14, 13, 15, 15, 18]
+            .iter()
+            .enumerate()
+            .map(|(i, &sequence)| CsiMeasurement { sequence: Some(sequence), ..frame(start, i as i64 * 100, 0.0) })
+            .collect();
+        let frames: Vec<&CsiMeasurement> = frames.iter().collect();
+        // 12-13 are skipped when 14 arrives and 16-17 when 18 does; the late 13 and the
+        // repeated 15 add nothing.
+        assert_eq!(sequence_gaps(&frames), 4);
+    }
+}
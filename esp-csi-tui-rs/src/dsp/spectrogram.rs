@@ -0,0 +1,196 @@
+use crate::dsp::resample::Resampler;
+use crate::models::CsiMeasurement;
+use anyhow::{Result, anyhow};
+use rustfft::{num_complex::Complex, FftPlanner};
+
+#[derive(Debug, Clone)]
+pub struct SpectrogramConfig {
+    pub sample_rate_hz: f32,
+    pub window_size: usize,
+    pub hop_size: usize,
+}
+
+impl Default for SpectrogramConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate_hz: 10.0,
+            window_size: 64,
+            hop_size: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Spectrogram {
+    /// Centre of each STFT frame, in seconds from the start of the series.
+    pub times: Vec<f32>,
+    /// Frequency of each bin in Hz, from DC up to Nyquist.
+    pub frequencies: Vec<f32>,
+    /// Power spectral density, indexed as `power[frame][bin]`.
+    pub power: Vec<Vec<f32>>,
+}
+
+impl Spectrogram {
+    pub fn is_empty(&self) -> bool {
+        self.power.is_empty()
+    }
+
+    pub fn mean_power(&self) -> Vec<f32> {
+        let mut mean = vec![0.0; self.frequencies.len()];
+        for frame in &self.power {
+            for (acc, p) in mean.iter_mut().zip(frame) {
+                *acc += p;
+            }
+        }
+        let frames = self.power.len().max(1) as f32;
+        mean.iter_mut().for_each(|p| *p /= frames);
+        mean
+    }
+
+    /// Frequency of the strongest non-DC bin averaged over all frames.
+    pub fn dominant_frequency(&self) -> Option<f32> {
+        self.mean_power()
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(bin, _)| self.frequencies[bin])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeFrequencyAnalysis {
+    pub averaged: Spectrogram,
+    pub per_subcarrier: Vec<Spectrogram>,
+}
+
+pub struct SpectrogramProcessor;
+
+impl SpectrogramProcessor {
+    /// Short-time Fourier transform with a Hann window. Each window has its mean
+    /// removed first, so the static part of the channel does not swamp the
+    /// low-frequency bins that carry motion and breathing.
+    pub fn stft(signal: &[f32], config: &SpectrogramConfig) -> Result<Spectrogram> {
+        if config.window_size < 2 || config.hop_size == 0 {
+            return Err(anyhow!(
+                "Invalid STFT window {} / hop {}",
+                config.window_size, config.hop_size
+            ));
+        }
+
+        let n = config.window_size;
+        let bins = n / 2 + 1;
+        let frequencies = (0..bins)
+            .map(|k| k as f32 * config.sample_rate_hz / n as f32)
+            .collect();
+
+        if signal.len() < n {
+            return Ok(Spectrogram { frequencies, ..Default::default() });
+        }
+
+        let window: Vec<f32> = (0..n)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (n - 1) as f32).cos())
+            .collect();
+        let window_energy: f32 = window.iter().map(|w| w * w).sum();
+        let scale = 1.0 / (window_energy * config.sample_rate_hz);
+
+        let fft = FftPlanner::<f32>::new().plan_fft_forward(n);
+        let mut buffer = vec![Complex::new(0.0, 0.0); n];
+        let mut times = Vec::new();
+        let mut power = Vec::new();
+
+        for start in (0..=signal.len() - n).step_by(config.hop_size) {
+            let segment = &signal[start..start + n];
+            let mean = segment.iter().sum::<f32>() / n as f32;
+            for (slot, (x, w)) in buffer.iter_mut().zip(segment.iter().zip(&window)) {
+                *slot = Complex::new((x - mean) * w, 0.0);
+            }
+            fft.process(&mut buffer);
+
+            let frame = buffer[..bins]
+                .iter()
+                .enumerate()
+                .map(|(k, c)| {
+                    // Fold negative frequencies into the one-sided spectrum.
+                    let one_sided = if k == 0 || (n.is_multiple_of(2) && k == n / 2) { 1.0 } else { 2.0 };
+                    c.norm_sqr() * scale * one_sided
+                })
+                .collect();
+
+            times.push((start as f32 + n as f32 / 2.0) / config.sample_rate_hz);
+            power.push(frame);
+        }
+
+        Ok(Spectrogram { times, frequencies, power })
+    }
+
+    pub fn analyze(measurements: &[CsiMeasurement], config: &SpectrogramConfig) -> Result<TimeFrequencyAnalysis> {
+        let series = Resampler::amplitudes(measurements, config.sample_rate_hz)?;
+
+        let averaged = Self::stft(&series.averaged(), config)?;
+        let per_subcarrier = (0..series.num_subcarriers())
+            .map(|idx| Self::stft(&series.subcarrier(idx), config))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(TimeFrequencyAnalysis { averaged, per_subcarrier })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency_hz: f32, amplitude: f32, samples: usize, config: &SpectrogramConfig) -> Vec<f32> {
+        (0..samples)
+            .map(|i| {
+                let t = i as f32 / config.sample_rate_hz;
+                3.0 + amplitude * (2.0 * std::f32::consts::PI * frequency_hz * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sine_peaks_in_its_frequency_bin() {
+        let config = SpectrogramConfig::default();
+        // 1.25 Hz falls exactly on bin 8 of a 64-point window at 10 Hz.
+        let spectrogram = SpectrogramProcessor::stft(&sine(1.25, 2.0, 200, &config), &config).unwrap();
+
+        assert_eq!(spectrogram.frequencies.len(), 33);
+        assert_eq!(spectrogram.frequencies[8], 1.25);
+        assert_eq!(spectrogram.frequencies[32], 5.0);
+        assert_eq!(spectrogram.dominant_frequency(), Some(1.25));
+
+        for frame in &spectrogram.power {
+            let peak = frame[8];
+            // Mean removal empties DC; the Hann window keeps leakage to the neighbouring bins.
+            assert!(frame[0] < peak * 1e-6);
+            for (bin, p) in frame.iter().enumerate() {
+                if bin.abs_diff(8) > 2 {
+                    assert!(*p < peak * 1e-3, "bin {} leaks {} against {}", bin, p, peak);
+                }
+            }
+            // The one-sided PSD integrates to the variance of the sine, A^2 / 2.
+            let variance: f32 = frame.iter().sum::<f32>() * config.sample_rate_hz / config.window_size as f32;
+            assert!((variance - 2.0).abs() < 0.1, "integrated power {}", variance);
+        }
+    }
+
+    #[test]
+    fn frames_follow_window_and_hop() {
+        let config = SpectrogramConfig::default();
+        let spectrogram = SpectrogramProcessor::stft(&sine(1.0, 1.0, 200, &config), &config).unwrap();
+
+        // (200 - 64) / 8 + 1 frames, centred half a window after each hop.
+        assert_eq!(spectrogram.power.len(), 18);
+        assert_eq!(spectrogram.times.len(), 18);
+        assert!((spectrogram.times[0] - 3.2).abs() < 1e-6);
+        assert!((spectrogram.times[17] - (3.2 + 17.0 * 0.8)).abs() < 1e-4);
+
+        let short = SpectrogramProcessor::stft(&sine(1.0, 1.0, 63, &config), &config).unwrap();
+        assert!(short.is_empty());
+        assert_eq!(short.frequencies.len(), 33);
+
+        let bad = SpectrogramConfig { hop_size: 0, ..config };
+        assert!(SpectrogramProcessor::stft(&[0.0; 100], &bad).is_err());
+    }
+}
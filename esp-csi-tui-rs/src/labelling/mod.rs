@@ -0,0 +1,140 @@
+use crate::models::CsiMeasurement;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Activities bound to the number keys 1-9 in labelling mode, in key order.
+pub const ACTIVITIES: [&str; 9] = [
+    "empty", "sitting", "standing", "walking", "breathing", "gesture", "falling", "lying", "running",
+];
+
+/// Activity started by a number key in labelling mode; `0` and other keys have none.
+pub fn activity_for_key(key: char) -> Option<&'static str> {
+    let index = key.to_digit(10)? as usize;
+    ACTIVITIES.get(index.checked_sub(1)?).copied()
+}
+
+/// A named activity between two host timestamps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelInterval {
+    pub label: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl LabelInterval {
+    pub fn duration_s(&self) -> f32 {
+        (self.end - self.start).num_milliseconds() as f32 / 1000.0
+    }
+
+    /// The measurements recorded during this interval.
+    pub fn slice<'a>(&self, measurements: &'a [CsiMeasurement]) -> &'a [CsiMeasurement] {
+        let start = measurements.partition_point(|m| m.timestamp < self.start);
+        let end = measurements.partition_point(|m| m.timestamp <= self.end);
+        &measurements[start..end.max(start)]
+    }
+}
+
+/// Label intervals marked during a capture; at most one activity is open at a time.
+#[derive(Debug, Clone, Default)]
+pub struct LabelTrack {
+    pub intervals: Vec<LabelInterval>,
+    active: Option<(String, DateTime<Utc>)>,
+}
+
+impl LabelTrack {
+    pub fn active(&self) -> Option<(&str, DateTime<Utc>)> {
+        self.active.as_ref().map(|(label, start)| (label.as_str(), *start))
+    }
+
+    /// Starts `label`, closing any other open activity. Starting the activity that is
+    /// already open closes it instead, so one key toggles an activity on and off.
+    pub fn toggle(&mut self, label: &str, now: DateTime<Utc>) {
+        let reopen = self.active.as_ref().is_none_or(|(open, _)| open != label);
+        self.close(now);
+        if reopen {
+            self.active = Some((label.to_string(), now));
+        }
+    }
+
+    /// Ends the open activity, if any, and returns the finished interval.
+    pub fn close(&mut self, now: DateTime<Utc>) -> Option<LabelInterval> {
+        let (label, start) = self.active.take()?;
+        let interval = LabelInterval { label, start, end: now };
+        self.intervals.push(interval.clone());
+        Some(interval)
+    }
+
+    /// Closed intervals plus the open one, if any, ending at `now`.
+    pub fn snapshot(&self, now: DateTime<Utc>) -> Vec<LabelInterval> {
+        let mut intervals = self.intervals.clone();
+        if let Some((label, start)) = self.active.clone() {
+            intervals.push(LabelInterval { label, start, end: now });
+        }
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn number_keys_map_to_activities() {
+        assert_eq!(activity_for_key('1'), Some("empty"));
+        assert_eq!(activity_for_key('4'), Some("walking"));
+        assert_eq!(activity_for_key('9'), Some("running"));
+        for key in ['0', 'a', ' '] {
+            assert_eq!(activity_for_key(key), None);
+        }
+        let keyed: Vec<&str> = ('1'..='9').filter_map(activity_for_key).collect();
+        assert_eq!(keyed, ACTIVITIES);
+    }
+
+    #[test]
+    fn toggling_keeps_one_activity_open() {
+        let start = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        let at = |s: i64| start + Duration::seconds(s);
+        let mut track = LabelTrack::default();
+
+        track.toggle("walking", at(0));
+        assert_eq!(track.active(), Some(("walking", at(0))));
+        // Another activity closes the open one; the same one toggles it off.
+        track.toggle("sitting", at(5));
+        track.toggle("sitting", at(8));
+        assert_eq!(track.active(), None);
+        assert!(track.close(at(9)).is_none());
+        track.toggle("falling", at(10));
+
+        let snapshot = track.snapshot(at(12));
+        let spans: Vec<(&str, f32)> = snapshot.iter().map(|i| (i.label.as_str(), i.duration_s())).collect();
+        assert_eq!(spans, vec![("walking", 5.0), ("sitting", 3.0), ("falling", 2.0)]);
+        assert_eq!(track.intervals.len(), 2);
+
+        let closed = track.close(at(13)).unwrap();
+        assert_eq!((closed.label.as_str(), closed.duration_s()), ("falling", 3.0));
+        assert_eq!(track.intervals.len(), 3);
+    }
+
+    #[test]
+    fn intervals_slice_their_measurements() {
+        let capture = crate::synthetic::capture(
+            crate::synthetic::Scenario::new(crate::synthetic::Scene::EmptyRoom).with_seed(3),
+            DateTime::from_timestamp(1_704_067_200, 0).unwrap(),
+            10.0,
+            10.0,
+        );
+        let interval = LabelInterval {
+            label: "sitting".to_string(),
+            start: capture[20].timestamp,
+            end: capture[49].timestamp,
+        };
+        let slice = interval.slice(&capture);
+        assert_eq!(slice.len(), 30);
+        assert_eq!(slice[0].timestamp, capture[20].timestamp);
+
+        let after = capture[99].timestamp;
+        let outside = LabelInterval { start: after + Duration::seconds(1), end: after + Duration::seconds(2), ..interval };
+        assert!(outside.slice(&capture).is_empty());
+    }
+}
@@ -5,9 +5,19 @@ mod storage;
 mod streaming;
 mod models;
 mod commands;
+mod capture;
+mod survey;
+mod synthetic;
+mod dsp;
+mod detection;
+mod vitals;
+mod calibration;
+mod classification;
+mod labelling;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use std::panic;
+use std::path::PathBuf;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -15,13 +25,40 @@ async fn main() -> Result<()> {
         .with_max_level(tracing::Level::DEBUG)
         .init();
 
+    let (mut script, mut seed) = (None, None);
+    // Address new devices connect to, e.g. `udp://0.0.0.0:5555`, instead of /dev/ttyUSB0.
+    let port = std::env::var("ESP_CSI_PORT").ok();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--script", Some(path)) => script = Some(PathBuf::from(path)),
+            ("--seed", Some(value)) => {
+                seed = Some(value.parse().map_err(|e| anyhow!("Invalid seed '{}': {}", value, e))?)
+            }
+            ("--convert", Some(input)) => {
+                let output = args.next().ok_or_else(|| anyhow!("Usage: esp-csi-tui-rs --convert <input> <output>"))?;
+                let count = storage::convert(input.as_ref(), output.as_ref())?;
+                println!("Converted {} measurements from {} to {}", count, input, output);
+                return Ok(());
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Usage: esp-csi-tui-rs [--seed <n>] [--script <file>] | --convert <input> <output>"
+                ))
+            }
+        }
+    }
+    if let Some(path) = script {
+        return commands::script::run(&path, seed);
+    }
+
     let default_panic = panic::take_hook();
     panic::set_hook(Box::new(move |info| {
         default_panic(info);
         let _ = ui::cleanup_terminal();
     }));
 
-    ui::app::run().await?;
+    ui::app::run(seed, port).await?;
 
     Ok(())
 }
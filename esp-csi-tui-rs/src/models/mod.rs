@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use anyhow::{Result, anyhow};
+use crate::detection::PresenceState;
+use crate::synthetic::Scenario;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CsiMeasurement {
@@ -9,6 +12,18 @@ pub struct CsiMeasurement {
     pub rssi: i8,
     pub noise_floor: i8,
     pub subcarrier_data: Vec<ComplexNumber>,
+    /// Firmware frame counter, when the device reports one.
+    #[serde(default)]
+    pub sequence: Option<u32>,
+    /// Device uptime at capture in microseconds, when the device reports it.
+    #[serde(default)]
+    pub device_timestamp_us: Option<u64>,
+    /// Receiver that captured the frame, set by its acquisition task.
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// MAC of the station that sent the frame, when the device reports it.
+    #[serde(default)]
+    pub transmitter: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -33,11 +48,52 @@ impl ComplexNumber {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceConfig {
+    /// Serial port, or `udp://<bind>:<port>` / `tcp://<bind>:<port>` for a networked ESP32.
     pub port: String,
     pub baud_rate: u32,
     pub channel: u8,
     pub bandwidth: u16,
     pub collection_interval_ms: u64,
+    #[serde(default)]
+    pub wifi: WifiConfig,
+    #[serde(default)]
+    pub csi: CsiConfig,
+    #[serde(default)]
+    pub traffic: TrafficConfig,
+}
+
+impl DeviceConfig {
+    /// CSI rate the device is asked for: the traffic rate when it generates traffic,
+    /// otherwise one frame per collection interval.
+    pub fn requested_rate_hz(&self) -> f32 {
+        if self.traffic.is_enabled() {
+            self.traffic.frequency_hz as f32
+        } else {
+            1000.0 / self.collection_interval_ms.max(1) as f32
+        }
+    }
+
+    /// Checks the settings against what the firmware accepts.
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=14).contains(&self.channel) {
+            return Err(anyhow!("Channel must be 1-14, got {}", self.channel));
+        }
+        if !matches!(self.bandwidth, 20 | 40) {
+            return Err(anyhow!("Bandwidth must be 20 or 40 MHz, got {}", self.bandwidth));
+        }
+        if self.collection_interval_ms < 10 {
+            return Err(anyhow!("Interval must be at least 10 ms, got {}", self.collection_interval_ms));
+        }
+        self.wifi.validate()?;
+        self.traffic.validate()?;
+        if self.traffic.is_enabled() && !self.wifi.mode.uses_station() {
+            return Err(anyhow!(
+                "Traffic generation needs a network to send to; switch from {} to station or ap-station mode",
+                self.wifi.mode.as_str()
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Default for DeviceConfig {
@@ -48,27 +104,413 @@ impl Default for DeviceConfig {
             channel: 6,
             bandwidth: 20,
             collection_interval_ms: 100,
+            wifi: WifiConfig::default(),
+            csi: CsiConfig::default(),
+            traffic: TrafficConfig::default(),
+        }
+    }
+}
+
+/// How the ESP32 uses its radio while capturing CSI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WifiMode {
+    /// Promiscuous capture of all traffic on the channel.
+    #[default]
+    Sniffer,
+    /// Joins an existing network.
+    Station,
+    /// Runs its own network for other devices to join.
+    AccessPoint,
+    AccessPointStation,
+}
+
+impl WifiMode {
+    pub const ALL: [WifiMode; 4] = [WifiMode::Sniffer, WifiMode::Station, WifiMode::AccessPoint, WifiMode::AccessPointStation];
+
+    /// Name used by the firmware protocol and the command palette.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WifiMode::Sniffer => "sniffer",
+            WifiMode::Station => "station",
+            WifiMode::AccessPoint => "ap",
+            WifiMode::AccessPointStation => "ap-station",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        WifiMode::ALL
+            .into_iter()
+            .find(|mode| mode.as_str() == name)
+            .ok_or_else(|| anyhow!("Unknown Wi-Fi mode '{}' (sniffer, station, ap or ap-station)", name))
+    }
+
+    pub fn uses_station(&self) -> bool {
+        matches!(self, WifiMode::Station | WifiMode::AccessPointStation)
+    }
+
+    pub fn uses_access_point(&self) -> bool {
+        matches!(self, WifiMode::AccessPoint | WifiMode::AccessPointStation)
+    }
+}
+
+/// Maximum stations the ESP32 access point accepts.
+pub const MAX_AP_CONNECTIONS: u8 = 10;
+
+/// Wi-Fi mode and credentials, sent with [`DeviceCommand::SetWifi`]. Passwords are
+/// not written to session files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WifiConfig {
+    pub mode: WifiMode,
+    /// Network joined in station modes.
+    pub sta_ssid: String,
+    #[serde(skip_serializing, default)]
+    pub sta_password: String,
+    /// Network offered in access point modes.
+    pub ap_ssid: String,
+    #[serde(skip_serializing, default)]
+    pub ap_password: String,
+    pub ap_hidden: bool,
+    pub max_connections: u8,
+}
+
+impl Default for WifiConfig {
+    fn default() -> Self {
+        Self {
+            mode: WifiMode::Sniffer,
+            sta_ssid: String::new(),
+            sta_password: String::new(),
+            ap_ssid: String::new(),
+            ap_password: String::new(),
+            ap_hidden: false,
+            max_connections: 1,
+        }
+    }
+}
+
+impl WifiConfig {
+    /// Checks the settings the mode needs before they are sent to a device.
+    pub fn validate(&self) -> Result<()> {
+        if self.mode.uses_station() {
+            validate_ssid("Station SSID", &self.sta_ssid)?;
+            validate_password("Station password", &self.sta_password)?;
+        }
+        if self.mode.uses_access_point() {
+            validate_ssid("AP SSID", &self.ap_ssid)?;
+            validate_password("AP password", &self.ap_password)?;
+            if !(1..=MAX_AP_CONNECTIONS).contains(&self.max_connections) {
+                return Err(anyhow!("Max connections must be 1-{}, got {}", MAX_AP_CONNECTIONS, self.max_connections));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_field(name: &str, value: &str) -> Result<()> {
+    // Fields are comma-separated on the wire.
+    if value.chars().any(|c| c == ',' || c.is_control()) {
+        return Err(anyhow!("{} must not contain commas or control characters", name));
+    }
+    Ok(())
+}
+
+fn validate_ssid(name: &str, ssid: &str) -> Result<()> {
+    if ssid.is_empty() || ssid.len() > 32 {
+        return Err(anyhow!("{} must be 1-32 bytes", name));
+    }
+    validate_field(name, ssid)
+}
+
+/// Empty for an open network, otherwise a WPA2 passphrase.
+fn validate_password(name: &str, password: &str) -> Result<()> {
+    if !password.is_empty() && !(8..=63).contains(&password.len()) {
+        return Err(anyhow!("{} must be empty (open) or 8-63 characters", name));
+    }
+    validate_field(name, password)
+}
+
+/// Frames the device sends to make its access point answer: CSI is only measured on
+/// received frames, so this sets the CSI rate on a quiet network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrafficKind {
+    /// ICMP echo requests to the gateway. Part of the protocol, not generated by the
+    /// bundled firmware.
+    Ping,
+    #[default]
+    Udp,
+    /// 802.11 null-data frames, acknowledged by the access point. Part of the protocol,
+    /// not generated by the bundled firmware.
+    NullData,
+}
+
+impl TrafficKind {
+    /// Kinds the bundled firmware generates, the only ones the form and palette offer.
+    pub const SUPPORTED: [TrafficKind; 1] = [TrafficKind::Udp];
+
+    /// Name used by the firmware protocol and the command palette.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrafficKind::Ping => "ping",
+            TrafficKind::Udp => "udp",
+            TrafficKind::NullData => "null",
         }
     }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        TrafficKind::SUPPORTED
+            .into_iter()
+            .find(|kind| kind.as_str() == name)
+            .ok_or_else(|| anyhow!("Unsupported traffic type '{}'; the firmware generates udp", name))
+    }
+}
+
+/// Highest traffic rate the firmware generates.
+pub const MAX_TRAFFIC_HZ: u32 = 1000;
+
+/// Traffic generation, sent with [`DeviceCommand::SetTraffic`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrafficConfig {
+    pub kind: TrafficKind,
+    /// Frames per second; 0 turns generation off.
+    pub frequency_hz: u32,
+}
+
+impl TrafficConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.frequency_hz > 0
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.frequency_hz > MAX_TRAFFIC_HZ {
+            return Err(anyhow!("Traffic rate must be 0-{} Hz, got {}", MAX_TRAFFIC_HZ, self.frequency_hz));
+        }
+        if self.is_enabled() && !TrafficKind::SUPPORTED.contains(&self.kind) {
+            return Err(anyhow!("The firmware does not generate {} traffic; use udp", self.kind.as_str()));
+        }
+        Ok(())
+    }
+
+    pub fn label(&self) -> String {
+        if self.is_enabled() {
+            format!("{} {} Hz", self.kind.as_str(), self.frequency_hz)
+        } else {
+            "off".to_string()
+        }
+    }
+}
+
+/// Which training fields the radio captures CSI from, sent with
+/// [`DeviceCommand::SetCsi`]. The flags differ by chip: the original ESP32 family
+/// (ESP32, -S3, -C3) picks LTFs, the 802.11ax ESP32-C6 picks PPDU types.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CsiConfig {
+    Esp32 {
+        lltf: bool,
+        htltf: bool,
+        stbc_htltf: bool,
+        ltf_merge: bool,
+    },
+    Esp32C6 {
+        enable: bool,
+        /// L-LTF of 11g PPDUs.
+        legacy: bool,
+        ht20: bool,
+        ht40: bool,
+        /// HE-LTF of HE20 SU / MU / DCM / beamformed PPDUs.
+        su: bool,
+        mu: bool,
+        dcm: bool,
+        beamformed: bool,
+        /// For STBC HE PPDUs: 0 = HE-LTF1, 1 = HE-LTF2, 2 = sampled from both.
+        he_stbc: u8,
+        /// Amplitude scaling, 0-3.
+        val_scale: u8,
+    },
+}
+
+impl Default for CsiConfig {
+    fn default() -> Self {
+        CsiConfig::Esp32 { lltf: true, htltf: true, stbc_htltf: true, ltf_merge: true }
+    }
+}
+
+impl CsiConfig {
+    /// Defaults for the chip named in a query reply, e.g. `esp32c6`.
+    pub fn for_chip(chip: &str) -> Self {
+        if chip.eq_ignore_ascii_case("esp32c6") {
+            CsiConfig::Esp32C6 {
+                enable: true,
+                legacy: true,
+                ht20: true,
+                ht40: true,
+                su: true,
+                mu: true,
+                dcm: true,
+                beamformed: true,
+                he_stbc: 2,
+                val_scale: 2,
+            }
+        } else {
+            CsiConfig::default()
+        }
+    }
+
+    pub fn matches_chip(&self, chip: &str) -> bool {
+        matches!(
+            (self, chip.eq_ignore_ascii_case("esp32c6")),
+            (CsiConfig::Esp32C6 { .. }, true) | (CsiConfig::Esp32 { .. }, false)
+        )
+    }
+
+    /// Settings by protocol name, flags as 0/1.
+    pub fn settings(&self) -> Vec<(&'static str, u8)> {
+        match *self {
+            CsiConfig::Esp32 { lltf, htltf, stbc_htltf, ltf_merge } => vec![
+                ("lltf", lltf as u8),
+                ("htltf", htltf as u8),
+                ("stbc-htltf", stbc_htltf as u8),
+                ("ltf-merge", ltf_merge as u8),
+            ],
+            CsiConfig::Esp32C6 { enable, legacy, ht20, ht40, su, mu, dcm, beamformed, he_stbc, val_scale } => vec![
+                ("enable", enable as u8),
+                ("legacy", legacy as u8),
+                ("ht20", ht20 as u8),
+                ("ht40", ht40 as u8),
+                ("su", su as u8),
+                ("mu", mu as u8),
+                ("dcm", dcm as u8),
+                ("beamformed", beamformed as u8),
+                ("he-stbc", he_stbc),
+                ("val-scale", val_scale),
+            ],
+        }
+    }
+
+    /// Largest value a setting takes: 1 for flags.
+    pub fn max_value(name: &str) -> u8 {
+        match name {
+            "he-stbc" => 2,
+            "val-scale" => 3,
+            _ => 1,
+        }
+    }
+
+    /// Changes one setting by name; flags take `on`/`off`, `true`/`false` or `1`/`0`.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let number: u8 = match value {
+            "on" | "true" => 1,
+            "off" | "false" => 0,
+            _ => value.parse().map_err(|_| anyhow!("Invalid value '{}' for {}", value, name))?,
+        };
+        if number > CsiConfig::max_value(name) {
+            return Err(anyhow!("{} must be 0-{}, got {}", name, CsiConfig::max_value(name), number));
+        }
+        let flag = number == 1;
+        let field = match (self, name) {
+            (CsiConfig::Esp32 { lltf, .. }, "lltf") => lltf,
+            (CsiConfig::Esp32 { htltf, .. }, "htltf") => htltf,
+            (CsiConfig::Esp32 { stbc_htltf, .. }, "stbc-htltf") => stbc_htltf,
+            (CsiConfig::Esp32 { ltf_merge, .. }, "ltf-merge") => ltf_merge,
+            (CsiConfig::Esp32C6 { enable, .. }, "enable") => enable,
+            (CsiConfig::Esp32C6 { legacy, .. }, "legacy") => legacy,
+            (CsiConfig::Esp32C6 { ht20, .. }, "ht20") => ht20,
+            (CsiConfig::Esp32C6 { ht40, .. }, "ht40") => ht40,
+            (CsiConfig::Esp32C6 { su, .. }, "su") => su,
+            (CsiConfig::Esp32C6 { mu, .. }, "mu") => mu,
+            (CsiConfig::Esp32C6 { dcm, .. }, "dcm") => dcm,
+            (CsiConfig::Esp32C6 { beamformed, .. }, "beamformed") => beamformed,
+            (CsiConfig::Esp32C6 { he_stbc, .. }, "he-stbc") => {
+                *he_stbc = number;
+                return Ok(());
+            }
+            (CsiConfig::Esp32C6 { val_scale, .. }, "val-scale") => {
+                *val_scale = number;
+                return Ok(());
+            }
+            (config, _) => {
+                let names: Vec<&str> = config.settings().iter().map(|(name, _)| *name).collect();
+                return Err(anyhow!("Unknown CSI setting '{}' for this chip ({})", name, names.join(", ")));
+            }
+        };
+        *field = flag;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in self.settings() {
+            if value > CsiConfig::max_value(name) {
+                return Err(anyhow!("{} must be 0-{}, got {}", name, CsiConfig::max_value(name), value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of the enabled captures, as reported in `+INFO` `csi=`.
+    pub fn enabled(&self) -> Vec<&'static str> {
+        if matches!(self, CsiConfig::Esp32C6 { enable: false, .. }) {
+            return Vec::new();
+        }
+        self.settings()
+            .into_iter()
+            .filter(|&(name, value)| CsiConfig::max_value(name) == 1 && name != "enable" && value == 1)
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+/// Identity and state a device reports in reply to [`DeviceCommand::Query`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub firmware_version: String,
+    pub chip: String,
+    pub mac: String,
+    /// `sniffer`, `station`, `ap` or `ap-station`.
+    pub wifi_mode: String,
+    pub channel: u8,
+    /// CSI captures the firmware has enabled, named as in [`CsiConfig::settings`].
+    pub csi_flags: Vec<String>,
+    pub uptime_us: u64,
+    /// Host time the report arrived.
+    pub received_at: DateTime<Utc>,
+}
+
+impl DeviceInfo {
+    /// Wi-Fi modes the firmware can run: the bundled firmware stays in the mode its build
+    /// starts in (`sniffer` over serial, `station` with `net`). Demo devices, which report
+    /// `demo`, and firmware reporting an unknown mode are offered all of them.
+    pub fn wifi_modes(&self) -> Vec<WifiMode> {
+        WifiMode::parse(&self.wifi_mode).map_or_else(|_| WifiMode::ALL.to_vec(), |mode| vec![mode])
+    }
+
+    /// Whether the firmware can generate traffic: the bundled firmware only does in its
+    /// `net` build, which runs in station mode. Demo devices simulate it.
+    pub fn generates_traffic(&self) -> bool {
+        matches!(self.wifi_mode.as_str(), "station" | "demo")
+    }
 }
 
 #[derive(Debug)]
 pub struct AppState {
-    pub measurements: Vec<CsiMeasurement>,
+    /// Measurements received from all devices.
+    pub received: usize,
     pub device_config: DeviceConfig,
     pub is_connected: bool,
     pub is_collecting: bool,
     pub current_tab: usize,
+    pub presence: PresenceState,
+    /// Countdown of the scheduled capture, if any.
+    pub capture: Option<String>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
-            measurements: Vec::new(),
+            received: 0,
             device_config: DeviceConfig::default(),
             is_connected: false,
             is_collecting: false,
             current_tab: 0,
+            presence: PresenceState::Uncalibrated,
+            capture: None,
         }
     }
 }
@@ -76,12 +518,67 @@ impl Default for AppState {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DeviceCommand {
     Configure(DeviceConfig),
+    SetWifi(WifiConfig),
+    SetCsi(CsiConfig),
+    SetTraffic(TrafficConfig),
+    /// Synthetic scenario of a demo device.
+    SetScenario(Scenario),
     StartCollection,
     StopCollection,
     Query,
     Disconnect,
 }
 
+impl DeviceCommand {
+    /// Short name, as typed in the command palette.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceCommand::Configure(_) => "configure",
+            DeviceCommand::SetWifi(_) => "wifi",
+            DeviceCommand::SetCsi(_) => "csi",
+            DeviceCommand::SetTraffic(_) => "traffic",
+            DeviceCommand::SetScenario(_) => "scenario",
+            DeviceCommand::StartCollection => "start",
+            DeviceCommand::StopCollection => "stop",
+            DeviceCommand::Query => "query",
+            DeviceCommand::Disconnect => "disconnect",
+        }
+    }
+
+    /// Rejects settings the firmware would refuse, before the command is sent; `info` is
+    /// the device's query reply, when one arrived.
+    pub fn validate(&self, info: Option<&DeviceInfo>) -> Result<()> {
+        match self {
+            DeviceCommand::Configure(config) => config.validate(),
+            DeviceCommand::SetWifi(wifi) => {
+                wifi.validate()?;
+                match info {
+                    Some(info) if !info.wifi_modes().contains(&wifi.mode) => Err(anyhow!(
+                        "The {} firmware only runs in {} mode, not {}",
+                        info.chip,
+                        info.wifi_mode,
+                        wifi.mode.as_str()
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            DeviceCommand::SetCsi(csi) => csi.validate(),
+            DeviceCommand::SetTraffic(traffic) => {
+                traffic.validate()?;
+                match info {
+                    Some(info) if traffic.is_enabled() && !info.generates_traffic() => Err(anyhow!(
+                        "The {} firmware in {} mode does not generate traffic; only the net build does",
+                        info.chip,
+                        info.wifi_mode
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StreamingConfig {
     pub enabled: bool,
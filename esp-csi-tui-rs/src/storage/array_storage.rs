@@ -0,0 +1,422 @@
+use crate::models::CsiMeasurement;
+use crate::storage::mat::{MatData, MatWriter};
+use crate::storage::npy;
+use crate::storage::session_storage::SessionDevice;
+use anyhow::{Result, anyhow};
+use std::path::Path;
+
+/// Column values, one per frame.
+enum Values {
+    Int64(Vec<i64>),
+    Int16(Vec<i16>),
+    Text { values: Vec<String>, width: usize },
+    /// Frames x subcarriers, row-major.
+    Complex(Vec<(f32, f32)>),
+}
+
+struct Column {
+    name: &'static str,
+    values: Values,
+}
+
+/// A recording as one array per field, with the CSI as a frames x subcarriers complex
+/// matrix. Frames with fewer subcarriers than the widest are padded with NaN and
+/// missing integers are -1.
+struct CsiArrays {
+    frames: usize,
+    subcarriers: usize,
+    columns: Vec<Column>,
+    metadata: String,
+}
+
+impl CsiArrays {
+    fn new(measurements: &[CsiMeasurement], session: &[SessionDevice]) -> Result<Self> {
+        let (first, last) = match (measurements.first(), measurements.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(anyhow!("Nothing to export")),
+        };
+        let frames = measurements.len();
+        let subcarriers = measurements.iter().map(|m| m.subcarrier_data.len()).max().unwrap_or(0).max(1);
+        let mut csi = vec![(f32::NAN, f32::NAN); frames * subcarriers];
+        for (row, m) in csi.chunks_mut(subcarriers).zip(measurements) {
+            for (cell, c) in row.iter_mut().zip(&m.subcarrier_data) {
+                *cell = (c.real, c.imag);
+            }
+        }
+        let text = |get: fn(&CsiMeasurement) -> Option<&String>| {
+            let values: Vec<String> = measurements.iter().map(|m| get(m).cloned().unwrap_or_default()).collect();
+            let width = values.iter().map(String::len).max().unwrap_or(0).max(1);
+            Values::Text { values, width }
+        };
+        let int16 = |get: fn(&CsiMeasurement) -> i16| Values::Int16(measurements.iter().map(get).collect());
+
+        let mut devices: Vec<&str> = measurements.iter().filter_map(|m| m.device_id.as_deref()).collect();
+        devices.sort_unstable();
+        devices.dedup();
+        let metadata = serde_json::json!({
+            "format": "csi_arrays",
+            "version": 1,
+            "host_version": env!("CARGO_PKG_VERSION"),
+            "frames": frames,
+            "subcarriers": subcarriers,
+            "start": first.timestamp,
+            "end": last.timestamp,
+            "devices": devices,
+            "csi": "frames x subcarriers complex64; rows of narrower frames padded with NaN",
+            "missing_integers": -1,
+            "session": session,
+        });
+
+        Ok(Self {
+            frames,
+            subcarriers,
+            columns: vec![
+                Column { name: "csi", values: Values::Complex(csi) },
+                Column {
+                    name: "timestamp_us",
+                    values: Values::Int64(measurements.iter().map(|m| m.timestamp.timestamp_micros()).collect()),
+                },
+                Column {
+                    name: "device_timestamp_us",
+                    values: Values::Int64(
+                        measurements.iter().map(|m| m.device_timestamp_us.map_or(-1, |us| us as i64)).collect(),
+                    ),
+                },
+                Column {
+                    name: "sequence",
+                    values: Values::Int64(measurements.iter().map(|m| m.sequence.map_or(-1, i64::from)).collect()),
+                },
+                Column { name: "channel", values: int16(|m| m.channel as i16) },
+                Column { name: "bandwidth", values: int16(|m| m.bandwidth as i16) },
+                Column { name: "rssi", values: int16(|m| m.rssi as i16) },
+                Column { name: "noise_floor", values: int16(|m| m.noise_floor as i16) },
+                Column { name: "device", values: text(|m| m.device_id.as_ref()) },
+                Column { name: "transmitter", values: text(|m| m.transmitter.as_ref()) },
+            ],
+            metadata: metadata.to_string(),
+        })
+    }
+
+    /// Shape of a column in C order.
+    fn shape(&self, values: &Values) -> Vec<usize> {
+        match values {
+            Values::Complex(_) => vec![self.frames, self.subcarriers],
+            _ => vec![self.frames],
+        }
+    }
+}
+
+impl Values {
+    /// NumPy dtype of one element.
+    fn dtype(&self) -> String {
+        match self {
+            Values::Int64(_) => "<i8".to_string(),
+            Values::Int16(_) => "<i2".to_string(),
+            Values::Text { width, .. } => format!("<U{}", width),
+            Values::Complex(_) => "<c8".to_string(),
+        }
+    }
+
+    /// Little-endian bytes of element `index`, strings as NumPy's null-padded UTF-32.
+    fn element(&self, index: usize, out: &mut Vec<u8>) {
+        match self {
+            Values::Int64(v) => out.extend_from_slice(&v[index].to_le_bytes()),
+            Values::Int16(v) => out.extend_from_slice(&v[index].to_le_bytes()),
+            Values::Text { values, width } => {
+                let start = out.len();
+                for c in values[index].chars().take(*width) {
+                    out.extend_from_slice(&(c as u32).to_le_bytes());
+                }
+                out.resize(start + width * 4, 0);
+            }
+            Values::Complex(v) => {
+                out.extend_from_slice(&v[index].0.to_le_bytes());
+                out.extend_from_slice(&v[index].1.to_le_bytes());
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Values::Int64(v) => v.len(),
+            Values::Int16(v) => v.len(),
+            Values::Text { values, .. } => values.len(),
+            Values::Complex(v) => v.len(),
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for index in 0..self.len() {
+            self.element(index, &mut out);
+        }
+        out
+    }
+}
+
+/// Exports recordings as arrays for Python and MATLAB: NumPy `.npy`/`.npz` and MATLAB
+/// `.mat`.
+pub struct ArrayStorage;
+
+impl ArrayStorage {
+    /// One structured array with a row per frame, the CSI in its `csi` field; the
+    /// metadata goes to `<file>.meta.json` next to it.
+    pub fn save_npy(measurements: &[CsiMeasurement], session: &[SessionDevice], path: &Path) -> Result<()> {
+        let arrays = CsiArrays::new(measurements, session)?;
+        let descr: Vec<String> = arrays
+            .columns
+            .iter()
+            .map(|c| match c.values {
+                Values::Complex(_) => format!("('{}', '{}', ({},))", c.name, c.values.dtype(), arrays.subcarriers),
+                _ => format!("('{}', '{}')", c.name, c.values.dtype()),
+            })
+            .collect();
+        let mut rows = Vec::new();
+        for frame in 0..arrays.frames {
+            for column in &arrays.columns {
+                match column.values {
+                    Values::Complex(_) => {
+                        let start = frame * arrays.subcarriers;
+                        for index in start..start + arrays.subcarriers {
+                            column.values.element(index, &mut rows);
+                        }
+                    }
+                    _ => column.values.element(frame, &mut rows),
+                }
+            }
+        }
+        std::fs::write(path.with_extension("meta.json"), &arrays.metadata)?;
+        std::fs::write(path, npy::encode(&format!("[{}]", descr.join(", ")), &[arrays.frames], &rows))?;
+        tracing::info!("Saved {} measurements to NPY: {}", arrays.frames, path.display());
+        Ok(())
+    }
+
+    /// One array per field plus `metadata`, a JSON string.
+    pub fn save_npz(measurements: &[CsiMeasurement], session: &[SessionDevice], path: &Path) -> Result<()> {
+        let arrays = CsiArrays::new(measurements, session)?;
+        let mut members: Vec<(&str, Vec<u8>)> = arrays
+            .columns
+            .iter()
+            .map(|c| {
+                let descr = format!("'{}'", c.values.dtype());
+                (c.name, npy::encode(&descr, &arrays.shape(&c.values), &c.values.bytes()))
+            })
+            .collect();
+        let metadata = Values::Text { width: arrays.metadata.chars().count().max(1), values: vec![arrays.metadata.clone()] };
+        members.push(("metadata", npy::encode(&format!("'{}'", metadata.dtype()), &[], &metadata.bytes())));
+        npy::write_npz(path, &members)?;
+        tracing::info!("Saved {} measurements to NPZ: {}", arrays.frames, path.display());
+        Ok(())
+    }
+
+    /// One variable per field plus `metadata`, a JSON char array. Strings are char
+    /// matrices with a row per frame, padded with spaces.
+    pub fn save_mat(measurements: &[CsiMeasurement], session: &[SessionDevice], path: &Path) -> Result<()> {
+        let arrays = CsiArrays::new(measurements, session)?;
+        let mut mat = MatWriter::new(&format!("esp-csi-tui-rs {}", env!("CARGO_PKG_VERSION")));
+        for column in &arrays.columns {
+            match &column.values {
+                Values::Int64(v) => mat.add(column.name, v.len(), 1, MatData::Int64(v))?,
+                Values::Int16(v) => mat.add(column.name, v.len(), 1, MatData::Int16(v))?,
+                Values::Text { values, width } => {
+                    mat.add(column.name, values.len(), *width, MatData::Char(&char_matrix(values, *width)))?
+                }
+                Values::Complex(v) => {
+                    // MATLAB is column-major: all frames of the first subcarrier first.
+                    let (mut re, mut im) = (Vec::with_capacity(v.len()), Vec::with_capacity(v.len()));
+                    for s in 0..arrays.subcarriers {
+                        for t in 0..arrays.frames {
+                            let (r, i) = v[t * arrays.subcarriers + s];
+                            re.push(r);
+                            im.push(i);
+                        }
+                    }
+                    mat.add(column.name, arrays.frames, arrays.subcarriers, MatData::Complex(&re, &im))?
+                }
+            }
+        }
+        let metadata: Vec<u16> = arrays.metadata.encode_utf16().collect();
+        mat.add("metadata", 1, metadata.len(), MatData::Char(&metadata))?;
+        mat.write(path)?;
+        tracing::info!("Saved {} measurements to MAT: {}", arrays.frames, path.display());
+        Ok(())
+    }
+}
+
+/// `values` as a space-padded `values.len()` x `width` char matrix, column-major.
+fn char_matrix(values: &[String], width: usize) -> Vec<u16> {
+    let rows: Vec<Vec<u16>> = values.iter().map(|v| v.encode_utf16().take(width).collect()).collect();
+    (0..width)
+        .flat_map(|c| rows.iter().map(move |row| row.get(c).copied().unwrap_or(b' ' as u16)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ComplexNumber;
+    use crate::synthetic::{self, Scenario, Scene};
+    use chrono::DateTime;
+    use flate2::read::{DeflateDecoder, ZlibDecoder};
+    use std::collections::HashMap;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    fn recording() -> Vec<CsiMeasurement> {
+        let start = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        let scenario = Scenario::new(Scene::Breathing { bpm: 15.0 }).with_seed(3);
+        let mut measurements = synthetic::capture(scenario, start, 2.0, 10.0);
+        for (i, m) in measurements.iter_mut().enumerate() {
+            m.device_id = Some(if i % 2 == 0 { "esp-a" } else { "demo-1" }.to_string());
+        }
+        // A frame with fewer subcarriers, padded with NaN.
+        measurements[3].subcarrier_data.truncate(10);
+        measurements
+    }
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("esp-csi-{}-{}", std::process::id(), name))
+    }
+
+    fn u16_at(b: &[u8], at: usize) -> usize {
+        u16::from_le_bytes(b[at..at + 2].try_into().unwrap()) as usize
+    }
+
+    fn u32_at(b: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(b[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    fn u64_at(b: &[u8], at: usize) -> usize {
+        u64::from_le_bytes(b[at..at + 8].try_into().unwrap()) as usize
+    }
+
+    fn f32_at(b: &[u8], at: usize) -> f32 {
+        f32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+    }
+
+    /// Header dictionary and data of an `.npy` file.
+    fn parse_npy(bytes: &[u8]) -> (String, &[u8]) {
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let len = u16_at(bytes, 8);
+        assert_eq!((10 + len) % 64, 0);
+        (String::from_utf8(bytes[10..10 + len].to_vec()).unwrap(), &bytes[10 + len..])
+    }
+
+    fn assert_csi(value: (f32, f32), expected: &ComplexNumber) {
+        assert_eq!(value, (expected.real, expected.imag));
+    }
+
+    #[test]
+    fn npy_holds_a_row_per_frame() {
+        let (measurements, path) = (recording(), temp("rows.npy"));
+        ArrayStorage::save_npy(&measurements, &[], &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let (header, data) = parse_npy(&bytes);
+        assert!(header.contains("('csi', '<c8', (52,))"), "{}", header);
+        assert!(header.contains("('device', '<U6')") && header.contains("'shape': (20,)"), "{}", header);
+
+        // csi, three i64, four i16, device (6 chars) and transmitter (none, 1 char).
+        let row = 52 * 8 + 3 * 8 + 4 * 2 + 6 * 4 + 4;
+        assert_eq!(data.len(), 20 * row);
+        for frame in [0, 3, 19] {
+            let at = |s: usize| (f32_at(data, frame * row + s * 8), f32_at(data, frame * row + s * 8 + 4));
+            assert_csi(at(5), &measurements[frame].subcarrier_data[5]);
+            assert_eq!(at(40).0.is_nan(), frame == 3);
+        }
+        let timestamp = u64_at(data, row + 52 * 8) as i64;
+        assert_eq!(timestamp, measurements[1].timestamp.timestamp_micros());
+
+        let metadata: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path.with_extension("meta.json")).unwrap()).unwrap();
+        assert_eq!(metadata["devices"], serde_json::json!(["demo-1", "esp-a"]));
+        std::fs::remove_file(path.with_extension("meta.json")).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn npz_members_inflate_to_arrays() {
+        let (measurements, path) = (recording(), temp("arrays.npz"));
+        ArrayStorage::save_npz(&measurements, &[], &path).unwrap();
+        let zip = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let end = zip.len() - 22;
+        assert_eq!(&zip[end..end + 4], b"PK\x05\x06");
+        let (count, mut entry) = (u16_at(&zip, end + 10), u32_at(&zip, end + 16));
+        let mut members = HashMap::new();
+        for _ in 0..count {
+            assert_eq!(&zip[entry..entry + 4], b"PK\x01\x02");
+            let (crc, size, name_len) = (u32_at(&zip, entry + 16), u32_at(&zip, entry + 20), u16_at(&zip, entry + 28));
+            let name = String::from_utf8(zip[entry + 46..entry + 46 + name_len].to_vec()).unwrap();
+            let local = u32_at(&zip, entry + 42);
+            assert_eq!(&zip[local..local + 4], b"PK\x03\x04");
+            let start = local + 30 + u16_at(&zip, local + 26) + u16_at(&zip, local + 28);
+            let mut data = Vec::new();
+            DeflateDecoder::new(&zip[start..start + size]).read_to_end(&mut data).unwrap();
+            assert_eq!(crc32fast::hash(&data) as usize, crc);
+            members.insert(name, data);
+            entry += 46 + name_len;
+        }
+        assert_eq!(members.len(), 11);
+
+        let (header, csi) = parse_npy(&members["csi.npy"]);
+        assert!(header.contains("'descr': '<c8'") && header.contains("'shape': (20, 52)"), "{}", header);
+        assert_csi((f32_at(csi, (7 * 52 + 2) * 8), f32_at(csi, (7 * 52 + 2) * 8 + 4)), &measurements[7].subcarrier_data[2]);
+        let (header, rssi) = parse_npy(&members["rssi.npy"]);
+        assert!(header.contains("'descr': '<i2'"));
+        assert_eq!(rssi[2] as i8, measurements[1].rssi);
+
+        let (header, text) = parse_npy(&members["metadata.npy"]);
+        assert!(header.contains("'shape': ()"));
+        let json: String = text.chunks(4).map(|c| char::from_u32(u32_at(c, 0) as u32).unwrap()).collect();
+        let metadata: serde_json::Value = serde_json::from_str(json.trim_end_matches('\0')).unwrap();
+        assert_eq!(metadata["frames"], 20);
+    }
+
+    #[test]
+    fn mat_variables_are_column_major() {
+        let (measurements, path) = (recording(), temp("vars.mat"));
+        ArrayStorage::save_mat(&measurements, &[], &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(bytes.starts_with(b"MATLAB 5.0 MAT-file, esp-csi-tui-rs"));
+        assert_eq!((u16_at(&bytes, 124), &bytes[126..128]), (0x0100, &b"IM"[..]));
+
+        // Variable name -> (array flags, dimensions, data elements).
+        let mut variables = HashMap::new();
+        let mut at = 128;
+        while at < bytes.len() {
+            assert_eq!(u32_at(&bytes, at), 15);
+            let len = u32_at(&bytes, at + 4);
+            let mut matrix = Vec::new();
+            ZlibDecoder::new(&bytes[at + 8..at + 8 + len]).read_to_end(&mut matrix).unwrap();
+            at += 8 + len;
+
+            assert_eq!(u32_at(&matrix, 0), 14);
+            let mut elements = Vec::new();
+            let mut p = 8;
+            while p < matrix.len() {
+                let size = u32_at(&matrix, p + 4);
+                elements.push((u32_at(&matrix, p), matrix[p + 8..p + 8 + size].to_vec()));
+                p += 8 + size.next_multiple_of(8);
+            }
+            assert_eq!(p, matrix.len());
+            let name = String::from_utf8(elements[2].1.clone()).unwrap();
+            let dims = (u32_at(&elements[1].1, 0), u32_at(&elements[1].1, 4));
+            variables.insert(name, (u32_at(&elements[0].1, 0), dims, elements[3..].to_vec()));
+        }
+        assert_eq!(variables.len(), 11);
+
+        let (flags, dims, parts) = &variables["csi"];
+        assert_eq!((*flags, *dims, parts.len()), (0x0800 | 7, (20, 52), 2));
+        let (t, s) = (7, 2);
+        assert_csi((f32_at(&parts[0].1, (s * 20 + t) * 4), f32_at(&parts[1].1, (s * 20 + t) * 4)), &measurements[t].subcarrier_data[s]);
+
+        let (flags, dims, parts) = &variables["device"];
+        assert_eq!((*flags, *dims), (4, (20, 6)));
+        let first_row: String = (0..6).map(|c| char::from(parts[0].1[c * 40])).collect();
+        assert_eq!(first_row, "esp-a ");
+        assert_eq!(variables["sequence"].0, 14);
+        assert_eq!(variables["metadata"].1 .0, 1);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::calibration::BaselineProfile;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Directory, relative to the working directory, holding one profile per device and channel.
+pub const BASELINE_DIR: &str = "baselines";
+
+pub struct BaselineStorage;
+
+impl BaselineStorage {
+    /// File a profile for `device` on `channel` is stored under, e.g.
+    /// `baselines/dev_ttyUSB0_ch6.json`.
+    pub fn path_for(dir: &Path, device: &str, channel: u8) -> PathBuf {
+        let key: String = device
+            .trim_start_matches('/')
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        dir.join(format!("{}_ch{}.json", key, channel))
+    }
+
+    pub fn save(profile: &BaselineProfile, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let path = Self::path_for(dir, &profile.device, profile.channel);
+        std::fs::write(&path, serde_json::to_string_pretty(profile)?)?;
+        tracing::info!("Saved baseline profile to {}", path.display());
+        Ok(path)
+    }
+
+    /// Returns `Ok(None)` when no profile was recorded for this device and channel.
+    pub fn load(dir: &Path, device: &str, channel: u8) -> Result<Option<BaselineProfile>> {
+        let path = Self::path_for(dir, device, channel);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let profile = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        tracing::info!("Loaded baseline profile from {}", path.display());
+        Ok(Some(profile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{self, Scenario, Scene};
+    use chrono::Utc;
+
+    #[test]
+    fn profiles_are_keyed_by_device_and_channel() {
+        let dir = std::env::temp_dir().join(format!("esp-csi-baselines-{}", std::process::id()));
+        assert_eq!(
+            BaselineStorage::path_for(&dir, "/dev/ttyUSB0", 6),
+            dir.join("dev_ttyUSB0_ch6.json")
+        );
+
+        let mut profiles = Vec::new();
+        for (seed, device, channel) in [(1, "/dev/ttyUSB0", 6), (2, "/dev/ttyUSB0", 11), (3, "udp://0.0.0.0:5500", 6)] {
+            let mut capture = synthetic::capture(Scenario::new(Scene::EmptyRoom).with_seed(seed), Utc::now(), 4.0, 10.0);
+            capture.iter_mut().for_each(|m| m.channel = channel);
+            let profile = BaselineProfile::compute(device, &capture, 10).unwrap();
+            BaselineStorage::save(&profile, &dir).unwrap();
+            profiles.push(profile);
+        }
+
+        for profile in &profiles {
+            let loaded = BaselineStorage::load(&dir, &profile.device, profile.channel).unwrap().unwrap();
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(profile).unwrap());
+        }
+        assert!(BaselineStorage::load(&dir, "/dev/ttyUSB0", 1).unwrap().is_none());
+        assert!(BaselineStorage::load(&dir, "/dev/ttyUSB1", 6).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,10 @@
-use crate::models::CsiMeasurement;
-use anyhow::Result;
+use crate::dsp::pca::SubcarrierRank;
+use crate::dsp::{Spectrogram, TimeFrequencyAnalysis};
+use crate::models::{ComplexNumber, CsiMeasurement};
+use crate::survey::ChannelSummary;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use ndarray::Array2;
 use csv::Writer;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -16,6 +21,39 @@ struct CsvRecord {
     imag: f32,
     magnitude: f32,
     phase: f32,
+    sequence: Option<u32>,
+    device_timestamp_us: Option<u64>,
+    device_id: Option<String>,
+    transmitter: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct SubcarrierRankRecord {
+    rank: usize,
+    subcarrier: usize,
+    relative_variance: f32,
+    sensitivity: f32,
+}
+
+#[derive(Serialize, Debug)]
+struct ChannelSummaryRecord {
+    channel: u8,
+    frames: usize,
+    rate_hz: f32,
+    mean_rssi: Option<f32>,
+    noise_floor: Option<f32>,
+    snr_db: Option<f32>,
+    transmitters: usize,
+    /// MACs joined with `|`.
+    transmitter_macs: String,
+}
+
+#[derive(Serialize, Debug)]
+struct SpectrogramRecord {
+    subcarrier: Option<usize>,
+    time_s: f32,
+    frequency_hz: f32,
+    power: f32,
 }
 
 pub struct CsvStorage;
@@ -37,6 +75,10 @@ impl CsvStorage {
                     imag: subcarrier.imag,
                     magnitude: subcarrier.magnitude(),
                     phase: subcarrier.phase(),
+                    sequence: measurement.sequence,
+                    device_timestamp_us: measurement.device_timestamp_us,
+                    device_id: measurement.device_id.clone(),
+                    transmitter: measurement.transmitter.clone(),
                 };
 
                 writer.serialize(record)?;
@@ -48,14 +90,125 @@ impl CsvStorage {
         Ok(())
     }
 
+    /// Rebuilds measurements from their subcarrier rows; a row with subcarrier index 0
+    /// starts the next measurement.
     pub fn load(path: &Path) -> Result<Vec<CsiMeasurement>> {
-        let reader = csv::Reader::from_path(path)?;
-        let measurements = Vec::new();
+        let mut reader = csv::Reader::from_path(path)?;
+        let mut measurements: Vec<CsiMeasurement> = Vec::new();
 
-        for result in reader.into_records() {
-            let _record = result?;
+        for result in reader.deserialize() {
+            let record: CsvRecord = result?;
+            let subcarrier = ComplexNumber::new(record.real, record.imag);
+            match measurements.last_mut() {
+                Some(last) if record.subcarrier_index != 0 => last.subcarrier_data.push(subcarrier),
+                _ => measurements.push(CsiMeasurement {
+                    timestamp: DateTime::parse_from_rfc3339(&record.timestamp)
+                        .map_err(|e| anyhow!("Invalid timestamp '{}': {}", record.timestamp, e))?
+                        .with_timezone(&Utc),
+                    channel: record.channel,
+                    bandwidth: record.bandwidth,
+                    rssi: record.rssi,
+                    noise_floor: record.noise_floor,
+                    subcarrier_data: vec![subcarrier],
+                    sequence: record.sequence,
+                    device_timestamp_us: record.device_timestamp_us,
+                    device_id: record.device_id,
+                    transmitter: record.transmitter,
+                }),
+            }
         }
 
+        tracing::info!("Loaded {} measurements from CSV: {}", measurements.len(), path.display());
         Ok(measurements)
     }
+
+    /// Writes the averaged spectrogram (empty `subcarrier` column) followed by
+    /// every per-subcarrier spectrogram, one row per time/frequency cell.
+    pub fn save_spectrogram(analysis: &TimeFrequencyAnalysis, path: &Path) -> Result<()> {
+        let mut writer = Writer::from_path(path)?;
+
+        write_spectrogram(&mut writer, None, &analysis.averaged)?;
+        for (idx, spectrogram) in analysis.per_subcarrier.iter().enumerate() {
+            write_spectrogram(&mut writer, Some(idx), spectrogram)?;
+        }
+
+        writer.flush()?;
+        tracing::info!(
+            "Saved spectrogram ({} frames, {} subcarriers) to CSV: {}",
+            analysis.averaged.times.len(),
+            analysis.per_subcarrier.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Writes principal component scores as `timestamp,pc1,...,pck`, one row per frame.
+    pub fn save_components(times: &[DateTime<Utc>], scores: &Array2<f32>, path: &Path) -> Result<()> {
+        let mut writer = Writer::from_path(path)?;
+
+        let mut header = vec!["timestamp".to_string()];
+        header.extend((1..=scores.ncols()).map(|c| format!("pc{}", c)));
+        writer.write_record(&header)?;
+        for (time, row) in times.iter().zip(scores.rows()) {
+            let mut record = vec![time.to_rfc3339()];
+            record.extend(row.iter().map(|v| v.to_string()));
+            writer.write_record(&record)?;
+        }
+
+        writer.flush()?;
+        tracing::info!("Saved {} x {} component scores to CSV: {}", scores.nrows(), scores.ncols(), path.display());
+        Ok(())
+    }
+
+    pub fn save_subcarrier_ranking(ranks: &[SubcarrierRank], path: &Path) -> Result<()> {
+        let mut writer = Writer::from_path(path)?;
+        for (rank, r) in ranks.iter().enumerate() {
+            writer.serialize(SubcarrierRankRecord {
+                rank: rank + 1,
+                subcarrier: r.index,
+                relative_variance: r.relative_variance,
+                sensitivity: r.sensitivity,
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes a channel survey, one row per channel.
+    pub fn save_survey(summaries: &[ChannelSummary], path: &Path) -> Result<()> {
+        let mut writer = Writer::from_path(path)?;
+        for summary in summaries {
+            writer.serialize(ChannelSummaryRecord {
+                channel: summary.channel,
+                frames: summary.frames,
+                rate_hz: summary.rate_hz,
+                mean_rssi: summary.mean_rssi,
+                noise_floor: summary.noise_floor,
+                snr_db: summary.snr_db(),
+                transmitters: summary.transmitters.len(),
+                transmitter_macs: summary.transmitters.iter().cloned().collect::<Vec<_>>().join("|"),
+            })?;
+        }
+        writer.flush()?;
+        tracing::info!("Saved survey of {} channels to CSV: {}", summaries.len(), path.display());
+        Ok(())
+    }
+}
+
+fn write_spectrogram<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    subcarrier: Option<usize>,
+    spectrogram: &Spectrogram,
+) -> Result<()> {
+    for (time_s, frame) in spectrogram.times.iter().zip(&spectrogram.power) {
+        for (frequency_hz, power) in spectrogram.frequencies.iter().zip(frame) {
+            writer.serialize(SpectrogramRecord {
+                subcarrier,
+                time_s: *time_s,
+                frequency_hz: *frequency_hz,
+                power: *power,
+            })?;
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,184 @@
+use crate::classification::FeatureConfig;
+use crate::dsp::Resampler;
+use crate::labelling::LabelInterval;
+use crate::models::CsiMeasurement;
+use crate::storage::npy;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Serialize)]
+struct WindowInfo {
+    label: String,
+    start: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct DatasetMeta<'a> {
+    classes: &'a [String],
+    sample_rate_hz: f32,
+    window_s: f32,
+    hop_s: f32,
+    num_subcarriers: usize,
+    windows: Vec<WindowInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatasetSummary {
+    pub windows: usize,
+    pub classes: Vec<String>,
+}
+
+pub struct DatasetExporter;
+
+impl DatasetExporter {
+    /// Slices every labelled interval into fixed-length windows of resampled
+    /// amplitude and writes them to `dir` in an HDF5-style layout:
+    ///
+    /// - `X.npy`: f32 `[windows, window_len, subcarriers]`
+    /// - `y.npy`: i64 `[windows]`, indices into `classes`
+    /// - `meta.json`: class names, window parameters and each window's start time
+    pub fn export(
+        measurements: &[CsiMeasurement],
+        intervals: &[LabelInterval],
+        config: &FeatureConfig,
+        dir: &Path,
+    ) -> Result<DatasetSummary> {
+        let window = config.window_len();
+        let hop = config.hop_len();
+
+        let mut classes: Vec<String> = Vec::new();
+        let mut num_subcarriers = None;
+        let mut x = Vec::new();
+        let mut y = Vec::new();
+        let mut windows = Vec::new();
+
+        for interval in intervals {
+            let slice = interval.slice(measurements);
+            if slice.len() < 2 {
+                continue;
+            }
+            let series = Resampler::amplitudes(slice, config.sample_rate_hz)?;
+            let width = series.num_subcarriers();
+            if series.samples.len() < window || width != *num_subcarriers.get_or_insert(width) {
+                continue;
+            }
+
+            let class = match classes.iter().position(|c| *c == interval.label) {
+                Some(index) => index,
+                None => {
+                    classes.push(interval.label.clone());
+                    classes.len() - 1
+                }
+            };
+            for start in (0..=series.samples.len() - window).step_by(hop) {
+                x.extend(series.samples[start..start + window].iter().flatten());
+                y.push(class as i64);
+                windows.push(WindowInfo {
+                    label: interval.label.clone(),
+                    start: series.start
+                        + Duration::microseconds((start as f64 * 1e6 / config.sample_rate_hz as f64) as i64),
+                });
+            }
+        }
+
+        let num_subcarriers = num_subcarriers.unwrap_or(0);
+        if y.is_empty() {
+            return Err(anyhow!("No labelled interval is at least {:.1} s long", config.window_s));
+        }
+
+        std::fs::create_dir_all(dir)?;
+        npy::write_f32(&dir.join("X.npy"), &[y.len(), window, num_subcarriers], &x)?;
+        npy::write_i64(&dir.join("y.npy"), &[y.len()], &y)?;
+        let meta = DatasetMeta {
+            classes: &classes,
+            sample_rate_hz: config.sample_rate_hz,
+            window_s: config.window_s,
+            hop_s: config.hop_s,
+            num_subcarriers,
+            windows,
+        };
+        std::fs::write(dir.join("meta.json"), serde_json::to_string_pretty(&meta)?)?;
+
+        tracing::info!("Exported {} labelled windows to {}", y.len(), dir.display());
+        Ok(DatasetSummary { windows: y.len(), classes })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{self, Scenario, Scene};
+
+    fn interval(label: &str, start: DateTime<Utc>, from_s: i64, to_s: i64) -> LabelInterval {
+        LabelInterval {
+            label: label.to_string(),
+            start: start + Duration::seconds(from_s),
+            end: start + Duration::seconds(to_s),
+        }
+    }
+
+    /// Shape and data of an `.npy` file.
+    fn read_npy(path: &Path) -> (String, Vec<u8>) {
+        let bytes = std::fs::read(path).unwrap();
+        let len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = String::from_utf8(bytes[10..10 + len].to_vec()).unwrap();
+        let shape = header.split("'shape': ").nth(1).unwrap().split(')').next().unwrap();
+        (format!("{})", shape), bytes[10 + len..].to_vec())
+    }
+
+    #[test]
+    fn labelled_intervals_become_windows() {
+        let start = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        let mut measurements = synthetic::capture(Scenario::new(Scene::EmptyRoom).with_seed(3), start, 30.0, 10.0);
+        // The first interval is shorter than a window and has another subcarrier count;
+        // it is skipped without fixing the dataset's width.
+        for m in &mut measurements[5..15] {
+            m.subcarrier_data.truncate(20);
+        }
+        let intervals = [
+            interval("walk", start, 0, 1),
+            interval("empty", start, 2, 12),
+            interval("walk", start, 14, 20),
+            interval("empty", start, 22, 28),
+        ];
+        let dir = std::env::temp_dir().join(format!("esp-csi-{}-dataset", std::process::id()));
+        let config = FeatureConfig::default();
+
+        let summary = DatasetExporter::export(&measurements, &intervals, &config, &dir).unwrap();
+        let (x_shape, x) = read_npy(&dir.join("X.npy"));
+        let (y_shape, y) = read_npy(&dir.join("y.npy"));
+        let meta: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.join("meta.json")).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(summary.classes, ["empty", "walk"]);
+        assert_eq!(summary.windows, 8);
+        assert_eq!(x_shape, "(8, 40, 52)");
+        assert_eq!(x.len(), 8 * 40 * 52 * 4);
+        assert_eq!(y_shape, "(8,)");
+        let y: Vec<i64> = y.chunks(8).map(|b| i64::from_le_bytes(b.try_into().unwrap())).collect();
+        assert_eq!(y, [0, 0, 0, 0, 1, 1, 0, 0]);
+        assert_eq!(meta["num_subcarriers"], 52);
+        assert_eq!(meta["classes"], serde_json::json!(["empty", "walk"]));
+
+        let starts: Vec<DateTime<Utc>> =
+            meta["windows"].as_array().unwrap().iter().map(|w| w["start"].as_str().unwrap().parse().unwrap()).collect();
+        for (w, interval) in [(0, &intervals[1]), (4, &intervals[2]), (6, &intervals[3])] {
+            assert!((starts[w] - interval.start).num_milliseconds().abs() <= 100, "window {} starts at {}", w, starts[w]);
+            assert_eq!(starts[w + 1] - starts[w], Duration::seconds(2));
+            assert_eq!(meta["windows"][w]["label"], interval.label.as_str());
+        }
+    }
+
+    #[test]
+    fn intervals_shorter_than_a_window_are_an_error() {
+        let start = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        let measurements = synthetic::capture(Scenario::new(Scene::EmptyRoom).with_seed(3), start, 10.0, 10.0);
+        let dir = std::env::temp_dir().join(format!("esp-csi-{}-no-dataset", std::process::id()));
+        let intervals = [interval("walk", start, 1, 3)];
+        assert!(DatasetExporter::export(&measurements, &intervals, &FeatureConfig::default(), &dir).is_err());
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::labelling::LabelInterval;
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+
+pub struct LabelStorage;
+
+impl LabelStorage {
+    /// Labels for a recording live next to it, e.g. `recording.rrd` ->
+    /// `recording.labels.json`, so CSV and RRD copies of a capture share one file.
+    pub fn path_for(recording: &Path) -> PathBuf {
+        recording.with_extension("labels.json")
+    }
+
+    pub fn save(intervals: &[LabelInterval], recording: &Path) -> Result<PathBuf> {
+        let path = Self::path_for(recording);
+        let data = serde_json::json!({
+            "format": "csi_labels",
+            "version": 1,
+            "intervals": intervals,
+        });
+        std::fs::write(&path, serde_json::to_string_pretty(&data)?)?;
+        tracing::info!("Saved {} label intervals to {}", intervals.len(), path.display());
+        Ok(path)
+    }
+
+    /// Returns an empty list when the recording has no labels file.
+    pub fn load(recording: &Path) -> Result<Vec<LabelInterval>> {
+        let path = Self::path_for(recording);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let data: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        let intervals = data
+            .get("intervals")
+            .cloned()
+            .ok_or_else(|| anyhow!("Invalid labels file: {}", path.display()))?;
+        Ok(serde_json::from_value(intervals)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration};
+
+    #[test]
+    fn labels_round_trip_next_to_the_recording() {
+        let start = DateTime::from_timestamp(1_704_067_200, 500_000).unwrap();
+        let intervals = vec![
+            LabelInterval { label: "walk".to_string(), start, end: start + Duration::seconds(5) },
+            LabelInterval { label: "empty".to_string(), start: start + Duration::seconds(8), end: start + Duration::seconds(20) },
+        ];
+        let recording = std::env::temp_dir().join(format!("esp-csi-{}-labelled.rrd", std::process::id()));
+        assert!(LabelStorage::load(&recording).unwrap().is_empty());
+
+        let path = LabelStorage::save(&intervals, &recording).unwrap();
+        assert_eq!(path, recording.with_extension("labels.json"));
+        let loaded = LabelStorage::load(&recording.with_extension("csv")).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        for (a, b) in loaded.iter().zip(&intervals) {
+            assert_eq!((&a.label, a.start, a.end), (&b.label, b.start, b.end));
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::{Result, anyhow};
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use std::io::Write;
+use std::path::Path;
+
+// Data types and array classes of the MAT-file Level 5 format.
+const MI_INT8: u32 = 1;
+const MI_INT16: u32 = 3;
+const MI_UINT16: u32 = 4;
+const MI_INT32: u32 = 5;
+const MI_UINT32: u32 = 6;
+const MI_SINGLE: u32 = 7;
+const MI_INT64: u32 = 12;
+const MI_MATRIX: u32 = 14;
+const MI_COMPRESSED: u32 = 15;
+const MX_CHAR_CLASS: u8 = 4;
+const MX_SINGLE_CLASS: u8 = 7;
+const MX_INT16_CLASS: u8 = 10;
+const MX_INT64_CLASS: u8 = 14;
+const COMPLEX_FLAG: u32 = 0x0800;
+
+/// Contents of a MATLAB variable, in column-major order.
+pub enum MatData<'a> {
+    Int16(&'a [i16]),
+    Int64(&'a [i64]),
+    /// Single-precision complex, as real and imaginary parts.
+    Complex(&'a [f32], &'a [f32]),
+    /// Characters, stored as UTF-16 code units.
+    Char(&'a [u16]),
+}
+
+/// A Level 5 MAT-file of compressed variables, as MATLAB's `save -v7` writes, readable
+/// by `load` and `scipy.io.loadmat`.
+pub struct MatWriter {
+    bytes: Vec<u8>,
+}
+
+impl MatWriter {
+    pub fn new(description: &str) -> Self {
+        let mut text = format!("MATLAB 5.0 MAT-file, {}", description).into_bytes();
+        text.resize(116, b' ');
+        let mut bytes = text;
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&0x0100u16.to_le_bytes());
+        bytes.extend_from_slice(b"IM");
+        Self { bytes }
+    }
+
+    /// Adds variable `name` of size `rows` x `columns`.
+    pub fn add(&mut self, name: &str, rows: usize, columns: usize, data: MatData) -> Result<()> {
+        let (class, flags, values) = match data {
+            MatData::Int16(v) => (MX_INT16_CLASS, 0, element(MI_INT16, &le(v, |x| x.to_le_bytes()))),
+            MatData::Int64(v) => (MX_INT64_CLASS, 0, element(MI_INT64, &le(v, |x| x.to_le_bytes()))),
+            MatData::Char(v) => (MX_CHAR_CLASS, 0, element(MI_UINT16, &le(v, |x| x.to_le_bytes()))),
+            MatData::Complex(re, im) => {
+                let mut parts = element(MI_SINGLE, &le(re, |x| x.to_le_bytes()));
+                parts.extend(element(MI_SINGLE, &le(im, |x| x.to_le_bytes())));
+                (MX_SINGLE_CLASS, COMPLEX_FLAG, parts)
+            }
+        };
+        let dims: Vec<u8> = [rows, columns]
+            .iter()
+            .map(|&d| i32::try_from(d).map_err(|_| anyhow!("{} has too many elements for a MAT file", name)))
+            .collect::<Result<Vec<i32>>>()?
+            .iter()
+            .flat_map(|d| d.to_le_bytes())
+            .collect();
+
+        let mut matrix = element(MI_UINT32, &[(flags | class as u32).to_le_bytes(), [0; 4]].concat());
+        matrix.extend(element(MI_INT32, &dims));
+        matrix.extend(element(MI_INT8, name.as_bytes()));
+        matrix.extend(values);
+        if matrix.len() > u32::MAX as usize {
+            return Err(anyhow!("{} is too large for a MAT file (4 GiB)", name));
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&element(MI_MATRIX, &matrix))?;
+        let compressed = encoder.finish()?;
+        self.bytes.extend_from_slice(&MI_COMPRESSED.to_le_bytes());
+        self.bytes.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        self.bytes.extend_from_slice(&compressed);
+        Ok(())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, &self.bytes)?;
+        Ok(())
+    }
+}
+
+fn le<T: Copy, const N: usize>(values: &[T], to_bytes: impl Fn(T) -> [u8; N]) -> Vec<u8> {
+    values.iter().flat_map(|&v| to_bytes(v)).collect()
+}
+
+/// A data element: type, byte count and the data padded to 8 bytes.
+fn element(data_type: u32, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + data.len().next_multiple_of(8));
+    out.extend_from_slice(&data_type.to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    out.resize(8 + data.len().next_multiple_of(8), 0);
+    out
+}
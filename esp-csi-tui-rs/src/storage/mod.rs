@@ -1,11 +1,110 @@
+pub mod array_storage;
+pub mod baseline_storage;
 pub mod csv_storage;
+pub mod dataset_export;
+pub mod label_storage;
+pub mod mat;
+pub mod npy;
+pub mod parquet_storage;
 pub mod rrd_storage;
+pub mod session_storage;
 
 use crate::models::CsiMeasurement;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use array_storage::ArrayStorage;
+use csv_storage::CsvStorage;
+use parquet_storage::ParquetStorage;
+use rrd_storage::RrdStorage;
+use session_storage::{SessionDevice, SessionStorage};
 use std::path::Path;
 
 pub trait Storage {
     fn save(&self, measurements: &[CsiMeasurement], path: &Path) -> Result<()>;
     fn load(&self, path: &Path) -> Result<Vec<CsiMeasurement>>;
 }
+
+/// Saves a recording in the format named by the extension of `path`: `.rrd`, `.csv`,
+/// `.parquet`, `.npy`, `.npz` or `.mat`. Parquet and array formats embed `session`.
+pub fn save(measurements: &[CsiMeasurement], session: &[SessionDevice], path: &Path) -> Result<()> {
+    match extension(path).as_str() {
+        "rrd" => RrdStorage::save(measurements, path),
+        "csv" => CsvStorage::save(measurements, path),
+        "parquet" => ParquetStorage::save(measurements, session, path),
+        "npy" => ArrayStorage::save_npy(measurements, session, path),
+        "npz" => ArrayStorage::save_npz(measurements, session, path),
+        "mat" => ArrayStorage::save_mat(measurements, session, path),
+        other => Err(anyhow!("Unknown recording format '{}' (rrd, csv, parquet, npy, npz or mat)", other)),
+    }
+}
+
+/// Loads an `.rrd`, `.csv` or `.parquet` recording.
+pub fn load(path: &Path) -> Result<Vec<CsiMeasurement>> {
+    match extension(path).as_str() {
+        "rrd" => RrdStorage::load(path),
+        "csv" => CsvStorage::load(path),
+        "parquet" => ParquetStorage::load(path),
+        other => Err(anyhow!("Cannot read '{}' recordings, only rrd, csv and parquet", other)),
+    }
+}
+
+/// Converts a recording between formats, carrying its session metadata along, and
+/// returns the number of measurements.
+pub fn convert(input: &Path, output: &Path) -> Result<usize> {
+    let measurements = load(input)?;
+    if measurements.is_empty() {
+        return Err(anyhow!("{} holds no measurements", input.display()));
+    }
+    let mut session = SessionStorage::load(input)?;
+    if session.is_empty() && extension(input) == "parquet" {
+        session = ParquetStorage::load_session(input)?;
+    }
+    save(&measurements, &session, output)?;
+    if !session.is_empty() {
+        SessionStorage::save(&session, output)?;
+    }
+    Ok(measurements.len())
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{self, Scenario, Scene};
+    use chrono::DateTime;
+
+    #[test]
+    fn csv_recordings_convert_with_their_session() {
+        let start = DateTime::from_timestamp(1_704_067_200, 250_000).unwrap();
+        let mut measurements = synthetic::capture(Scenario::new(Scene::EmptyRoom).with_seed(5), start, 1.0, 10.0);
+        measurements[2].device_id = Some("esp-a".to_string());
+        measurements[2].transmitter = Some("02:00:00:00:06:01".to_string());
+        let dir = std::env::temp_dir().join(format!("esp-csi-convert-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (csv, npz) = (dir.join("capture.csv"), dir.join("capture.npz"));
+
+        save(&measurements, &[], &csv).unwrap();
+        let loaded = load(&csv).unwrap();
+        assert_eq!(loaded.len(), measurements.len());
+        for (a, b) in loaded.iter().zip(&measurements) {
+            assert_eq!(serde_json::to_value(a).unwrap(), serde_json::to_value(b).unwrap());
+        }
+
+        let session = [SessionDevice {
+            id: "esp-a".to_string(),
+            demo: true,
+            config: Default::default(),
+            info: None,
+            csi_captured: vec!["lltf".to_string()],
+            frames: 10,
+        }];
+        SessionStorage::save(&session, &csv).unwrap();
+        assert_eq!(convert(&csv, &npz).unwrap(), 10);
+        assert_eq!(SessionStorage::load(&npz).unwrap()[0].id, "esp-a");
+        assert!(convert(&npz, &csv).is_err());
+        assert!(save(&measurements, &[], &dir.join("capture.h5")).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{Result, anyhow};
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use std::io::Write;
+use std::path::Path;
+
+/// Writes a little-endian, C-order NumPy `.npy` (format 1.0) file.
+pub fn write_f32(path: &Path, shape: &[usize], data: &[f32]) -> Result<()> {
+    let bytes: Vec<u8> = data.iter().flat_map(|v| v.to_le_bytes()).collect();
+    write(path, &encode("'<f4'", shape, &bytes))
+}
+
+pub fn write_i64(path: &Path, shape: &[usize], data: &[i64]) -> Result<()> {
+    let bytes: Vec<u8> = data.iter().flat_map(|v| v.to_le_bytes()).collect();
+    write(path, &encode("'<i8'", shape, &bytes))
+}
+
+/// Encodes an array as `.npy`. `descr` is the dtype as a Python literal, e.g. `'<c8'`
+/// or a structured `[('a', '<i8'), ('b', '<c8', (52,))]`.
+pub fn encode(descr: &str, shape: &[usize], bytes: &[u8]) -> Vec<u8> {
+    let dims = match shape {
+        [n] => format!("({},)", n),
+        _ => format!("({})", shape.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': {}, 'fortran_order': False, 'shape': {}, }}", descr, dims);
+    // Magic (6) + version (2) + header length (2) + header must be a multiple of 64,
+    // with the header terminated by a newline.
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+
+    let mut out = Vec::with_capacity(header.len() + 10 + bytes.len());
+    out.extend_from_slice(b"\x93NUMPY\x01\x00");
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// Writes `.npy` members into a deflated `.npz` archive, as `numpy.savez_compressed`
+/// does; `numpy.load` returns them by name without the `.npy` suffix.
+pub fn write_npz(path: &Path, members: &[(&str, Vec<u8>)]) -> Result<()> {
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in members {
+        let name = format!("{}.npy", name);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        let offset = archive.len();
+        if [data.len(), compressed.len(), offset].iter().any(|&n| n > u32::MAX as usize) {
+            return Err(anyhow!("{} is too large for an .npz archive (4 GiB)", name));
+        }
+
+        // Version 2.0, no flags, deflate, 1980-01-01 00:00, CRC, sizes, name length.
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&[20, 0, 0, 0, 8, 0, 0, 0, 0x21, 0]);
+        fields.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        fields.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&[0, 0]);
+
+        archive.extend_from_slice(b"PK\x03\x04");
+        archive.extend_from_slice(&fields);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&compressed);
+
+        directory.extend_from_slice(b"PK\x01\x02\x14\x00");
+        directory.extend_from_slice(&fields);
+        // Comment length, disk, internal and external attributes, local header offset.
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&(offset as u32).to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = archive.len();
+    if directory_offset > u32::MAX as usize {
+        return Err(anyhow!("Archive is too large for an .npz file (4 GiB)"));
+    }
+    let count = (members.len() as u16).to_le_bytes();
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    archive.extend_from_slice(&count);
+    archive.extend_from_slice(&count);
+    archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&(directory_offset as u32).to_le_bytes());
+    archive.extend_from_slice(&[0, 0]);
+    write(path, &archive)
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    file.write_all(bytes)?;
+    file.flush()?;
+    Ok(())
+}
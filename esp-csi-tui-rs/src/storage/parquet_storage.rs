@@ -0,0 +1,330 @@
+use crate::models::{ComplexNumber, CsiMeasurement};
+use crate::storage::session_storage::SessionDevice;
+use anyhow::{Context, Result, anyhow};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Int8Type, TimestampMicrosecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow_array::{
+    Array, ArrayRef, Float32Array, Int8Array, ListArray, RecordBatch, StringArray, StructArray,
+    TimestampMicrosecondArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use chrono::DateTime;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Measurements buffered before they are encoded as a batch.
+const BATCH_ROWS: usize = 1024;
+/// Measurements per row group, the unit readers skip or load at once.
+const ROW_GROUP_ROWS: usize = 16 * BATCH_ROWS;
+/// Key of the session metadata in the file's key-value metadata.
+const SESSION_KEY: &str = "csi_session";
+
+fn subcarrier_fields() -> Fields {
+    Fields::from(vec![Field::new("real", DataType::Float32, false), Field::new("imag", DataType::Float32, false)])
+}
+
+fn subcarrier_item() -> Arc<Field> {
+    Arc::new(Field::new("item", DataType::Struct(subcarrier_fields()), false))
+}
+
+/// One row per measurement, the subcarriers as a list of `{real, imag}` structs.
+fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("timestamp", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
+        Field::new("device_id", DataType::Utf8, true),
+        Field::new("channel", DataType::UInt8, false),
+        Field::new("bandwidth", DataType::UInt16, false),
+        Field::new("rssi", DataType::Int8, false),
+        Field::new("noise_floor", DataType::Int8, false),
+        Field::new("sequence", DataType::UInt32, true),
+        Field::new("device_timestamp_us", DataType::UInt64, true),
+        Field::new("transmitter", DataType::Utf8, true),
+        Field::new("subcarriers", DataType::List(subcarrier_item()), false),
+    ]))
+}
+
+fn batch(schema: &SchemaRef, measurements: &[CsiMeasurement]) -> Result<RecordBatch> {
+    let subcarriers = measurements.iter().flat_map(|m| &m.subcarrier_data);
+    let parts: Vec<ArrayRef> = vec![
+        Arc::new(subcarriers.clone().map(|c| c.real).collect::<Float32Array>()),
+        Arc::new(subcarriers.map(|c| c.imag).collect::<Float32Array>()),
+    ];
+    let list = ListArray::try_new(
+        subcarrier_item(),
+        arrow_buffer::OffsetBuffer::from_lengths(measurements.iter().map(|m| m.subcarrier_data.len())),
+        Arc::new(StructArray::try_new(subcarrier_fields(), parts, None)?),
+        None,
+    )?;
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            measurements
+                .iter()
+                .map(|m| Some(m.timestamp.timestamp_micros()))
+                .collect::<TimestampMicrosecondArray>()
+                .with_timezone("UTC"),
+        ),
+        Arc::new(measurements.iter().map(|m| m.device_id.as_deref()).collect::<StringArray>()),
+        Arc::new(measurements.iter().map(|m| Some(m.channel)).collect::<UInt8Array>()),
+        Arc::new(measurements.iter().map(|m| Some(m.bandwidth)).collect::<UInt16Array>()),
+        Arc::new(measurements.iter().map(|m| Some(m.rssi)).collect::<Int8Array>()),
+        Arc::new(measurements.iter().map(|m| Some(m.noise_floor)).collect::<Int8Array>()),
+        Arc::new(measurements.iter().map(|m| m.sequence).collect::<UInt32Array>()),
+        Arc::new(measurements.iter().map(|m| m.device_timestamp_us).collect::<UInt64Array>()),
+        Arc::new(measurements.iter().map(|m| m.transmitter.as_deref()).collect::<StringArray>()),
+        Arc::new(list),
+    ];
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Streams measurements into a zstd-compressed Parquet file as they arrive, so a
+/// multi-hour capture never has to be held in memory. Each row group is written out
+/// as soon as it is full, but only a closed file has the footer readers need.
+pub struct ParquetWriter {
+    path: PathBuf,
+    schema: SchemaRef,
+    writer: ArrowWriter<File>,
+    pending: Vec<CsiMeasurement>,
+    written: usize,
+}
+
+impl ParquetWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .set_max_row_group_size(ROW_GROUP_ROWS)
+            .build();
+        let schema = schema();
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(properties))?;
+        Ok(Self { path: path.to_path_buf(), schema, writer, pending: Vec::new(), written: 0 })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Measurements written so far, including those still buffered.
+    pub fn len(&self) -> usize {
+        self.written + self.pending.len()
+    }
+
+    pub fn write<'a>(&mut self, measurements: impl IntoIterator<Item = &'a CsiMeasurement>) -> Result<()> {
+        self.pending.extend(measurements.into_iter().cloned());
+        if self.pending.len() >= BATCH_ROWS {
+            self.write_pending()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered measurements, ending each row group as it fills. Rows that
+    /// fail to encode or write are dropped, so the file can still be closed.
+    fn write_pending(&mut self) -> Result<()> {
+        let mut pending = std::mem::take(&mut self.pending);
+        while !pending.is_empty() {
+            let room = ROW_GROUP_ROWS - self.written % ROW_GROUP_ROWS;
+            let rows: Vec<CsiMeasurement> = pending.drain(..room.min(pending.len())).collect();
+            self.writer.write(&batch(&self.schema, &rows)?)?;
+            self.written += rows.len();
+            if self.written.is_multiple_of(ROW_GROUP_ROWS) {
+                self.writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes what is buffered and the footer, storing `session` in the file's
+    /// key-value metadata, and returns the number of measurements.
+    pub fn close(mut self, session: &[SessionDevice]) -> Result<usize> {
+        self.write_pending()?;
+        if !session.is_empty() {
+            self.writer.append_key_value_metadata(KeyValue::new(SESSION_KEY.to_string(), serde_json::to_string(session)?));
+        }
+        self.writer.close()?;
+        tracing::info!("Saved {} measurements to Parquet: {}", self.written, self.path.display());
+        Ok(self.written)
+    }
+}
+
+/// Columnar storage for DuckDB, Polars and pandas.
+pub struct ParquetStorage;
+
+impl ParquetStorage {
+    pub fn save(measurements: &[CsiMeasurement], session: &[SessionDevice], path: &Path) -> Result<()> {
+        let mut writer = ParquetWriter::create(path)?;
+        for chunk in measurements.chunks(BATCH_ROWS) {
+            writer.write(chunk)?;
+        }
+        writer.close(session)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Vec<CsiMeasurement>> {
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+        let mut measurements = Vec::new();
+        for batch in reader {
+            let batch = batch?;
+            let column = |name: &str| batch.column_by_name(name).ok_or_else(|| anyhow!("Missing column '{}'", name));
+            let timestamp = column("timestamp")?.as_primitive_opt::<TimestampMicrosecondType>();
+            let device_id = column("device_id")?.as_string_opt::<i32>();
+            let channel = column("channel")?.as_primitive_opt::<UInt8Type>();
+            let bandwidth = column("bandwidth")?.as_primitive_opt::<UInt16Type>();
+            let rssi = column("rssi")?.as_primitive_opt::<Int8Type>();
+            let noise_floor = column("noise_floor")?.as_primitive_opt::<Int8Type>();
+            let sequence = column("sequence")?.as_primitive_opt::<UInt32Type>();
+            let device_timestamp_us = column("device_timestamp_us")?.as_primitive_opt::<UInt64Type>();
+            let transmitter = column("transmitter")?.as_string_opt::<i32>();
+            let subcarriers = column("subcarriers")?.as_list_opt::<i32>();
+            let (
+                Some(timestamp),
+                Some(device_id),
+                Some(channel),
+                Some(bandwidth),
+                Some(rssi),
+                Some(noise_floor),
+                Some(sequence),
+                Some(device_timestamp_us),
+                Some(transmitter),
+                Some(subcarriers),
+            ) = (timestamp, device_id, channel, bandwidth, rssi, noise_floor, sequence, device_timestamp_us, transmitter, subcarriers)
+            else {
+                return Err(anyhow!("Unexpected column types in {}", path.display()));
+            };
+            let values = subcarriers.values().as_struct();
+            let (real, imag) = (values.column(0).as_primitive::<Float32Type>(), values.column(1).as_primitive::<Float32Type>());
+            let optional = |array: &dyn Array, row: usize| array.is_valid(row);
+
+            for row in 0..batch.num_rows() {
+                let range = subcarriers.value_offsets()[row] as usize..subcarriers.value_offsets()[row + 1] as usize;
+                measurements.push(CsiMeasurement {
+                    timestamp: DateTime::from_timestamp_micros(timestamp.value(row))
+                        .ok_or_else(|| anyhow!("Invalid timestamp in row {}", row))?,
+                    channel: channel.value(row),
+                    bandwidth: bandwidth.value(row),
+                    rssi: rssi.value(row),
+                    noise_floor: noise_floor.value(row),
+                    subcarrier_data: range.map(|i| ComplexNumber::new(real.value(i), imag.value(i))).collect(),
+                    sequence: optional(sequence, row).then(|| sequence.value(row)),
+                    device_timestamp_us: optional(device_timestamp_us, row).then(|| device_timestamp_us.value(row)),
+                    device_id: optional(device_id, row).then(|| device_id.value(row).to_string()),
+                    transmitter: optional(transmitter, row).then(|| transmitter.value(row).to_string()),
+                });
+            }
+        }
+        tracing::info!("Loaded {} measurements from Parquet: {}", measurements.len(), path.display());
+        Ok(measurements)
+    }
+
+    /// Session metadata stored by [`ParquetWriter::close`], if any.
+    pub fn load_session(path: &Path) -> Result<Vec<SessionDevice>> {
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+        let session = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .and_then(|kv| kv.iter().find(|kv| kv.key == SESSION_KEY))
+            .and_then(|kv| kv.value.as_deref());
+        match session {
+            Some(json) => Ok(serde_json::from_str(json)?),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{self, Scenario, Scene};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn recording(seconds: f64) -> Vec<CsiMeasurement> {
+        let start = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        let mut measurements = synthetic::capture(Scenario::new(Scene::EmptyRoom).with_seed(9), start, seconds, 100.0);
+        for (i, m) in measurements.iter_mut().enumerate() {
+            m.device_id = (i % 3 != 0).then(|| format!("esp-{}", i % 2));
+            m.sequence = m.sequence.filter(|_| i % 5 != 0);
+            m.transmitter = (i % 7 == 0).then(|| "02:00:00:00:06:01".to_string());
+        }
+        measurements[10].subcarrier_data.truncate(3);
+        measurements
+    }
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("esp-csi-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn measurements_round_trip_as_compressed_rows() {
+        let (measurements, path) = (recording(25.0), temp("round-trip.parquet"));
+        let session = [SessionDevice {
+            id: "esp-1".to_string(),
+            demo: false,
+            config: Default::default(),
+            info: None,
+            csi_captured: vec!["htltf".to_string()],
+            frames: measurements.len(),
+        }];
+        ParquetStorage::save(&measurements, &session, &path).unwrap();
+
+        let loaded = ParquetStorage::load(&path).unwrap();
+        assert_eq!(loaded.len(), 2500);
+        for (a, b) in loaded.iter().zip(&measurements) {
+            assert_eq!(serde_json::to_value(a).unwrap(), serde_json::to_value(b).unwrap());
+        }
+        assert_eq!(ParquetStorage::load_session(&path).unwrap()[0].csi_captured, ["htltf"]);
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 2500);
+        let row_group = metadata.row_group(0);
+        assert!(row_group.columns().iter().all(|c| c.compression() == Compression::ZSTD(ZstdLevel::default())));
+        let raw = 2500 * 52 * 8;
+        assert!(std::fs::metadata(&path).unwrap().len() < raw as u64, "not compressed");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn streamed_frames_are_written_in_batches() {
+        let (measurements, path) = (recording(12.0), temp("stream.parquet"));
+        let mut writer = ParquetWriter::create(&path).unwrap();
+        for chunk in measurements.chunks(8) {
+            writer.write(chunk).unwrap();
+            assert_eq!(writer.pending.len(), writer.len() % BATCH_ROWS);
+        }
+        assert_eq!((writer.written, writer.len()), (BATCH_ROWS, 1200));
+        assert_eq!(writer.close(&[]).unwrap(), 1200);
+
+        let loaded = ParquetStorage::load(&path).unwrap();
+        assert_eq!(loaded.len(), 1200);
+        assert_eq!(loaded[10].subcarrier_data.len(), 3);
+        assert_eq!(loaded[1199].timestamp, measurements[1199].timestamp);
+        assert!(ParquetStorage::load_session(&path).unwrap().is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn full_row_groups_reach_the_file_before_close() {
+        let (measurements, path) = (recording(170.0), temp("row-groups.parquet"));
+        let mut writer = ParquetWriter::create(&path).unwrap();
+        for chunk in measurements.chunks(500) {
+            writer.write(chunk).unwrap();
+        }
+        assert_eq!(writer.writer.flushed_row_groups().len(), 1);
+        assert_eq!(writer.writer.flushed_row_groups()[0].num_rows(), ROW_GROUP_ROWS as i64);
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+
+        assert_eq!(writer.close(&[]).unwrap(), 17_000);
+        assert_eq!(ParquetStorage::load(&path).unwrap().len(), 17_000);
+        std::fs::remove_file(path).unwrap();
+    }
+}
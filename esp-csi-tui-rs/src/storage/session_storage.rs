@@ -0,0 +1,73 @@
+use crate::device::manager::DeviceHandle;
+use crate::models::{DeviceConfig, DeviceInfo};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// One receiver as it was when a recording was saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDevice {
+    pub id: String,
+    pub demo: bool,
+    pub config: DeviceConfig,
+    /// Last reply to a device query, if the firmware answered one.
+    pub info: Option<DeviceInfo>,
+    /// CSI captures in effect (e.g. `lltf`, `htltf`), as last reported by the firmware
+    /// or, without a report, as configured.
+    pub csi_captured: Vec<String>,
+    pub frames: usize,
+}
+
+impl SessionDevice {
+    pub fn from_handle(device: &DeviceHandle) -> Self {
+        Self {
+            id: device.id.clone(),
+            demo: device.demo,
+            config: device.config.clone(),
+            info: device.info.clone(),
+            csi_captured: match &device.info {
+                Some(info) => info.csi_flags.clone(),
+                None => device.config.csi.enabled().into_iter().map(String::from).collect(),
+            },
+            frames: device.measurements.total(),
+        }
+    }
+}
+
+pub struct SessionStorage;
+
+impl SessionStorage {
+    /// Session metadata sits next to the recording, e.g. `recording.rrd` ->
+    /// `recording.session.json`.
+    pub fn path_for(recording: &Path) -> PathBuf {
+        recording.with_extension("session.json")
+    }
+
+    pub fn save(devices: &[SessionDevice], recording: &Path) -> Result<PathBuf> {
+        let path = Self::path_for(recording);
+        let data = serde_json::json!({
+            "format": "csi_session",
+            "version": 1,
+            "saved_at": chrono::Utc::now(),
+            "host_version": env!("CARGO_PKG_VERSION"),
+            "devices": devices,
+        });
+        std::fs::write(&path, serde_json::to_string_pretty(&data)?)?;
+        tracing::info!("Saved session metadata for {} device(s) to {}", devices.len(), path.display());
+        Ok(path)
+    }
+
+    /// Returns an empty list when the recording has no session file.
+    pub fn load(recording: &Path) -> Result<Vec<SessionDevice>> {
+        let path = Self::path_for(recording);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let data: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        let devices = data
+            .get("devices")
+            .cloned()
+            .ok_or_else(|| anyhow!("Invalid session file: {}", path.display()))?;
+        Ok(serde_json::from_value(devices)?)
+    }
+}
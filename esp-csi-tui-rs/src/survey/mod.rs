@@ -0,0 +1,273 @@
+use crate::models::CsiMeasurement;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeSet;
+
+/// Channels surveyed when none are given: those usable in most regions.
+pub const DEFAULT_CHANNELS: std::ops::RangeInclusive<u8> = 1..=13;
+pub const DEFAULT_DWELL: Duration = Duration::seconds(5);
+/// Time after switching channel before frames count, so the radio settles and frames
+/// already in flight drain.
+const SETTLE: Duration = Duration::milliseconds(300);
+
+/// Channels to step through and how long to stay on each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurveyPlan {
+    pub channels: Vec<u8>,
+    pub dwell: Duration,
+}
+
+impl Default for SurveyPlan {
+    fn default() -> Self {
+        Self { channels: DEFAULT_CHANNELS.collect(), dwell: DEFAULT_DWELL }
+    }
+}
+
+impl SurveyPlan {
+    /// Parses `[channels=1,6,11|1-13] [dwell=<n>s]`.
+    pub fn parse(args: &str) -> Result<Self> {
+        let mut plan = Self::default();
+        for arg in args.split_whitespace() {
+            let (key, value) = arg
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value, got '{}'", arg))?;
+            match key {
+                "channels" => plan.channels = parse_channels(value)?,
+                "dwell" => {
+                    let seconds: f64 = value
+                        .trim_end_matches('s')
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid dwell '{}' (seconds, e.g. 5s)", value))?;
+                    if seconds < 1.0 {
+                        return Err(anyhow!("Dwell must be at least 1 s"));
+                    }
+                    plan.dwell = Duration::milliseconds((seconds * 1000.0) as i64);
+                }
+                _ => return Err(anyhow!("Unknown survey setting '{}'", key)),
+            }
+        }
+        Ok(plan)
+    }
+}
+
+fn parse_channels(text: &str) -> Result<Vec<u8>> {
+    let invalid = || anyhow!("Invalid channels '{}' (e.g. 1,6,11 or 1-13)", text);
+    let mut channels = Vec::new();
+    for part in text.split(',') {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let first: u8 = first.parse().map_err(|_| invalid())?;
+        let last: u8 = last.parse().map_err(|_| invalid())?;
+        if first == 0 || last > 14 || first > last {
+            return Err(anyhow!("Channels must be between 1 and 14, got '{}'", part));
+        }
+        channels.extend(first..=last);
+    }
+    let mut seen = BTreeSet::new();
+    channels.retain(|channel| seen.insert(*channel));
+    Ok(channels)
+}
+
+/// What a channel looked like while the device dwelt on it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelSummary {
+    pub channel: u8,
+    pub frames: usize,
+    pub rate_hz: f32,
+    pub mean_rssi: Option<f32>,
+    pub noise_floor: Option<f32>,
+    /// Distinct transmitter MACs, when the firmware reports them.
+    pub transmitters: BTreeSet<String>,
+}
+
+impl ChannelSummary {
+    /// Mean RSSI above the mean noise floor.
+    pub fn snr_db(&self) -> Option<f32> {
+        Some(self.mean_rssi? - self.noise_floor?)
+    }
+}
+
+/// What the survey wants done now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurveyAction {
+    Tune(u8),
+    Finished,
+}
+
+/// Steps a device through [`SurveyPlan::channels`], summarising the frames received on
+/// each. Driven by [`Survey::update`] with the clock; frames are fed through
+/// [`Survey::record`] and only count when they report the channel being surveyed.
+#[derive(Debug, Clone)]
+pub struct Survey {
+    pub plan: SurveyPlan,
+    pub results: Vec<ChannelSummary>,
+    /// Index into the plan's channels and when the device was tuned to it.
+    current: Option<(usize, DateTime<Utc>)>,
+    rssi_sum: f32,
+    noise_sum: f32,
+    finished: bool,
+}
+
+impl Survey {
+    pub fn new(plan: SurveyPlan) -> Self {
+        Self { plan, results: Vec::new(), current: None, rssi_sum: 0.0, noise_sum: 0.0, finished: false }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn current_channel(&self) -> Option<u8> {
+        self.current.map(|(index, _)| self.plan.channels[index])
+    }
+
+    pub fn record(&mut self, measurement: &CsiMeasurement, now: DateTime<Utc>) {
+        let Some((index, tuned)) = self.current else { return };
+        if measurement.channel != self.plan.channels[index] || now - tuned < SETTLE {
+            return;
+        }
+        let summary = self.results.last_mut().expect("a summary per visited channel");
+        summary.frames += 1;
+        self.rssi_sum += measurement.rssi as f32;
+        self.noise_sum += measurement.noise_floor as f32;
+        summary.mean_rssi = Some(self.rssi_sum / summary.frames as f32);
+        summary.noise_floor = Some(self.noise_sum / summary.frames as f32);
+        if let Some(transmitter) = &measurement.transmitter {
+            summary.transmitters.insert(transmitter.clone());
+        }
+    }
+
+    pub fn update(&mut self, now: DateTime<Utc>) -> Option<SurveyAction> {
+        if self.finished {
+            return None;
+        }
+        let next = match self.current {
+            None => 0,
+            Some((index, tuned)) if now - tuned >= SETTLE + self.plan.dwell => {
+                let summary = self.results.last_mut().expect("a summary per visited channel");
+                summary.rate_hz = summary.frames as f32 / self.plan.dwell.num_milliseconds() as f32 * 1000.0;
+                index + 1
+            }
+            Some(_) => return None,
+        };
+        let Some(&channel) = self.plan.channels.get(next) else {
+            self.current = None;
+            self.finished = true;
+            return Some(SurveyAction::Finished);
+        };
+        self.current = Some((next, now));
+        self.rssi_sum = 0.0;
+        self.noise_sum = 0.0;
+        self.results.push(ChannelSummary { channel, ..ChannelSummary::default() });
+        Some(SurveyAction::Tune(channel))
+    }
+
+    /// Ends the survey early; the current channel's rate covers the time spent on it.
+    pub fn cancel(&mut self, now: DateTime<Utc>) {
+        if let (Some((_, tuned)), Some(summary)) = (self.current, self.results.last_mut()) {
+            let elapsed_ms = (now - tuned - SETTLE).num_milliseconds();
+            summary.rate_hz = if elapsed_ms > 0 { summary.frames as f32 / elapsed_ms as f32 * 1000.0 } else { 0.0 };
+        }
+        self.current = None;
+        self.finished = true;
+    }
+
+    /// Surveyed channel with the best link margin in 3 dB steps, fewer transmitters
+    /// breaking ties.
+    pub fn best(&self) -> Option<&ChannelSummary> {
+        self.results
+            .iter()
+            .filter(|s| s.frames > 0 && Some(s.channel) != self.current_channel())
+            .max_by(|a, b| {
+                let margin = |s: &ChannelSummary| (s.snr_db().unwrap_or(f32::MIN) / 3.0).round() as i32;
+                margin(a).cmp(&margin(b)).then(b.transmitters.len().cmp(&a.transmitters.len()))
+            })
+    }
+
+    /// One row per surveyed channel, the best marked with `*`.
+    pub fn table(&self) -> String {
+        let best = self.best().map(|s| s.channel);
+        let mut text = String::from("  Ch  Frames  Rate Hz  RSSI dBm  Noise dBm  SNR dB  Transmitters\n");
+        let db = |value: Option<f32>| value.map_or("-".to_string(), |v| format!("{:.1}", v));
+        for summary in &self.results {
+            let live = Some(summary.channel) == self.current_channel();
+            text.push_str(&format!(
+                "{} {:>3}  {:>6}  {:>7}  {:>8}  {:>9}  {:>6}  {:>12}\n",
+                if Some(summary.channel) == best { "*" } else if live { ">" } else { " " },
+                summary.channel,
+                summary.frames,
+                if live { "-".to_string() } else { format!("{:.1}", summary.rate_hz) },
+                db(summary.mean_rssi),
+                db(summary.noise_floor),
+                db(summary.snr_db()),
+                summary.transmitters.len()
+            ));
+        }
+        text
+    }
+
+    /// Time left on the current channel and in the whole survey.
+    pub fn remaining(&self, now: DateTime<Utc>) -> (Duration, Duration) {
+        let per_channel = SETTLE + self.plan.dwell;
+        let Some((index, tuned)) = self.current else { return (Duration::zero(), Duration::zero()) };
+        let here = (tuned + per_channel - now).max(Duration::zero());
+        let later = self.plan.channels.len() - index - 1;
+        (here, here + per_channel * later as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ComplexNumber;
+
+    fn at(ms: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap() + Duration::milliseconds(ms)
+    }
+
+    fn frame(channel: u8, rssi: i8, transmitter: &str) -> CsiMeasurement {
+        CsiMeasurement {
+            timestamp: Utc::now(),
+            channel,
+            bandwidth: 20,
+            rssi,
+            noise_floor: -95,
+            subcarrier_data: vec![ComplexNumber::new(1.0, 0.0)],
+            sequence: None,
+            device_timestamp_us: None,
+            device_id: None,
+            transmitter: Some(transmitter.to_string()),
+        }
+    }
+
+    #[test]
+    fn parses_channel_lists() {
+        let plan = SurveyPlan::parse("channels=1-3,6,11 dwell=2s").unwrap();
+        assert_eq!(plan.channels, vec![1, 2, 3, 6, 11]);
+        assert_eq!(plan.dwell, Duration::seconds(2));
+        assert_eq!(SurveyPlan::parse("").unwrap(), SurveyPlan::default());
+        assert!(SurveyPlan::parse("channels=0-3").is_err());
+        assert!(SurveyPlan::parse("channels=6-1").is_err());
+        assert!(SurveyPlan::parse("dwell=0.5s").is_err());
+    }
+
+    #[test]
+    fn steps_through_channels_and_summarises_each() {
+        let mut survey = Survey::new(SurveyPlan::parse("channels=1,6 dwell=1s").unwrap());
+        assert_eq!(survey.update(at(0)), Some(SurveyAction::Tune(1)));
+        survey.record(&frame(11, -40, "a"), at(100)); // before settling
+        survey.record(&frame(1, -60, "a"), at(400));
+        survey.record(&frame(6, -40, "a"), at(500)); // still on the old channel
+        survey.record(&frame(1, -70, "b"), at(600));
+        assert_eq!(survey.update(at(1000)), None);
+        assert_eq!(survey.update(at(1300)), Some(SurveyAction::Tune(6)));
+        survey.record(&frame(6, -50, "c"), at(1700));
+        assert_eq!(survey.update(at(2600)), Some(SurveyAction::Finished));
+        assert!(survey.is_finished());
+
+        let first = &survey.results[0];
+        assert_eq!((first.frames, first.mean_rssi, first.transmitters.len()), (2, Some(-65.0), 2));
+        assert_eq!(first.snr_db(), Some(30.0));
+        assert!((first.rate_hz - 2.0).abs() < 1e-6);
+        assert_eq!(survey.best().map(|s| s.channel), Some(6));
+    }
+}
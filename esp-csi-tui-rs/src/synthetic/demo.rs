@@ -0,0 +1,157 @@
+use crate::models::{CsiMeasurement, DeviceConfig};
+use crate::synthetic::{Scenario, Scene, SyntheticCsi};
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Instant;
+
+/// Breathing rate of the stationary subject simulated in demo mode.
+pub const DEMO_BREATHING_BPM: f64 = 15.0;
+/// Start of the virtual clock of seeded demo devices: 2024-01-01 00:00:00 UTC.
+const VIRTUAL_EPOCH_S: i64 = 1_704_067_200;
+
+/// Time base of demo frames.
+#[derive(Debug, Clone, Copy)]
+enum DemoClock {
+    /// Host time since the source was created.
+    Wall { started: Instant },
+    /// Starts at a fixed time and moves one frame interval per frame, so a run is
+    /// the same however fast frames are drawn.
+    Virtual { start: DateTime<Utc>, elapsed: Duration },
+}
+
+/// Frames of a demo device: the synthetic channel of its scenario, on the channel and
+/// at the rate it was configured with, heard from a few neighbouring stations.
+///
+/// A seeded source draws everything from its seed and runs on a virtual clock, so it
+/// produces the same measurements, byte for byte, on every run.
+pub struct DemoSource {
+    /// Radio settings the demo device was configured with.
+    pub config: DeviceConfig,
+    generator: SyntheticCsi,
+    clock: DemoClock,
+    /// Clock reading when the current scenario began.
+    scenario_start: Duration,
+    sequence: u32,
+    seeded: bool,
+    rng: StdRng,
+}
+
+impl DemoSource {
+    /// A breathing subject in a random room, on host time.
+    pub fn new() -> Self {
+        Self {
+            config: DeviceConfig::default(),
+            generator: SyntheticCsi::new(Scenario::new(Scene::Breathing { bpm: DEMO_BREATHING_BPM })),
+            clock: DemoClock::Wall { started: Instant::now() },
+            scenario_start: Duration::zero(),
+            sequence: 0,
+            seeded: false,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// A breathing subject in the room drawn from `seed`, on a virtual clock.
+    pub fn seeded(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let scenario = Scenario { seed: Some(rng.gen()), ..Scenario::new(Scene::Breathing { bpm: DEMO_BREATHING_BPM }) };
+        Self {
+            generator: SyntheticCsi::new(scenario),
+            clock: DemoClock::Virtual {
+                start: DateTime::from_timestamp(VIRTUAL_EPOCH_S, 0).expect("valid epoch"),
+                elapsed: Duration::zero(),
+            },
+            seeded: true,
+            rng,
+            ..Self::new()
+        }
+    }
+
+    /// Time since the source started.
+    pub fn elapsed(&self) -> Duration {
+        match self.clock {
+            DemoClock::Wall { started } => Duration::from_std(started.elapsed()).unwrap_or_default(),
+            DemoClock::Virtual { elapsed, .. } => elapsed,
+        }
+    }
+
+    /// Current time on the source's clock.
+    pub fn now(&self) -> DateTime<Utc> {
+        match self.clock {
+            DemoClock::Wall { .. } => Utc::now(),
+            DemoClock::Virtual { start, elapsed } => start + elapsed,
+        }
+    }
+
+    /// Starts `scenario` from its beginning. A seeded source gives scenarios without a
+    /// seed one of its own, so they stay reproducible.
+    pub fn set_scenario(&mut self, scenario: &Scenario) {
+        let mut scenario = scenario.clone();
+        if self.seeded && scenario.seed.is_none() {
+            scenario.seed = Some(self.rng.gen());
+        }
+        self.generator = SyntheticCsi::new(scenario);
+        self.scenario_start = self.elapsed();
+    }
+
+    pub fn next_measurement(&mut self) -> CsiMeasurement {
+        self.sequence += 1;
+        let (elapsed, timestamp) = (self.elapsed(), self.now());
+        let scenario_s = (elapsed - self.scenario_start).num_microseconds().unwrap_or(0) as f64 / 1e6;
+        let (channel, bandwidth) = (self.config.channel, self.config.bandwidth);
+        let mut measurement = self.generator.measurement(self.sequence, timestamp, scenario_s, channel, bandwidth);
+        measurement.device_timestamp_us = elapsed.num_microseconds().map(|us| us as u64);
+
+        // Channels differ in link margin and in how many neighbouring stations are heard.
+        let busy = (channel as i8 * 3) % 4;
+        measurement.rssi = measurement.rssi.saturating_sub(busy * 2);
+        measurement.noise_floor = measurement.noise_floor.saturating_add(busy);
+        let station = self.rng.gen_range(0..1 + busy as u8);
+        measurement.transmitter = Some(format!("02:00:00:00:{:02x}:{:02x}", channel, station));
+
+        if let DemoClock::Virtual { elapsed, .. } = &mut self.clock {
+            let interval_us = 1e6 / self.config.requested_rate_hz().max(0.1) as f64;
+            *elapsed += Duration::microseconds(interval_us.round() as i64);
+        }
+        measurement
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::codec;
+
+    fn frames(source: &mut DemoSource, count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|_| codec::encode_binary(&source.next_measurement()).unwrap()).collect()
+    }
+
+    #[test]
+    fn seeded_sources_repeat_byte_for_byte() {
+        let (mut a, mut b) = (DemoSource::seeded(42), DemoSource::seeded(42));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(frames(&mut a, 50), frames(&mut b, 50));
+        assert_ne!(frames(&mut DemoSource::seeded(42), 10), frames(&mut DemoSource::seeded(43), 10));
+
+        let json = |source: &mut DemoSource| serde_json::to_vec(&source.next_measurement()).unwrap();
+        let scenario = Scenario::parse("empty:1,walking:1").unwrap();
+        a.set_scenario(&scenario);
+        b.set_scenario(&scenario);
+        for _ in 0..30 {
+            assert_eq!(json(&mut a), json(&mut b));
+        }
+    }
+
+    #[test]
+    fn virtual_clock_follows_the_configured_rate() {
+        let mut source = DemoSource::seeded(1);
+        source.config.collection_interval_ms = 50;
+        let first = source.next_measurement();
+        let second = source.next_measurement();
+        assert_eq!(first.timestamp, DateTime::from_timestamp(VIRTUAL_EPOCH_S, 0).unwrap());
+        assert_eq!(second.timestamp - first.timestamp, Duration::milliseconds(50));
+        assert_eq!((first.sequence, second.sequence), (Some(1), Some(2)));
+        assert_eq!(second.device_timestamp_us, Some(50_000));
+        assert_eq!(source.elapsed(), Duration::milliseconds(100));
+    }
+}
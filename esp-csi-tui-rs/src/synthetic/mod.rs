@@ -0,0 +1,312 @@
+//! Synthetic CSI from a multipath channel model, for demo devices and DSP tests.
+//!
+//! Each frame sums the propagation paths of a room, and of a person when the
+//! [`Scenario`] has one, at every subcarrier frequency. It then applies the receiver:
+//! noise at the given SNR, a common phase rotation from the residual CFO, a phase
+//! slope from the symbol timing offset, a stepped AGC gain and int8 quantisation, as
+//! the ESP32 reports it. The same seed gives the same room and noise.
+
+pub mod demo;
+pub mod scenario;
+
+pub use demo::DemoSource;
+pub use scenario::{Scenario, Scene};
+
+use crate::models::{ComplexNumber, CsiMeasurement};
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+const SUBCARRIER_SPACING_HZ: f64 = 312_500.0;
+/// RMS amplitude, in int8 units, the AGC aims for.
+const AGC_TARGET: f64 = 24.0;
+/// Thermal noise floor the receiver reports.
+const NOISE_FLOOR_DBM: f64 = -95.0;
+/// Number of reflections in a generated room besides the line of sight.
+const REFLECTIONS: usize = 6;
+
+/// Centre frequency of a 2.4 GHz channel.
+pub fn carrier_hz(channel: u8) -> f64 {
+    if channel == 14 { 2484e6 } else { 2407e6 + 5e6 * channel as f64 }
+}
+
+/// Subcarrier indices reported at a bandwidth: HT20 without DC, HT40 without the
+/// three centre subcarriers.
+pub fn subcarrier_indices(bandwidth: u16) -> Vec<i32> {
+    let (edge, dc) = if bandwidth >= 40 { (58, 1) } else { (26, 0) };
+    (-edge..=edge).filter(|k: &i32| k.abs() > dc).collect()
+}
+
+/// One propagation path from transmitter to receiver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub delay_ns: f64,
+    /// Amplitude relative to the line of sight.
+    pub gain: f64,
+    /// Phase picked up on reflection, in radians.
+    pub phase: f64,
+    /// Constant Doppler shift from a steadily moving reflector.
+    pub doppler_hz: f64,
+    /// Periodic changes of the path length as (amplitude in metres, frequency in Hz),
+    /// from a reflector moving back and forth.
+    pub sway: Vec<(f64, f64)>,
+}
+
+impl Path {
+    pub fn fixed(delay_ns: f64, gain: f64, phase: f64) -> Self {
+        Self { delay_ns, gain, phase, doppler_hz: 0.0, sway: Vec::new() }
+    }
+
+    /// Delay at `t` seconds, including the sway.
+    fn delay_s(&self, t: f64) -> f64 {
+        let length: f64 = self.sway.iter().map(|&(amplitude, hz)| amplitude * (2.0 * PI * hz * t).sin()).sum();
+        self.delay_ns * 1e-9 + length / SPEED_OF_LIGHT
+    }
+}
+
+/// Generates CSI frames for a [`Scenario`]; see the module docs.
+pub struct SyntheticCsi {
+    scenario: Scenario,
+    room: Vec<Path>,
+    /// Delay and reflection phase of the path via the person.
+    body: (f64, f64),
+    /// Received power of the line of sight.
+    rssi_dbm: f64,
+    agc_gain_db: Option<f64>,
+    rng: StdRng,
+}
+
+impl SyntheticCsi {
+    /// A room of a line of sight and a few reflections, drawn from the scenario's seed.
+    pub fn new(scenario: Scenario) -> Self {
+        let mut rng = StdRng::seed_from_u64(scenario.seed.unwrap_or_else(rand::random));
+        let los_ns = rng.gen_range(10.0..20.0);
+        let mut room = vec![Path::fixed(los_ns, 1.0, rng.gen_range(0.0..2.0 * PI))];
+        for _ in 0..REFLECTIONS {
+            let extra_ns: f64 = rng.gen_range(5.0..80.0);
+            let gain = 0.6 * (-extra_ns / 40.0).exp() * rng.gen_range(0.5..1.0);
+            room.push(Path::fixed(los_ns + extra_ns, gain, rng.gen_range(0.0..2.0 * PI)));
+        }
+        let body = (los_ns + rng.gen_range(5.0..25.0), rng.gen_range(0.0..2.0 * PI));
+        let rssi_dbm = rng.gen_range(-55.0..-40.0);
+        Self { scenario, room, body, rssi_dbm, agc_gain_db: None, rng }
+    }
+
+    /// Uses the given static paths instead of a generated room.
+    #[cfg(test)]
+    pub fn with_room(scenario: Scenario, room: Vec<Path>) -> Self {
+        Self { room, ..Self::new(scenario) }
+    }
+
+    /// Static paths plus those via the person in `scene`.
+    pub fn paths(&self, scene: Scene) -> Vec<Path> {
+        let (delay_ns, phase) = self.body;
+        let mut paths = self.room.clone();
+        match scene {
+            Scene::EmptyRoom => {}
+            // Chest motion of a few millimetres, doubled on the way there and back.
+            Scene::Breathing { bpm } => paths.push(Path { sway: vec![(0.008, bpm / 60.0)], ..Path::fixed(delay_ns, 0.3, phase) }),
+            Scene::Walking { speed_mps } => {
+                // Up and down a 3 m stretch at `speed_mps` at its fastest, with the
+                // limbs swinging about the torso.
+                let stride = (1.5, speed_mps / (2.0 * PI * 1.5));
+                paths.push(Path { sway: vec![stride], ..Path::fixed(delay_ns, 0.4, phase) });
+                paths.push(Path { sway: vec![stride, (0.15, 1.8)], ..Path::fixed(delay_ns + 2.0, 0.15, phase + 1.0) });
+            }
+        }
+        paths
+    }
+
+    /// The frame received `elapsed_s` into the scenario on `channel`.
+    pub fn measurement(
+        &mut self,
+        sequence: u32,
+        timestamp: DateTime<Utc>,
+        elapsed_s: f64,
+        channel: u8,
+        bandwidth: u16,
+    ) -> CsiMeasurement {
+        let impairments = self.scenario.impairments;
+        let paths = self.paths(self.scenario.scene_at(elapsed_s));
+        let carrier = carrier_hz(channel);
+        let sto_s = self.rng.gen_range(-1.0..=1.0) * impairments.sto_ns * 1e-9;
+        let common_phase = 2.0 * PI * impairments.cfo_hz * elapsed_s;
+
+        let mut response: Vec<(f64, f64)> = subcarrier_indices(bandwidth)
+            .into_iter()
+            .map(|k| {
+                let offset = k as f64 * SUBCARRIER_SPACING_HZ;
+                let (mut re, mut im) = (0.0, 0.0);
+                for path in &paths {
+                    let phase = path.phase - 2.0 * PI * (carrier + offset) * path.delay_s(elapsed_s)
+                        + 2.0 * PI * path.doppler_hz * elapsed_s;
+                    re += path.gain * phase.cos();
+                    im += path.gain * phase.sin();
+                }
+                let rotation = common_phase - 2.0 * PI * offset * sto_s;
+                (re * rotation.cos() - im * rotation.sin(), re * rotation.sin() + im * rotation.cos())
+            })
+            .collect();
+
+        let power = response.iter().map(|(re, im)| re * re + im * im).sum::<f64>() / response.len().max(1) as f64;
+        let sigma = (power / 10f64.powf(impairments.snr_db / 10.0) / 2.0).sqrt();
+        for (re, im) in response.iter_mut() {
+            *re += sigma * self.gaussian();
+            *im += sigma * self.gaussian();
+        }
+
+        let power_db = 10.0 * power.max(f64::MIN_POSITIVE).log10();
+        let gain_db = self.agc(power_db, impairments.agc_step_db);
+        let scale = 10f64.powf(gain_db / 20.0);
+        let quantise = |value: f64| (value * scale).round().clamp(-128.0, 127.0) as f32;
+        let subcarrier_data = response.iter().map(|&(re, im)| ComplexNumber::new(quantise(re), quantise(im))).collect();
+
+        let rssi = self.rssi_dbm + power_db + self.gaussian();
+        let noise_floor = NOISE_FLOOR_DBM + self.gaussian();
+        CsiMeasurement {
+            timestamp,
+            channel,
+            bandwidth,
+            rssi: rssi.round().clamp(-128.0, 0.0) as i8,
+            noise_floor: noise_floor.round() as i8,
+            subcarrier_data,
+            sequence: Some(sequence),
+            device_timestamp_us: Some((elapsed_s * 1e6) as u64),
+            device_id: None,
+            transmitter: None,
+        }
+    }
+
+    /// Gain bringing `power_db` to the target, changed only by whole steps once the
+    /// power has drifted more than a step away.
+    fn agc(&mut self, power_db: f64, step_db: f64) -> f64 {
+        let ideal = 20.0 * AGC_TARGET.log10() - power_db;
+        let gain = match self.agc_gain_db {
+            Some(gain) if step_db <= 0.0 || (ideal - gain).abs() <= step_db => gain,
+            _ if step_db > 0.0 => (ideal / step_db).round() * step_db,
+            _ => ideal,
+        };
+        self.agc_gain_db = Some(gain);
+        gain
+    }
+
+    /// Standard normal sample (Box-Muller).
+    fn gaussian(&mut self) -> f64 {
+        let u: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+        let v: f64 = self.rng.gen_range(0.0..1.0);
+        (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+    }
+}
+
+/// `seconds` of frames at `rate_hz` on channel 6, HT20, starting at `start`.
+#[cfg(test)]
+pub fn capture(scenario: Scenario, start: DateTime<Utc>, seconds: f64, rate_hz: f64) -> Vec<CsiMeasurement> {
+    let mut generator = SyntheticCsi::new(scenario);
+    (0..(seconds * rate_hz) as u32)
+        .map(|i| {
+            let elapsed_s = i as f64 / rate_hz;
+            let timestamp = start + chrono::Duration::microseconds((elapsed_s * 1e6) as i64);
+            generator.measurement(i, timestamp, elapsed_s, 6, 20)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::scenario::Impairments;
+    use crate::detection::motion::MotionFeatures;
+
+    fn start() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn amplitudes(m: &CsiMeasurement) -> Vec<f32> {
+        m.subcarrier_data.iter().map(|c| c.magnitude()).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_frames() {
+        let scenario = Scenario::new(Scene::Walking { speed_mps: 1.0 }).with_seed(7);
+        let a = capture(scenario.clone(), start(), 2.0, 10.0);
+        let b = capture(scenario.clone(), start(), 2.0, 10.0);
+        let c = capture(scenario.with_seed(8), start(), 2.0, 10.0);
+        assert_eq!(a.iter().map(amplitudes).collect::<Vec<_>>(), b.iter().map(amplitudes).collect::<Vec<_>>());
+        assert_ne!(a.iter().map(amplitudes).collect::<Vec<_>>(), c.iter().map(amplitudes).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn frames_look_like_esp32_csi() {
+        let mut generator = SyntheticCsi::new(Scenario::new(Scene::EmptyRoom).with_seed(1));
+        for (bandwidth, subcarriers) in [(20, 52), (40, 114)] {
+            let m = generator.measurement(0, start(), 0.0, 11, bandwidth);
+            assert_eq!((m.channel, m.bandwidth, m.subcarrier_data.len()), (11, bandwidth, subcarriers));
+            for c in &m.subcarrier_data {
+                assert!(c.real.fract() == 0.0 && c.imag.fract() == 0.0 && c.real.abs() <= 128.0);
+            }
+            let rms = (m.subcarrier_data.iter().map(|c| c.magnitude().powi(2)).sum::<f32>() / subcarriers as f32).sqrt();
+            assert!((rms - AGC_TARGET as f32).abs() < 4.0, "rms {}", rms);
+            assert!((-70..=-30).contains(&m.rssi) && m.noise_floor < -85);
+        }
+    }
+
+    #[test]
+    fn path_delays_shape_the_spectrum() {
+        // Two equal paths 100 ns apart cancel every 10 MHz, i.e. every 32 subcarriers.
+        let room = vec![Path::fixed(0.0, 1.0, 0.0), Path::fixed(100.0, 1.0, 0.0)];
+        let scenario = Scenario::new(Scene::EmptyRoom).with_impairments(Impairments::ideal());
+        let m = SyntheticCsi::with_room(scenario, room).measurement(0, start(), 0.0, 6, 20);
+        let a = amplitudes(&m);
+        let indices = subcarrier_indices(20);
+        let at = |k: i32| a[indices.iter().position(|&i| i == k).unwrap()];
+        for k in -26..=-6 {
+            assert!((at(k) - at(k + 32)).abs() <= 1.5, "subcarrier {}: {} vs {}", k, at(k), at(k + 32));
+        }
+        let (min, max) = a.iter().fold((f32::MAX, 0f32), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+        assert!(min < 0.2 * max, "no notch: {}..{}", min, max);
+    }
+
+    #[test]
+    fn doppler_rotates_the_phase() {
+        let room = vec![Path { doppler_hz: 5.0, ..Path::fixed(0.0, 1.0, 0.0) }];
+        let scenario = Scenario::new(Scene::EmptyRoom).with_impairments(Impairments::ideal());
+        let mut generator = SyntheticCsi::with_room(scenario, room);
+        let phase = |m: &CsiMeasurement| m.subcarrier_data[0].imag.atan2(m.subcarrier_data[0].real);
+        let first = generator.measurement(0, start(), 0.0, 6, 20);
+        let second = generator.measurement(1, start(), 0.01, 6, 20);
+        let turned = (phase(&second) - phase(&first)).rem_euclid(2.0 * std::f32::consts::PI);
+        assert!((turned - 0.1 * std::f32::consts::PI).abs() < 0.05, "turned {} rad", turned);
+    }
+
+    #[test]
+    fn motion_stands_out_from_an_empty_room() {
+        let features = |scene| {
+            let frames = capture(Scenario::new(scene).with_seed(3), start(), 5.0, 20.0);
+            MotionFeatures::from_window(&frames).unwrap()
+        };
+        let empty = features(Scene::EmptyRoom);
+        let walking = features(Scene::Walking { speed_mps: 1.0 });
+        assert!(walking.variance > 10.0 * empty.variance, "{:?} vs {:?}", walking, empty);
+        assert!(walking.decorrelation > 5.0 * empty.decorrelation, "{:?} vs {:?}", walking, empty);
+    }
+
+    #[test]
+    fn scripts_loop_through_their_scenes() {
+        let scenario = Scenario::parse("empty:10,walking:5s,breathing:15 bpm=12 snr=30 seed=4").unwrap();
+        assert_eq!(scenario.seed, Some(4));
+        assert_eq!(scenario.impairments.snr_db, 30.0);
+        assert_eq!(scenario.scene_at(3.0), Scene::EmptyRoom);
+        assert_eq!(scenario.scene_at(12.0), Scene::Walking { speed_mps: 1.0 });
+        assert_eq!(scenario.scene_at(29.0), Scene::Breathing { bpm: 12.0 });
+        assert_eq!(scenario.scene_at(31.0), Scene::EmptyRoom);
+        assert_eq!(scenario.label(), "empty 10s > walking 5s > breathing 15s");
+
+        assert_eq!(Scenario::parse("walking").unwrap().scene_at(1e6), Scene::Walking { speed_mps: 1.0 });
+        assert!(Scenario::parse("empty,walking:5").is_err());
+        assert!(Scenario::parse("dancing").is_err());
+        assert!(Scenario::parse("breathing bpm=100").is_err());
+        assert!(Scenario::parse("").is_err());
+    }
+}
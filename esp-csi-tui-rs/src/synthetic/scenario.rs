@@ -0,0 +1,177 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// What the simulated person in the room is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Scene {
+    EmptyRoom,
+    /// Walking back and forth across the room.
+    Walking { speed_mps: f64 },
+    /// Sitting still, breathing.
+    Breathing { bpm: f64 },
+}
+
+impl Scene {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scene::EmptyRoom => "empty",
+            Scene::Walking { .. } => "walking",
+            Scene::Breathing { .. } => "breathing",
+        }
+    }
+}
+
+/// One scene held for a while; `None` holds it for good.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    pub scene: Scene,
+    pub seconds: Option<f64>,
+}
+
+/// Receiver and estimation impairments applied on top of the multipath channel.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Impairments {
+    /// Ratio of the mean channel power to the noise added to each subcarrier.
+    pub snr_db: f64,
+    /// Residual carrier frequency offset, rotating all subcarriers together.
+    pub cfo_hz: f64,
+    /// Largest symbol timing offset, drawn anew for each frame; tilts the phase
+    /// across subcarriers.
+    pub sto_ns: f64,
+    /// AGC gain step. The gain moves in whole steps to keep the received power near
+    /// its target, so amplitudes jump when the channel changes; 0 keeps the gain set
+    /// by the first frame.
+    pub agc_step_db: f64,
+}
+
+impl Default for Impairments {
+    fn default() -> Self {
+        Self { snr_db: 25.0, cfo_hz: 20.0, sto_ns: 25.0, agc_step_db: 1.0 }
+    }
+}
+
+impl Impairments {
+    /// No noise, offsets or AGC steps: only the channel and int8 quantisation.
+    #[cfg(test)]
+    pub fn ideal() -> Self {
+        Self { snr_db: f64::INFINITY, cfo_hz: 0.0, sto_ns: 0.0, agc_step_db: 0.0 }
+    }
+}
+
+/// A script of scenes, looped when every segment has a duration, with the
+/// impairments and the seed of the room and noise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    pub segments: Vec<Segment>,
+    pub impairments: Impairments,
+    /// `None` picks a random room.
+    pub seed: Option<u64>,
+}
+
+impl Scenario {
+    pub fn new(scene: Scene) -> Self {
+        Self { segments: vec![Segment { scene, seconds: None }], impairments: Impairments::default(), seed: None }
+    }
+
+    #[cfg(test)]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_impairments(mut self, impairments: Impairments) -> Self {
+        self.impairments = impairments;
+        self
+    }
+
+    /// Parses `<scene>[:<seconds>][,<scene>:<seconds>...]` followed by optional
+    /// `bpm=<n>`, `speed=<m/s>`, `snr=<dB>`, `cfo=<Hz>`, `sto=<ns>`, `agc=<dB>` and
+    /// `seed=<n>`; scenes are `empty`, `walking` and `breathing`.
+    pub fn parse(args: &str) -> Result<Self> {
+        let mut words = args.split_whitespace();
+        let script = words
+            .next()
+            .ok_or_else(|| anyhow!("A scenario needs scenes, e.g. empty:10,walking:20,breathing:30"))?;
+        let (mut bpm, mut speed_mps) = (15.0, 1.0);
+        let mut impairments = Impairments::default();
+        let mut seed = None;
+        for arg in words {
+            let (key, value) = arg
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value, got '{}'", arg))?;
+            let number = || value.parse::<f64>().map_err(|_| anyhow!("Invalid {} '{}'", key, value));
+            match key {
+                "bpm" => bpm = number()?,
+                "speed" => speed_mps = number()?,
+                "snr" => impairments.snr_db = number()?,
+                "cfo" => impairments.cfo_hz = number()?,
+                "sto" => impairments.sto_ns = number()?.abs(),
+                "agc" => impairments.agc_step_db = number()?.abs(),
+                "seed" => seed = Some(value.parse().map_err(|_| anyhow!("Invalid seed '{}'", value))?),
+                _ => return Err(anyhow!("Unknown scenario setting '{}'", key)),
+            }
+        }
+        if !(3.0..=60.0).contains(&bpm) {
+            return Err(anyhow!("Breathing rate must be 3-60 bpm, got {}", bpm));
+        }
+        if !(0.1..=3.0).contains(&speed_mps) {
+            return Err(anyhow!("Walking speed must be 0.1-3 m/s, got {}", speed_mps));
+        }
+
+        let parts: Vec<&str> = script.split(',').collect();
+        let mut segments = Vec::new();
+        for part in &parts {
+            let (name, seconds) = match part.split_once(':') {
+                Some((name, seconds)) => {
+                    let seconds: f64 = seconds
+                        .trim_end_matches('s')
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid duration in '{}'", part))?;
+                    if seconds <= 0.0 {
+                        return Err(anyhow!("Scene durations must be positive, got '{}'", part));
+                    }
+                    (name, Some(seconds))
+                }
+                None if parts.len() == 1 => (*part, None),
+                None => return Err(anyhow!("Every scene of a script needs a duration, e.g. {}:10", part)),
+            };
+            let scene = match name {
+                "empty" => Scene::EmptyRoom,
+                "walking" => Scene::Walking { speed_mps },
+                "breathing" => Scene::Breathing { bpm },
+                _ => return Err(anyhow!("Unknown scene '{}' (empty, walking or breathing)", name)),
+            };
+            segments.push(Segment { scene, seconds });
+        }
+        Ok(Self { segments, impairments, seed })
+    }
+
+    /// Scene at `elapsed_s` into the scenario.
+    pub fn scene_at(&self, elapsed_s: f64) -> Scene {
+        let total: Option<f64> = self.segments.iter().map(|s| s.seconds).sum();
+        let mut t = match total {
+            Some(total) if total > 0.0 => elapsed_s.rem_euclid(total),
+            _ => elapsed_s,
+        };
+        for segment in &self.segments {
+            match segment.seconds {
+                Some(seconds) if t >= seconds => t -= seconds,
+                _ => return segment.scene,
+            }
+        }
+        self.segments.last().map_or(Scene::EmptyRoom, |s| s.scene)
+    }
+
+    /// Short description for status lines, e.g. `empty 10s > walking 20s`.
+    pub fn label(&self) -> String {
+        self.segments
+            .iter()
+            .map(|s| match s.seconds {
+                Some(seconds) => format!("{} {}s", s.scene.name(), seconds),
+                None => s.scene.name().to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
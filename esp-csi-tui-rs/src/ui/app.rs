@@ -1,18 +1,94 @@
-use crate::models::AppState;
+use crate::models::{AppState, CsiMeasurement, DeviceCommand, DeviceConfig, WifiMode};
 use crate::ui::{setup_terminal, cleanup_terminal};
+use crate::ui::FormAction;
+use crate::ui::csi_form::CsiForm;
+use crate::ui::wifi_form::WifiForm;
 use crate::ui::components::{draw_header, draw_footer};
-use crate::device::EspClient;
+use crate::capture::{CaptureAction, CapturePlan, CaptureSchedule};
+use crate::survey::{Survey, SurveyAction, SurveyPlan};
+use crate::commands::{CommandExecutor, Target};
+use crate::calibration::{BaselineProfile, CalibrationSession};
+use crate::classification::{self, Classifier, FeatureConfig, FeatureExtractor, Prediction, FEATURE_NAMES, MODEL_DIR, TRAINING_DIR};
+use crate::detection::{DetectorConfig, MotionDetector};
+use crate::device::buffer::HISTORY_FRAMES;
+use crate::device::{DeviceManager, DeviceStatus};
+use crate::labelling::{activity_for_key, LabelTrack, ACTIVITIES};
+use crate::dsp::align::{self, DeviceAlignment};
+use crate::dsp::pca::StreamingPca;
+use crate::dsp::{Resampler, SpectrogramConfig, SpectrogramProcessor};
+use crate::storage::baseline_storage::{BaselineStorage, BASELINE_DIR};
+use crate::storage::csv_storage::CsvStorage;
+use crate::storage::dataset_export::DatasetExporter;
+use crate::storage::label_storage::LabelStorage;
+use crate::storage::rrd_storage::RrdStorage;
+use crate::storage::session_storage::{SessionDevice, SessionStorage};
+use crate::vitals::{BreathingConfig, BreathingEstimator};
 use anyhow::Result;
 use crossterm::event::{self, Event, KeyCode};
 use ratatui::prelude::*;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
+const NUM_TABS: usize = 13;
+const LABELS_TAB: usize = 8;
+const DEVICES_TAB: usize = 10;
+const DIAGNOSTICS_TAB: usize = 11;
+const SURVEY_TAB: usize = 12;
+/// Latest frames per device aligned for the Devices tab.
+const ALIGNMENT_WINDOW: usize = 200;
+/// The Devices tab alignment is recomputed every this many received frames.
+const ALIGNMENT_REFRESH: usize = 50;
+/// Frames in the sliding PCA window (~20 s at 10 Hz).
+const PCA_WINDOW: usize = 200;
+const PCA_COMPONENTS: usize = 5;
+/// Components are re-decomposed every this many frames; the covariance updates per frame.
+const PCA_REFRESH: usize = 10;
+/// Sparkline glyphs from lowest to highest value for component time series.
+const SPARK_GLYPHS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Length of the guided empty-room recording started with 'b'.
+const CALIBRATION_DURATION: Duration = Duration::from_secs(30);
+/// Recent measurements compared against the baseline profile in the Baseline tab.
+const BASELINE_COMPARE_WINDOW: usize = 100;
+/// Measurements fed to the spectrogram tab and its export (~25 s at 10 Hz).
+const SPECTROGRAM_HISTORY: usize = 256;
+/// Shading from lowest to highest relative power in the text spectrogram.
+const SPECTROGRAM_SHADES: &[char] = &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+/// A channel survey of one device, with what to restore once it ends.
+struct SurveyRun {
+    survey: Survey,
+    device: String,
+    original: DeviceConfig,
+    was_collecting: bool,
+}
+
 pub struct App {
     state: AppState,
     should_quit: bool,
     last_update: Instant,
-    esp_client: Option<EspClient>,
+    devices: DeviceManager,
+    executor: CommandExecutor,
+    /// Command palette input while it is open.
+    palette: Option<String>,
+    wifi_form: Option<WifiForm>,
+    csi_form: Option<CsiForm>,
+    /// Timed or scheduled capture started from the palette.
+    capture: Option<CaptureSchedule>,
+    /// Device positions when the running capture started.
+    capture_start: Vec<usize>,
+    /// Running or last finished channel survey.
+    survey: Option<SurveyRun>,
     status_message: String,
+    detector: MotionDetector,
+    calibration: Option<CalibrationSession>,
+    baseline: Option<BaselineProfile>,
+    classifier: Option<Box<dyn Classifier>>,
+    activity: Option<Prediction>,
+    labelling: bool,
+    labels: LabelTrack,
+    pca: StreamingPca,
+    /// Clock alignment of each device's latest frames, for the Devices tab.
+    alignment: Vec<DeviceAlignment>,
 }
 
 impl App {
@@ -21,136 +97,846 @@ impl App {
             state: AppState::default(),
             should_quit: false,
             last_update: Instant::now(),
-            esp_client: None,
+            devices: DeviceManager::new(),
+            executor: CommandExecutor::new(),
+            palette: None,
+            wifi_form: None,
+            csi_form: None,
+            capture: None,
+            capture_start: Vec::new(),
+            survey: None,
             status_message: "Ready. Press 'c' to connect (or 'd' for demo mode)".to_string(),
+            detector: MotionDetector::new(DetectorConfig::default()),
+            calibration: None,
+            baseline: None,
+            classifier: classification::load_model(Path::new(MODEL_DIR)).unwrap_or_else(|e| {
+                tracing::warn!("Failed to load activity model: {}", e);
+                None
+            }),
+            activity: None,
+            labelling: false,
+            labels: LabelTrack::default(),
+            pca: StreamingPca::new(PCA_WINDOW, PCA_COMPONENTS),
+            alignment: Vec::new(),
         }
     }
 
     pub fn handle_key_event(&mut self, key_code: KeyCode) {
+        if self.palette.is_some() {
+            self.handle_palette_key(key_code);
+            return;
+        }
+        if self.wifi_form.is_some() {
+            self.handle_wifi_form_key(key_code);
+            return;
+        }
+        if self.csi_form.is_some() {
+            self.handle_csi_form_key(key_code);
+            return;
+        }
         match key_code {
             KeyCode::Char('q') => self.should_quit = true,
-            KeyCode::Char('c') => self.handle_connect(),
-            KeyCode::Char('d') => self.handle_demo_mode(),
+            KeyCode::Char(':') => self.palette = Some(String::new()),
+            KeyCode::Char('c') => self.handle_add_device(false),
+            KeyCode::Char('d') => self.handle_add_device(true),
+            KeyCode::Char('r') => self.handle_remove_device(),
+            KeyCode::Up => self.select_device(-1),
+            KeyCode::Down => self.select_device(1),
             KeyCode::Char('s') => self.handle_start(),
             KeyCode::Char('e') => self.handle_stop(),
-            KeyCode::Char('t') => self.state.current_tab = (self.state.current_tab + 1) % 3,
+            KeyCode::Char('i') => self.handle_query(),
+            KeyCode::Char('f') => {
+                let info = self.devices.selected().and_then(|device| device.info.as_ref());
+                self.wifi_form = Some(WifiForm::new(self.device_config(), info));
+            }
+            KeyCode::Char('a') => self.csi_form = Some(CsiForm::new(&self.device_config().csi)),
+            KeyCode::Char('x') => self.handle_export_spectrogram(),
+            KeyCode::Char('b') => self.handle_calibrate(),
+            KeyCode::Char('m') => self.handle_model(),
+            KeyCode::Char('l') => self.handle_labelling(),
+            KeyCode::Char('w') => self.handle_save_recording(),
+            KeyCode::Char('o') => self.handle_stream_recording(),
+            KeyCode::Char('n') => self.handle_export_dataset(),
+            KeyCode::Char('p') => self.handle_export_pca(),
+            KeyCode::Char('v') if self.survey_running() => self.finish_survey(),
+            KeyCode::Char('v') => self.handle_survey(""),
+            KeyCode::Char(c @ '0'..='9') if self.labelling => self.handle_label_key(c),
+            KeyCode::Char('+') => self.scale_detector_thresholds(1.25),
+            KeyCode::Char('-') => self.scale_detector_thresholds(0.8),
+            KeyCode::Char('t') => self.state.current_tab = (self.state.current_tab + 1) % NUM_TABS,
             KeyCode::Esc => self.state.current_tab = 0,
             _ => {}
         }
     }
 
-    fn handle_demo_mode(&mut self) {
-        tracing::info!("Starting demo mode with mock CSI data...");
-        self.status_message = "Demo mode: Generating mock CSI data...".to_string();
-        
-        let mut client = EspClient::new(
-            self.state.device_config.port.clone(),
-            self.state.device_config.baud_rate
-        );
-        
-        client.enable_demo_mode();
-        
-        match client.connect() {
+    fn handle_palette_key(&mut self, key_code: KeyCode) {
+        let Some(input) = self.palette.as_mut() else { return };
+        match key_code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => {
+                let line = self.palette.take().unwrap_or_default();
+                if line.trim().is_empty() {
+                    return;
+                }
+                let line = line.trim();
+                let (word, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                match word {
+                    "capture" => return self.handle_capture(args),
+                    "survey" => {
+                        match args.trim() {
+                            "stop" if self.survey_running() => self.finish_survey(),
+                            "stop" => self.status_message = "No survey running".to_string(),
+                            args => self.handle_survey(args),
+                        }
+                        return;
+                    }
+                    _ => {}
+                }
+                self.status_message = match self.executor.execute_line(&self.devices, line) {
+                    Ok(request) => format!("Sent '{}' to {} device(s)", line, request.devices),
+                    Err(e) => format!("Command failed: {}", e),
+                };
+            }
+            KeyCode::Esc => self.palette = None,
+            _ => {}
+        }
+    }
+
+    /// Applies the Wi-Fi form to the selected device, or to the template for new
+    /// devices when none is selected. Invalid settings keep the form open.
+    fn handle_wifi_form_key(&mut self, key_code: KeyCode) {
+        let Some(form) = self.wifi_form.as_mut() else { return };
+        match form.handle_key(key_code) {
+            FormAction::Continue => {}
+            FormAction::Cancel => self.wifi_form = None,
+            FormAction::Submit => {
+                let Some(form) = &self.wifi_form else { return };
+                let config = match form.to_config(self.device_config()) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        self.status_message = format!("Invalid Wi-Fi settings: {}", e);
+                        return;
+                    }
+                };
+                self.wifi_form = None;
+                let Some(device) = self.devices.selected() else {
+                    self.status_message = format!("Wi-Fi mode {} set for new devices", config.wifi.mode.as_str());
+                    self.state.device_config = config;
+                    return;
+                };
+                let id = device.id.clone();
+                let mode = config.wifi.mode;
+                let traffic = config.traffic;
+                let result = self
+                    .executor
+                    .execute(&self.devices, &Target::Selected, DeviceCommand::SetWifi(config.wifi.clone()))
+                    .and_then(|_| self.executor.execute(&self.devices, &Target::Selected, DeviceCommand::Configure(config)))
+                    .and_then(|_| self.executor.execute(&self.devices, &Target::Selected, DeviceCommand::SetTraffic(traffic)));
+                self.status_message = match result {
+                    Ok(_) => format!("Switching {} to {} mode...", id, mode.as_str()),
+                    Err(e) => format!("Wi-Fi change failed: {}", e),
+                };
+            }
+        }
+    }
+
+    /// Sends the CSI flags to the selected device, or keeps them for devices added next.
+    fn handle_csi_form_key(&mut self, key_code: KeyCode) {
+        let Some(form) = self.csi_form.as_mut() else { return };
+        match form.handle_key(key_code) {
+            FormAction::Continue => {}
+            FormAction::Cancel => self.csi_form = None,
+            FormAction::Submit => {
+                let Some(form) = self.csi_form.take() else { return };
+                let csi = form.config().clone();
+                let enabled = csi.enabled().join(", ");
+                if self.devices.selected().is_none() {
+                    self.state.device_config.csi = csi;
+                    self.status_message = format!("CSI flags for new devices: {}", enabled);
+                    return;
+                }
+                self.status_message = match self.executor.execute(&self.devices, &Target::Selected, DeviceCommand::SetCsi(csi)) {
+                    Ok(_) => format!("Setting CSI flags: {}", if enabled.is_empty() { "none" } else { &enabled }),
+                    Err(e) => format!("CSI flags failed: {}", e),
+                };
+            }
+        }
+    }
+
+    /// Adds a device using the config template, or a demo device generating mock CSI.
+    fn handle_add_device(&mut self, demo: bool) {
+        let config = self.state.device_config.clone();
+        let collecting = self.devices.any_collecting();
+        let id = self.devices.add(config.clone(), demo).id.clone();
+        self.status_message = format!("Added {} - connecting...", id);
+        self.on_device_selected();
+
+        // Join a running capture straight away.
+        if collecting {
+            let target = Target::Device(id);
+            let _ = self.executor.execute(&self.devices, &target, DeviceCommand::Configure(config));
+            let _ = self.executor.execute(&self.devices, &target, DeviceCommand::StartCollection);
+        }
+    }
+
+    fn handle_remove_device(&mut self) {
+        match self.devices.remove_selected() {
+            Some(device) => {
+                self.status_message = format!("Removed {} after {} measurements", device.id, device.measurements.total());
+                self.on_device_selected();
+            }
+            None => self.status_message = "No devices".to_string(),
+        }
+    }
+
+    fn select_device(&mut self, step: isize) {
+        if self.devices.select_next(step) {
+            self.on_device_selected();
+            if let Some(device) = self.devices.selected() {
+                self.status_message = format!("Viewing {}", device.id);
+            }
+        }
+    }
+
+    /// Analysis state follows the selected device, so restart it from that device's data.
+    fn on_device_selected(&mut self) {
+        self.calibration = None;
+        self.activity = None;
+        self.pca = StreamingPca::new(PCA_WINDOW, PCA_COMPONENTS);
+        for measurement in self.devices.selected_measurements().iter().rev().take(PCA_WINDOW).rev() {
+            self.pca.push(measurement);
+        }
+        self.pca.refresh();
+        self.detector = MotionDetector::new(self.detector.config.clone());
+        self.baseline = None;
+        self.state.presence = self.detector.state();
+        self.load_baseline();
+    }
+
+    /// Config of the selected device, or the template for the next device.
+    fn device_config(&self) -> &DeviceConfig {
+        self.devices.selected().map_or(&self.state.device_config, |d| &d.config)
+    }
+
+    fn handle_start(&mut self) {
+        if !self.devices.any_connected() {
+            self.status_message = "Not connected! Press 'c' to connect or 'd' for demo.".to_string();
+            return;
+        }
+
+        let mut result = Ok(());
+        for device in self.devices.devices() {
+            let target = Target::Device(device.id.clone());
+            result = result.and_then(|_| {
+                self.executor
+                    .execute(&self.devices, &target, DeviceCommand::Configure(device.config.clone()))
+                    .map(|_| ())
+            });
+        }
+        match result.and_then(|_| self.executor.execute(&self.devices, &Target::All, DeviceCommand::StartCollection)) {
             Ok(_) => {
-                self.state.is_connected = true;
-                self.esp_client = Some(client);
-                self.status_message = "Demo mode active!".to_string();
-                tracing::info!("Demo mode activated");
+                self.status_message = format!("Collecting data from {} device(s)...", self.devices.devices().len());
+                tracing::info!("Started data collection");
+                self.load_baseline();
             }
             Err(e) => {
-                self.status_message = format!("Demo mode failed: {}", e);
-                tracing::error!("Demo mode failed: {}", e);
+                self.status_message = format!("Failed to start: {}", e);
+                tracing::error!("Failed to start collection: {}", e);
             }
         }
     }
 
-    fn handle_connect(&mut self) {
-        tracing::info!("Attempting to connect to device...");
-        self.status_message = "Connecting to /dev/ttyUSB0...".to_string();
-        
-        let mut client = EspClient::new(
-            self.state.device_config.port.clone(),
-            self.state.device_config.baud_rate
-        );
-        
-        match client.connect() {
+    fn handle_stop(&mut self) {
+        match self.executor.execute(&self.devices, &Target::All, DeviceCommand::StopCollection) {
             Ok(_) => {
-                self.state.is_connected = true;
-                self.esp_client = Some(client);
-                self.status_message = "Connected successfully! Press 's' to start.".to_string();
-                tracing::info!("Connected to device");
+                self.status_message = "Data collection stopped".to_string();
+                tracing::info!("Stopped data collection");
             }
             Err(e) => {
-                self.status_message = format!("Connection failed: {}. Try 'd' for demo mode.", e);
-                tracing::error!("Connection failed: {}", e);
+                self.status_message = format!("Failed to stop: {}", e);
+                tracing::error!("Failed to stop: {}", e);
             }
         }
     }
 
-    fn handle_start(&mut self) {
-        if !self.state.is_connected {
-            self.status_message = "Not connected! Press 'c' to connect or 'd' for demo.".to_string();
+    fn spectrogram_config(&self) -> SpectrogramConfig {
+        SpectrogramConfig {
+            sample_rate_hz: 1000.0 / self.device_config().collection_interval_ms.max(1) as f32,
+            ..Default::default()
+        }
+    }
+
+    fn spectrogram_window(&self) -> &[CsiMeasurement] {
+        let measurements = self.devices.selected_measurements();
+        &measurements[measurements.len().saturating_sub(SPECTROGRAM_HISTORY)..]
+    }
+
+    fn handle_export_spectrogram(&mut self) {
+        let config = self.spectrogram_config();
+        let analysis = match SpectrogramProcessor::analyze(self.spectrogram_window(), &config) {
+            Ok(analysis) if !analysis.averaged.is_empty() => analysis,
+            Ok(_) => {
+                self.status_message = format!(
+                    "Not enough data for a spectrogram (need {} measurements)",
+                    config.window_size
+                );
+                return;
+            }
+            Err(e) => {
+                self.status_message = format!("Spectrogram failed: {}", e);
+                tracing::error!("Spectrogram failed: {}", e);
+                return;
+            }
+        };
+
+        let path = PathBuf::from(format!(
+            "spectrogram_{}.csv",
+            chrono::Local::now().format("%Y%m%d_%H%M%S")
+        ));
+        match CsvStorage::save_spectrogram(&analysis, &path) {
+            Ok(_) => self.status_message = format!("Spectrogram exported to {}", path.display()),
+            Err(e) => {
+                self.status_message = format!("Export failed: {}", e);
+                tracing::error!("Spectrogram export failed: {}", e);
+            }
+        }
+    }
+
+    /// Baselines are stored per receiver: its serial port, or "demo" for demo devices.
+    fn device_key(&self) -> String {
+        match self.devices.selected() {
+            Some(device) if device.demo => "demo".to_string(),
+            _ => self.device_config().port.clone(),
+        }
+    }
+
+    fn handle_calibrate(&mut self) {
+        if self.calibration.take().is_some() {
+            self.status_message = "Calibration cancelled".to_string();
+            return;
+        }
+        if !self.devices.selected().is_some_and(|d| d.is_collecting()) {
+            self.status_message = "Start collection ('s') before calibrating".to_string();
             return;
         }
 
-        if let Some(ref mut client) = self.esp_client {
-            match client.configure(&self.state.device_config) {
-                Ok(_) => {
-                    match client.start_collection() {
-                        Ok(_) => {
-                            self.state.is_collecting = true;
-                            self.status_message = "Collecting data...".to_string();
-                            tracing::info!("Started data collection");
-                        }
-                        Err(e) => {
-                            self.status_message = format!("Failed to start: {}", e);
-                            tracing::error!("Failed to start collection: {}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    self.status_message = format!("Failed to configure: {}", e);
-                    tracing::error!("Failed to configure: {}", e);
-                }
+        self.calibration = Some(CalibrationSession::new(self.selected_total(), CALIBRATION_DURATION));
+        self.status_message = format!(
+            "Calibrating for {} s - keep the room empty ('b' to cancel)",
+            CALIBRATION_DURATION.as_secs()
+        );
+        tracing::info!("Started baseline calibration");
+    }
+
+    fn update_calibration(&mut self) {
+        let Some(session) = self.calibration.as_ref() else {
+            return;
+        };
+        if !session.is_complete() {
+            self.status_message = format!(
+                "Calibrating: {} s left - keep the room empty",
+                session.remaining().as_secs() + 1
+            );
+            return;
+        }
+
+        let recorded = self.devices.selected().map_or(&[][..], |d| d.measurements.since(session.start_index));
+        self.calibration = None;
+        let profile = match BaselineProfile::compute(&self.device_key(), recorded, self.detector.config.window) {
+            Ok(profile) => profile,
+            Err(e) => {
+                self.status_message = format!("Calibration failed: {}", e);
+                tracing::error!("Calibration failed: {}", e);
+                return;
             }
+        };
+
+        self.status_message = match BaselineStorage::save(&profile, Path::new(BASELINE_DIR)) {
+            Ok(path) => format!("Baseline saved to {}", path.display()),
+            Err(e) => {
+                tracing::error!("Failed to save baseline: {}", e);
+                format!("Baseline captured but not saved: {}", e)
+            }
+        };
+        self.apply_baseline(profile);
+    }
+
+    /// Loads the stored baseline for the current device and channel, if any.
+    fn load_baseline(&mut self) {
+        if self.devices.selected().is_none() {
+            return;
+        }
+        let device = self.device_key();
+        let channel = self.device_config().channel;
+        if self
+            .baseline
+            .as_ref()
+            .is_some_and(|b| b.device == device && b.channel == channel)
+        {
+            return;
+        }
+
+        match BaselineStorage::load(Path::new(BASELINE_DIR), &device, channel) {
+            Ok(Some(profile)) => {
+                self.status_message = format!(
+                    "Loaded baseline from {}",
+                    profile.recorded_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+                );
+                self.apply_baseline(profile);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to load baseline: {}", e),
         }
     }
 
-    fn handle_stop(&mut self) {
-        if let Some(ref mut client) = self.esp_client {
-            match client.stop_collection() {
-                Ok(_) => {
-                    self.state.is_collecting = false;
-                    self.status_message = "Data collection stopped".to_string();
-                    tracing::info!("Stopped data collection");
-                }
-                Err(e) => {
-                    self.status_message = format!("Failed to stop: {}", e);
-                    tracing::error!("Failed to stop: {}", e);
-                }
+    fn apply_baseline(&mut self, profile: BaselineProfile) {
+        self.detector.set_baseline(profile.motion);
+        self.state.presence = self.detector.state();
+        self.baseline = Some(profile);
+    }
+
+    /// Scales both thresholds together, so exit stays below enter. A step that would take
+    /// exit under 1.0, the empty-room baseline score, is refused.
+    fn scale_detector_thresholds(&mut self, factor: f32) {
+        let config = &mut self.detector.config;
+        if config.exit_threshold * factor < 1.0 {
+            self.status_message = format!(
+                "Detector thresholds at their minimum: enter {:.2} / exit {:.2}",
+                config.enter_threshold, config.exit_threshold
+            );
+            return;
+        }
+        config.enter_threshold *= factor;
+        config.exit_threshold *= factor;
+        self.status_message = format!(
+            "Detector thresholds: enter {:.2} / exit {:.2}",
+            config.enter_threshold, config.exit_threshold
+        );
+    }
+
+    fn handle_labelling(&mut self) {
+        self.labelling = !self.labelling;
+        if self.labelling {
+            self.state.current_tab = LABELS_TAB;
+            self.status_message = "Labelling: 1-9 start/stop an activity, 0 ends it, 'l' to leave".to_string();
+        } else {
+            self.labels.close(chrono::Utc::now());
+            self.status_message = format!("Labelling off ({} intervals)", self.labels.intervals.len());
+        }
+    }
+
+    fn handle_label_key(&mut self, key: char) {
+        let now = chrono::Utc::now();
+        if key == '0' {
+            self.status_message = match self.labels.close(now) {
+                Some(interval) => format!("Ended '{}' ({:.1} s)", interval.label, interval.duration_s()),
+                None => "No activity in progress".to_string(),
+            };
+            return;
+        }
+
+        let Some(label) = activity_for_key(key) else {
+            return;
+        };
+        if !self.state.is_collecting {
+            self.status_message = "Start collection ('s') before labelling".to_string();
+            return;
+        }
+        self.labels.toggle(label, now);
+        self.status_message = match self.labels.active() {
+            Some((active, _)) => format!("Labelling '{}'", active),
+            None => format!("Ended '{}'", label),
+        };
+    }
+
+    /// Asks the selected device to report its firmware and radio state; the reply
+    /// shows up in the Config panel.
+    fn handle_query(&mut self) {
+        let Some(device) = self.devices.selected() else {
+            self.status_message = "No device selected".to_string();
+            return;
+        };
+        self.status_message = match self.executor.execute(&self.devices, &Target::Selected, DeviceCommand::Query) {
+            Ok(_) => format!("Querying {}...", device.id),
+            Err(e) => format!("Query failed: {}", e),
+        };
+    }
+
+    /// `capture <plan>` schedules timed captures; `capture stop` cancels the schedule,
+    /// saving a capture that is running.
+    fn handle_capture(&mut self, args: &str) {
+        if args.trim() == "stop" {
+            let Some(schedule) = self.capture.take() else {
+                self.status_message = "No capture scheduled".to_string();
+                return;
+            };
+            if schedule.first_measurement().is_some() {
+                self.handle_stop();
+                let start = std::mem::take(&mut self.capture_start);
+                self.save_recording(&start, &format!("capture_{}", schedule.completed() + 1));
+            } else {
+                self.status_message = format!("Capture cancelled after {} run(s)", schedule.completed());
             }
+            return;
+        }
+        match CapturePlan::parse(args, chrono::Utc::now()) {
+            Ok(plan) => {
+                self.capture = Some(CaptureSchedule::new(plan));
+                self.status_message = "Capture scheduled".to_string();
+            }
+            Err(e) => self.status_message = format!("Capture failed: {}", e),
         }
     }
 
-    fn collect_data(&mut self) {
-        if self.state.is_collecting {
-            if let Some(ref mut client) = self.esp_client {
-                match client.read_measurement() {
-                    Ok(Some(measurement)) => {
-                        self.state.measurements.push(measurement);
-                        self.status_message = format!("Collected {} measurements", self.state.measurements.len());
-                    }
-                    Ok(None) => {
-                        // No data available yet
-                    }
-                    Err(e) => {
-                        tracing::warn!("Failed to read measurement: {}", e);
+    /// Starts, stops and saves captures as the schedule comes due.
+    fn update_capture(&mut self) {
+        let now = chrono::Utc::now();
+        let measurements = self.devices.received();
+        let Some(schedule) = self.capture.as_mut() else {
+            self.state.capture = None;
+            return;
+        };
+        let action = schedule.update(now, measurements);
+        self.state.capture = Some(schedule.countdown(now, measurements));
+        if schedule.is_done() {
+            self.capture = None;
+        }
+        match action {
+            Some(CaptureAction::Start) => {
+                self.capture_start = self.devices.positions();
+                self.handle_start();
+            }
+            Some(CaptureAction::StopAndSave { index }) => {
+                self.handle_stop();
+                let start = std::mem::take(&mut self.capture_start);
+                self.save_recording(&start, &format!("capture_{}", index));
+            }
+            None => {}
+        }
+    }
+
+    fn survey_running(&self) -> bool {
+        self.survey.as_ref().is_some_and(|run| !run.survey.is_finished())
+    }
+
+    /// Starts surveying the selected device's channels; `args` as for
+    /// [`SurveyPlan::parse`].
+    fn handle_survey(&mut self, args: &str) {
+        let Some(device) = self.devices.selected().filter(|d| d.is_connected()) else {
+            self.status_message = "Select a connected device to survey".to_string();
+            return;
+        };
+        let plan = match SurveyPlan::parse(args) {
+            Ok(plan) => plan,
+            Err(e) => {
+                self.status_message = format!("Survey failed: {}", e);
+                return;
+            }
+        };
+        self.status_message = format!("Surveying {} channel(s) on {}...", plan.channels.len(), device.id);
+        self.survey = Some(SurveyRun {
+            survey: Survey::new(plan),
+            device: device.id.clone(),
+            original: device.config.clone(),
+            was_collecting: device.is_collecting(),
+        });
+        self.state.current_tab = SURVEY_TAB;
+        self.update_survey(&[]);
+    }
+
+    /// Feeds the surveyed device's new frames to the survey and retunes it as the
+    /// survey steps through its channels.
+    fn update_survey(&mut self, received: &[(usize, CsiMeasurement)]) {
+        let now = chrono::Utc::now();
+        let Some(run) = self.survey.as_mut().filter(|run| !run.survey.is_finished()) else { return };
+        let devices = self.devices.devices();
+        for (index, measurement) in received {
+            if devices.get(*index).is_some_and(|d| d.id == run.device) {
+                run.survey.record(measurement, now);
+            }
+        }
+        let first = run.survey.current_channel().is_none();
+        let target = Target::Device(run.device.clone());
+        let result = match run.survey.update(now) {
+            Some(SurveyAction::Tune(channel)) => {
+                let config = DeviceConfig { channel, ..run.original.clone() };
+                self.executor.execute(&self.devices, &target, DeviceCommand::Configure(config)).and_then(|_| {
+                    if first && !run.was_collecting {
+                        self.executor.execute(&self.devices, &target, DeviceCommand::StartCollection)?;
                     }
+                    Ok(())
+                })
+            }
+            Some(SurveyAction::Finished) => {
+                self.finish_survey();
+                return;
+            }
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            self.status_message = format!("Survey failed: {}", e);
+            self.finish_survey();
+        }
+    }
+
+    /// Ends the survey, early when it is still running, puts the device back on its
+    /// channel and saves the per-channel summary.
+    fn finish_survey(&mut self) {
+        let Some(run) = self.survey.as_mut() else { return };
+        let cancelled = !run.survey.is_finished();
+        if cancelled {
+            run.survey.cancel(chrono::Utc::now());
+        }
+        let target = Target::Device(run.device.clone());
+        let mut result = self.executor.execute(&self.devices, &target, DeviceCommand::Configure(run.original.clone()));
+        if !run.was_collecting {
+            result = result.and_then(|_| self.executor.execute(&self.devices, &target, DeviceCommand::StopCollection));
+        }
+        if let Err(e) = result {
+            tracing::warn!("Failed to restore {} after survey: {}", run.device, e);
+        }
+
+        let path = PathBuf::from(format!("survey_{}.csv", chrono::Local::now().format("%Y%m%d_%H%M%S")));
+        let best = run.survey.best().map_or("none".to_string(), |s| s.channel.to_string());
+        self.status_message = match CsvStorage::save_survey(&run.survey.results, &path) {
+            Ok(_) => format!(
+                "Survey {} on {}: best channel {}, saved to {}",
+                if cancelled { "cancelled" } else { "done" },
+                run.device,
+                best,
+                path.display()
+            ),
+            Err(e) => format!("Failed to save survey: {}", e),
+        };
+    }
+
+    fn handle_save_recording(&mut self) {
+        self.save_recording(&[], "recording");
+    }
+
+    /// Saves the merged recording of all devices from their `positions` on (everything
+    /// held when empty) as RRD and CSV, time-aligned across devices, with the label
+    /// intervals and session metadata alongside.
+    fn save_recording(&mut self, positions: &[usize], prefix: &str) {
+        let measurements = self.devices.merged_since(positions);
+        if measurements.is_empty() {
+            self.status_message = "Nothing to save yet".to_string();
+            return;
+        }
+
+        let base = PathBuf::from(format!("{}_{}", prefix, chrono::Local::now().format("%Y%m%d_%H%M%S")));
+        let rrd = base.with_extension("rrd");
+        let intervals = self.labels.snapshot(chrono::Utc::now());
+        let recording = align::align(&measurements);
+        let result = RrdStorage::save(&recording.measurements, &rrd)
+            .and_then(|_| CsvStorage::save(&recording.measurements, &base.with_extension("csv")))
+            .and_then(|_| LabelStorage::save(&intervals, &rrd))
+            .and_then(|_| SessionStorage::save(&self.session_devices(), &rrd));
+
+        match result {
+            Ok(_) => {
+                self.status_message = format!(
+                    "Saved {} measurements from {} device(s) and {} labels to {}.{{rrd,csv}}",
+                    recording.measurements.len(),
+                    recording.devices.len(),
+                    intervals.len(),
+                    base.display()
+                )
+            }
+            Err(e) => {
+                self.status_message = format!("Save failed: {}", e);
+                tracing::error!("Failed to save recording: {}", e);
+            }
+        }
+    }
+
+    fn session_devices(&self) -> Vec<SessionDevice> {
+        self.devices
+            .devices()
+            .iter()
+            .map(SessionDevice::from_handle)
+            .collect()
+    }
+
+    /// Starts streaming every device's measurements to `recording_<time>.parquet`, or
+    /// finishes the running stream.
+    fn handle_stream_recording(&mut self) {
+        if self.devices.recording().is_some() {
+            self.status_message = match self.devices.stop_recording() {
+                Ok(Some((path, count))) => format!("Recorded {} measurements to {}", count, path.display()),
+                Ok(None) => "Not recording".to_string(),
+                Err(e) => format!("Recording failed: {}", e),
+            };
+            return;
+        }
+        let path = PathBuf::from(format!("recording_{}.parquet", chrono::Local::now().format("%Y%m%d_%H%M%S")));
+        self.status_message = match self.devices.start_recording(&path) {
+            Ok(()) => format!("Recording to {}", path.display()),
+            Err(e) => format!("Recording failed: {}", e),
+        };
+    }
+
+    fn handle_export_dataset(&mut self) {
+        let intervals = self.labels.snapshot(chrono::Utc::now());
+        if intervals.is_empty() {
+            self.status_message = "No labels yet - press 'l' and mark activities with 1-9".to_string();
+            return;
+        }
+
+        let dir = PathBuf::from(format!("dataset_{}", chrono::Local::now().format("%Y%m%d_%H%M%S")));
+        match DatasetExporter::export(self.devices.selected_measurements(), &intervals, &self.feature_config(), &dir) {
+            Ok(summary) => {
+                self.status_message = format!(
+                    "Exported {} windows ({}) to {}",
+                    summary.windows,
+                    summary.classes.join(", "),
+                    dir.display()
+                )
+            }
+            Err(e) => {
+                self.status_message = format!("Dataset export failed: {}", e);
+                tracing::error!("Dataset export failed: {}", e);
+            }
+        }
+    }
+
+    /// Exports the component time series and the subcarrier ranking of the PCA window.
+    fn handle_export_pca(&mut self) {
+        let Some((times, scores)) = self.pca.component_series() else {
+            self.status_message = format!("Not enough data for PCA (need {} measurements)", PCA_REFRESH);
+            return;
+        };
+
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let path = PathBuf::from(format!("pca_{}.csv", stamp));
+        let ranking = PathBuf::from(format!("pca_{}_subcarriers.csv", stamp));
+        let result = CsvStorage::save_components(&times, &scores, &path)
+            .and_then(|_| CsvStorage::save_subcarrier_ranking(&self.pca.rank_subcarriers(), &ranking));
+        match result {
+            Ok(_) => self.status_message = format!("PCA exported to {} and {}", path.display(), ranking.display()),
+            Err(e) => {
+                self.status_message = format!("PCA export failed: {}", e);
+                tracing::error!("PCA export failed: {}", e);
+            }
+        }
+    }
+
+    fn feature_config(&self) -> FeatureConfig {
+        FeatureConfig {
+            sample_rate_hz: 1000.0 / self.device_config().collection_interval_ms.max(1) as f32,
+            ..Default::default()
+        }
+    }
+
+    /// Reloads the activity model, training a k-NN model from `training/` when no
+    /// saved model exists.
+    fn handle_model(&mut self) {
+        let model_dir = Path::new(MODEL_DIR);
+        let loaded = match classification::load_model(model_dir) {
+            Ok(Some(model)) => Ok(model),
+            Ok(None) => classification::train_knn(Path::new(TRAINING_DIR), model_dir, &self.feature_config())
+                .map(|model| Box::new(model) as Box<dyn Classifier>),
+            Err(e) => Err(e),
+        };
+
+        match loaded {
+            Ok(model) => {
+                self.status_message = format!("{} activity model: {}", model.name(), model.labels().join(", "));
+                self.classifier = Some(model);
+                self.activity = None;
+            }
+            Err(e) => {
+                self.status_message = format!("No activity model: {}", e);
+                tracing::error!("Failed to load or train activity model: {}", e);
+            }
+        }
+    }
+
+    /// Classifies the latest window once per hop; `received` frames arrived since the last call.
+    fn update_activity(&mut self, received: usize) {
+        let Some(ref classifier) = self.classifier else {
+            return;
+        };
+        let config = self.feature_config();
+        let measurements = self.devices.selected_measurements();
+        if measurements.len() < config.window_len() || !crossed(self.selected_total(), received, config.hop_len()) {
+            return;
+        }
+
+        let window = &measurements[measurements.len() - config.window_len()..];
+        match FeatureExtractor::extract(window, &config).and_then(|f| classifier.predict(&f)) {
+            Ok(prediction) => self.activity = Some(prediction),
+            Err(e) => tracing::warn!("Activity classification failed: {}", e),
+        }
+    }
+
+    /// Shows failed commands, and completed palette commands, in the status bar.
+    fn report_outcomes(&mut self) {
+        for outcome in self.devices.take_outcomes() {
+            match outcome.result {
+                Err(e) => self.status_message = format!("{}: {} failed: {}", outcome.device, outcome.command, e),
+                Ok(_) if !matches!(outcome.command, "configure" | "start" | "stop") => {
+                    self.status_message = format!("{}: {} done", outcome.device, outcome.command);
                 }
+                Ok(_) => {}
             }
         }
     }
 
+    /// Merges new frames from all devices into the recording and runs the analysis
+    /// stages on the selected device's frames.
+    fn collect_data(&mut self) {
+        let received = self.devices.poll();
+        self.state.is_connected = self.devices.any_connected();
+        self.state.is_collecting = self.devices.any_collecting();
+        self.report_outcomes();
+        self.update_capture();
+        self.update_survey(&received);
+        if received.is_empty() {
+            return;
+        }
+
+        let selected = self.devices.selected_index();
+        let mut new_selected = 0;
+        for (_, measurement) in received.iter().filter(|(index, _)| *index == selected) {
+            self.pca.push(measurement);
+            new_selected += 1;
+        }
+        self.state.received = self.devices.received();
+        if crossed(self.state.received, received.len(), ALIGNMENT_REFRESH) {
+            self.refresh_alignment();
+        }
+        self.status_message = format!(
+            "Collected {} measurements from {} device(s)",
+            self.state.received,
+            self.devices.devices().len()
+        );
+        if let Some((path, count)) = self.devices.recording() {
+            self.status_message += &format!(", {} recorded to {}", count, path.display());
+        }
+        if new_selected == 0 {
+            return;
+        }
+
+        if crossed(self.selected_total(), new_selected, PCA_REFRESH) {
+            self.pca.refresh();
+        }
+        if let Some(event) = self.detector.update(self.devices.selected_measurements()) {
+            self.status_message = format!(
+                "{} at {} (score {:.2})",
+                event.state.label(),
+                event.timestamp.format("%H:%M:%S"),
+                event.score
+            );
+        }
+        self.state.presence = self.detector.state();
+        self.update_calibration();
+        self.update_activity(new_selected);
+    }
+
     pub fn ui(&self, frame: &mut Frame) {
         let main_layout = crate::ui::components::create_main_layout(frame.area());
 
@@ -163,18 +949,38 @@ impl App {
         self.draw_status_bar(frame, main_layout[2]);
     }
 
+    /// Device list with the selected device's config underneath.
     fn draw_left_panel(&self, frame: &mut Frame, area: Rect) {
         use ratatui::widgets::{Block, Borders, Paragraph};
 
-        let config = &self.state.device_config;
-        let info = format!(
-            "Device Config
+        let mut info = "Devices (Up/Down)\n\n".to_string();
+        if self.devices.devices().is_empty() {
+            info.push_str("None - 'c' connect, 'd' demo\n");
+        }
+        for (index, device) in self.devices.devices().iter().enumerate() {
+            info.push_str(&format!(
+                "{} {} [{}] {}\n",
+                if index == self.devices.selected_index() { ">" } else { " " },
+                device.id,
+                device.status.label(),
+                device.measurements.total()
+            ));
+        }
+
+        let config = self.device_config();
+        info.push_str(&format!(
+            "
+Device Config
 
 Port: {}
 Baud: {}
 Channel: {}
 BW: {} MHz
 Interval: {} ms
+Wi-Fi: {}
+CSI: {}
+Traffic: {}
+CSI rate: {:.1} / {:.1} Hz
 
 Status:
 {}
@@ -182,9 +988,43 @@ Status:
 Measurements: {}",
             config.port, config.baud_rate, config.channel, 
             config.bandwidth, config.collection_interval_ms,
-            if self.state.is_connected { "Connected" } else { "Disconnected" },
-            self.state.measurements.len()
-        );
+            match config.wifi.mode {
+                WifiMode::Sniffer => "sniffer".to_string(),
+                WifiMode::Station => format!("station '{}'", config.wifi.sta_ssid),
+                WifiMode::AccessPoint => format!("ap '{}'", config.wifi.ap_ssid),
+                WifiMode::AccessPointStation => format!("ap '{}' + station '{}'", config.wifi.ap_ssid, config.wifi.sta_ssid),
+            },
+            match config.csi.enabled() {
+                enabled if enabled.is_empty() => "off".to_string(),
+                enabled => enabled.join(", "),
+            },
+            config.traffic.label(),
+            self.devices.selected().map_or(0.0, |d| d.rate_hz()),
+            config.requested_rate_hz(),
+            match self.devices.selected() {
+                Some(device) => match device.status {
+                    DeviceStatus::Error(ref e) => e.clone(),
+                    ref status => status.label().to_string(),
+                },
+                None => "Disconnected".to_string(),
+            },
+            self.devices.selected_measurements().len()
+        ));
+
+        match self.devices.selected().and_then(|d| d.info.as_ref()) {
+            Some(device_info) => info.push_str(&format!(
+                "\n\nFirmware ('i' refresh)\n\nVersion: {}\nChip: {}\nMAC: {}\nWi-Fi: {} ch {}\nCSI: {}\nUptime: {}s",
+                device_info.firmware_version,
+                device_info.chip,
+                device_info.mac,
+                device_info.wifi_mode,
+                device_info.channel,
+                if device_info.csi_flags.is_empty() { "-".to_string() } else { device_info.csi_flags.join(", ") },
+                device_info.uptime_us / 1_000_000
+            )),
+            None if self.devices.selected().is_some() => info.push_str("\n\nFirmware: unknown ('i' query)"),
+            None => {}
+        }
 
         let widget = Paragraph::new(info)
             .block(Block::default().borders(Borders::ALL).title("Config"));
@@ -195,7 +1035,25 @@ Measurements: {}",
     fn draw_right_panel(&self, frame: &mut Frame, area: Rect) {
         use ratatui::widgets::{Block, Borders, Paragraph};
 
-        let content = if self.state.measurements.is_empty() {
+        let measurements = self.devices.selected_measurements();
+        if let Some(form) = &self.wifi_form {
+            let widget = Paragraph::new(form.text()).block(Block::default().borders(Borders::ALL).title("Wi-Fi & Traffic"));
+            frame.render_widget(widget, area);
+            return;
+        }
+        if let Some(form) = &self.csi_form {
+            let widget = Paragraph::new(form.text()).block(Block::default().borders(Borders::ALL).title("CSI Flags"));
+            frame.render_widget(widget, area);
+            return;
+        }
+
+        let content = if self.state.current_tab == DEVICES_TAB {
+            self.devices_text()
+        } else if self.state.current_tab == DIAGNOSTICS_TAB {
+            self.diagnostics_text(area)
+        } else if self.state.current_tab == SURVEY_TAB {
+            self.survey_text()
+        } else if measurements.is_empty() {
             match self.state.current_tab {
                 0 => "2D Magnitude Plot
 
@@ -207,12 +1065,37 @@ No data collected yet.
 Press 's' to start collection.".to_string(),
                 2 => "Heatmap
 
+No data collected yet.
+Press 's' to start collection.".to_string(),
+                3 => "Spectrogram
+
+No data collected yet.
+Press 's' to start collection.".to_string(),
+                4 => "Motion Detection
+
+No data collected yet.
+Press 's' to start collection.".to_string(),
+                5 => "Breathing Rate
+
+No data collected yet.
+Press 's' to start collection.".to_string(),
+                6 => "Baseline
+
+No data collected yet.
+Press 's' to start collection, then 'b' to calibrate.".to_string(),
+                7 => "Activity
+
+No data collected yet.
+Press 's' to start collection.".to_string(),
+                LABELS_TAB => self.labels_text(),
+                9 => "PCA
+
 No data collected yet.
 Press 's' to start collection.".to_string(),
                 _ => "Plot View".to_string(),
             }
         } else {
-            let latest = &self.state.measurements[self.state.measurements.len() - 1];
+            let latest = &measurements[measurements.len() - 1];
             match self.state.current_tab {
                 0 => {
                     let magnitudes: Vec<f32> = latest.subcarrier_data.iter().map(|c| c.magnitude()).collect();
@@ -249,8 +1132,15 @@ Noise Floor: {} dBm",
 Total Measurements: {}
 Latest: {}
 Collection Rate: ~10 Hz", 
-                    self.state.measurements.len(), 
+                    measurements.len(), 
                     latest.timestamp.format("%H:%M:%S")),
+                3 => self.spectrogram_text(area),
+                4 => self.detection_text(area),
+                5 => self.vitals_text(),
+                6 => self.baseline_text(),
+                7 => self.activity_text(),
+                LABELS_TAB => self.labels_text(),
+                9 => self.pca_text(area),
                 _ => "Plot".to_string(),
             }
         };
@@ -261,6 +1151,16 @@ Collection Rate: ~10 Hz",
                     0 => "Magnitude",
                     1 => "Phase",
                     2 => "Heatmap",
+                    3 => "Spectrogram",
+                    4 => "Detection",
+                    5 => "Vitals",
+                    6 => "Baseline",
+                    7 => "Activity",
+                    LABELS_TAB => "Labels",
+                    DEVICES_TAB => "Devices",
+                    DIAGNOSTICS_TAB => "Diagnostics",
+                    SURVEY_TAB => "Survey",
+                    9 => "PCA",
                     _ => "Visualization"
                 }
             ));
@@ -268,10 +1168,479 @@ Collection Rate: ~10 Hz",
         frame.render_widget(widget, area);
     }
 
+    fn spectrogram_text(&self, area: Rect) -> String {
+        let config = self.spectrogram_config();
+        let series = match Resampler::resample(self.spectrogram_window(), config.sample_rate_hz) {
+            Ok(series) => series,
+            Err(e) => return format!("Spectrogram\n\nError: {}", e),
+        };
+        let spectrogram = match SpectrogramProcessor::stft(&series.amplitudes().averaged(), &config) {
+            Ok(spectrogram) => spectrogram,
+            Err(e) => return format!("Spectrogram\n\nError: {}", e),
+        };
+
+        let mut text = format!(
+            "Time-Frequency (STFT, averaged subcarriers)
+
+Window: {} samples @ {:.1} Hz | Hop: {}
+Frames: {} | Dropped: {} | Jitter: {:.1} ms | Clock: {}
+",
+            config.window_size, config.sample_rate_hz, config.hop_size, spectrogram.times.len(),
+            series.dropped_frames, series.jitter_ms,
+            series.clock.map_or("host".to_string(), |c| format!("device ({:+.0} ppm)", c.drift_ppm()))
+        );
+
+        if spectrogram.is_empty() {
+            text.push_str(&format!("\nCollecting... need {} measurements.", config.window_size));
+            return text;
+        }
+
+        if let Some(freq) = spectrogram.dominant_frequency() {
+            text.push_str(&format!("Dominant: {:.2} Hz ({:.1} /min)\n\n", freq, freq * 60.0));
+        }
+
+        // One text row per frequency bin (highest at the top), one column per frame.
+        let columns = (area.width.saturating_sub(10) as usize).max(1);
+        let rows = (area.height.saturating_sub(9) as usize).min(spectrogram.frequencies.len() - 1);
+        let frames = &spectrogram.power[spectrogram.power.len().saturating_sub(columns)..];
+        let db = |p: f32| 10.0 * p.max(1e-12).log10();
+        let (lo, hi) = frames
+            .iter()
+            .flat_map(|f| f[1..].iter().map(|&p| db(p)))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        let span = (hi - lo).max(1e-6);
+
+        for row in (0..rows).rev() {
+            // Map rows onto bins 1..=nyquist so DC is never shown.
+            let bin = 1 + row * (spectrogram.frequencies.len() - 1) / rows.max(1);
+            text.push_str(&format!("{:5.2}Hz ", spectrogram.frequencies[bin]));
+            for frame in frames {
+                let level = ((db(frame[bin]) - lo) / span * (SPECTROGRAM_SHADES.len() - 1) as f32).round();
+                text.push(SPECTROGRAM_SHADES[level as usize]);
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+
+    fn detection_text(&self, area: Rect) -> String {
+        let detector = &self.detector;
+        let config = &detector.config;
+
+        let mut text = format!(
+            "Motion Detection
+
+State: {}
+Score: {:.2} (enter >= {:.2}, exit < {:.2})
+Window: {} measurements | Hysteresis: {} up / {} down
+",
+            detector.state().label(),
+            detector.score(),
+            config.enter_threshold,
+            config.exit_threshold,
+            config.window,
+            config.enter_updates,
+            config.exit_updates
+        );
+
+        if let Some(features) = detector.features() {
+            text.push_str(&format!(
+                "Variance: {:.5} | Decorrelation: {:.5}\n",
+                features.variance, features.decorrelation
+            ));
+        }
+        match detector.baseline() {
+            Some(baseline) => text.push_str(&format!(
+                "Baseline: variance {:.5} | decorrelation {:.5}\n",
+                baseline.variance, baseline.decorrelation
+            )),
+            None => text.push_str("Baseline: none - press 'b' with the room empty\n"),
+        }
+
+        text.push_str("\nEvents:\n");
+        let rows = area.height.saturating_sub(12) as usize;
+        for event in detector.events().iter().rev().take(rows) {
+            text.push_str(&format!(
+                "  {}  {:<12} score {:.2}\n",
+                event.timestamp.format("%H:%M:%S"),
+                event.state.label(),
+                event.score
+            ));
+        }
+
+        text
+    }
+
+    fn vitals_text(&self) -> String {
+        let config = BreathingConfig {
+            sample_rate_hz: 1000.0 / self.device_config().collection_interval_ms.max(1) as f32,
+            ..Default::default()
+        };
+        // Twice the analysis window leaves room for dropped or irregular frames.
+        let needed = (2.0 * config.window_s * config.sample_rate_hz) as usize;
+        let measurements = self.devices.selected_measurements();
+        let window = &measurements[measurements.len().saturating_sub(needed)..];
+
+        let header = format!(
+            "Breathing Rate (stationary subject)
+
+Band: {:.1}-{:.1} Hz | Window: {:.0} s | Subcarriers used: {}
+",
+            config.band_low_hz, config.band_high_hz, config.window_s, config.num_subcarriers
+        );
+
+        match BreathingEstimator::estimate(window, &config) {
+            Ok(Some(estimate)) => {
+                let mut text = format!(
+                    "{}
+Rate: {:.1} breaths/min
+Confidence: {:.0}%
+Based on: {:.1} s
+Subcarriers: {:?}
+",
+                    header,
+                    estimate.breaths_per_minute,
+                    estimate.confidence * 100.0,
+                    estimate.duration_s,
+                    estimate.subcarriers
+                );
+                if estimate.confidence < 0.5 {
+                    text.push_str("\nLow confidence: subject may be moving or absent.");
+                }
+                text
+            }
+            Ok(None) => format!(
+                "{}\nCollecting... need {:.0} s of data.",
+                header, config.min_duration_s
+            ),
+            Err(e) => format!("{}\nError: {}", header, e),
+        }
+    }
+
+    fn baseline_text(&self) -> String {
+        let mut text = "Empty-Room Baseline\n\n".to_string();
+
+        if let Some(ref session) = self.calibration {
+            let recorded = self.selected_total().saturating_sub(session.start_index);
+            text.push_str(&format!(
+                "CALIBRATING - keep the room empty\n{} s remaining | {} measurements recorded\n\n",
+                session.remaining().as_secs() + 1,
+                recorded
+            ));
+        }
+
+        let Some(ref profile) = self.baseline else {
+            text.push_str(&format!(
+                "No baseline for {} on channel {}.\nPress 'b' to record {} s of the empty room.",
+                self.device_key(),
+                self.device_config().channel,
+                CALIBRATION_DURATION.as_secs()
+            ));
+            return text;
+        };
+
+        text.push_str(&format!(
+            "Device: {} | Channel: {} | BW: {} MHz
+Recorded: {} | {:.1} s | {} measurements
+Mean RSSI: {:.1} dBm | Subcarriers: {}
+",
+            profile.device,
+            profile.channel,
+            profile.bandwidth,
+            profile.recorded_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            profile.duration_s,
+            profile.num_measurements,
+            profile.mean_rssi,
+            profile.mean.len()
+        ));
+
+        let measurements = self.devices.selected_measurements();
+        let window = &measurements[measurements.len().saturating_sub(BASELINE_COMPARE_WINDOW)..];
+        match profile.compare(window) {
+            Some(cmp) => text.push_str(&format!(
+                "
+Live vs baseline (last {} measurements):
+Mean |z|: {:.2}
+Max |z|: {:.2} (subcarrier {})
+Variance ratio: {:.2}
+Covariance similarity: {:.3}",
+                window.len(),
+                cmp.mean_abs_z,
+                cmp.max_z.1.abs(),
+                cmp.max_z.0,
+                cmp.variance_ratio,
+                cmp.covariance_similarity
+            )),
+            None => text.push_str("\nNo live data matching the baseline subcarrier layout."),
+        }
+
+        text
+    }
+
+    fn activity_text(&self) -> String {
+        let config = self.feature_config();
+        let mut text = format!("Activity Classification\n\nWindow: {:.1} s | Features: {}\n", config.window_s, FEATURE_NAMES.len());
+
+        let Some(ref classifier) = self.classifier else {
+            text.push_str(&format!(
+                "\nNo model loaded. Put recordings in {}/<label>/*.rrd and press 'm' to train,\nor place a model in {}/.",
+                TRAINING_DIR, MODEL_DIR
+            ));
+            return text;
+        };
+        text.push_str(&format!("Model: {} | Labels: {}\n", classifier.name(), classifier.labels().join(", ")));
+
+        let Some(ref prediction) = self.activity else {
+            text.push_str(&format!("\nCollecting... need {} measurements.", config.window_len()));
+            return text;
+        };
+        text.push_str(&format!(
+            "\nActivity: {} ({:.0}%)\n\n",
+            prediction.label,
+            prediction.confidence * 100.0
+        ));
+        for (label, score) in classifier.labels().iter().zip(&prediction.scores) {
+            let bar = "#".repeat((score * 30.0).round() as usize);
+            text.push_str(&format!("{:<12} {:>4.0}% {}\n", label, score * 100.0, bar));
+        }
+        text
+    }
+
+    fn labels_text(&self) -> String {
+        let now = chrono::Utc::now();
+        let mut text = format!(
+            "Labelled Capture [{}]\n\n",
+            if self.labelling { "LABELLING" } else { "press 'l' to start" }
+        );
+
+        for (i, activity) in ACTIVITIES.iter().enumerate() {
+            text.push_str(&format!("{}: {}\n", i + 1, activity));
+        }
+        text.push_str("0: end current activity\n\n");
+
+        match self.labels.active() {
+            Some((label, start)) => text.push_str(&format!(
+                "Current: {} ({:.1} s)\n",
+                label,
+                (now - start).num_milliseconds() as f32 / 1000.0
+            )),
+            None => text.push_str("Current: -\n"),
+        }
+
+        text.push_str(&format!(
+            "\nIntervals: {} | 'w' saves the recording, 'n' exports labelled windows\n",
+            self.labels.intervals.len()
+        ));
+        for interval in self.labels.intervals.iter().rev().take(10) {
+            text.push_str(&format!(
+                "{} {:<10} {:.1} s\n",
+                interval.start.with_timezone(&chrono::Local).format("%H:%M:%S"),
+                interval.label,
+                interval.duration_s()
+            ));
+        }
+        text
+    }
+
+    fn pca_text(&self, area: Rect) -> String {
+        let mut text = format!("Principal Components (last {} frames)\n\n", PCA_WINDOW);
+        let (Some(pca), Some((_, scores))) = (self.pca.pca(), self.pca.component_series()) else {
+            text.push_str(&format!("Collecting... {} frames so far.", self.pca.len()));
+            return text;
+        };
+
+        let ratios = pca.explained_variance_ratio();
+        text.push_str(&format!(
+            "Explained variance: {} (total {:.0}%)\n\n",
+            ratios.iter().map(|r| format!("{:.0}%", r * 100.0)).collect::<Vec<_>>().join(" / "),
+            ratios.iter().sum::<f32>() * 100.0
+        ));
+
+        // One sparkline per component, showing the most recent frames that fit.
+        let width = (area.width as usize).saturating_sub(12).max(8);
+        for (c, column) in scores.columns().into_iter().enumerate().take(3) {
+            let recent: Vec<f32> = column.iter().skip(column.len().saturating_sub(width)).copied().collect();
+            let min = recent.iter().cloned().fold(f32::INFINITY, f32::min);
+            let max = recent.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            text.push_str(&format!("PC{} {:>5.2} {}\n", c + 1, max - min, sparkline(&recent)));
+        }
+
+        text.push_str("\nMost informative subcarriers (sensitivity / rel. variance):\n");
+        for rank in self.pca.rank_subcarriers().iter().take(8) {
+            text.push_str(&format!(
+                "  #{:<3} {:>5.1}%  {:.5}\n",
+                rank.index,
+                rank.sensitivity * 100.0,
+                rank.relative_variance
+            ));
+        }
+        text.push_str("\n'p' exports component scores and the ranking to CSV.");
+        text
+    }
+
+    /// Frames the selected device has delivered, including ones no longer held.
+    fn selected_total(&self) -> usize {
+        self.devices.selected().map_or(0, |d| d.measurements.total())
+    }
+
+    /// Re-fits each device's clock over its latest frames for the Devices tab.
+    fn refresh_alignment(&mut self) {
+        let recent: Vec<CsiMeasurement> = self
+            .devices
+            .devices()
+            .iter()
+            .flat_map(|d| d.measurements[d.measurements.len().saturating_sub(ALIGNMENT_WINDOW)..].iter().cloned())
+            .collect();
+        self.alignment = align::align(&recent).devices;
+    }
+
+    /// Per-device status side by side, with each device's clock alignment over its
+    /// recent frames.
+    fn devices_text(&self) -> String {
+        let mut text = format!(
+            "Devices\n\n{:<10} {:<13} {:>3} {:>7} {:>6} {:>5} {:>10} {:>9}\n",
+            "ID", "Status", "Ch", "Frames", "Rate", "RSSI", "Drift ppm", "Delay ms"
+        );
+        if self.devices.devices().is_empty() {
+            text.push_str("\nNo devices. 'c' adds the configured port, 'd' adds a demo device.");
+            return text;
+        }
+
+        for device in self.devices.devices() {
+            let clock = self
+                .alignment
+                .iter()
+                .find(|a| a.device_id.as_deref() == Some(device.id.as_str()));
+            let (drift, delay) = match clock {
+                Some(a) => (
+                    a.clock.map_or("-".to_string(), |c| format!("{:.1}", c.drift_ppm())),
+                    a.clock.map_or("-".to_string(), |_| format!("{:.1}", a.mean_delay_ms)),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            text.push_str(&format!(
+                "{:<10} {:<13} {:>3} {:>7} {:>6.1} {:>5} {:>10} {:>9}\n",
+                device.id,
+                device.status.label(),
+                device.config.channel,
+                device.measurements.total(),
+                device.rate_hz(),
+                device.measurements.last().map_or("-".to_string(), |m| m.rssi.to_string()),
+                drift,
+                delay
+            ));
+        }
+
+        let held: usize = self.devices.devices().iter().map(|d| d.measurements.len()).sum();
+        text.push_str(&format!(
+            "\nHeld: {} frames (latest {} per device). 'w' saves them with timestamps aligned\n\
+             across devices, 'o' streams everything to Parquet. Analysis tabs show the selected device.",
+            held, HISTORY_FRAMES
+        ));
+        text
+    }
+
+    /// Link quality of the selected device: loss, duplicates and reordering from the
+    /// firmware sequence counter, transport throughput and decode errors, with history.
+    fn diagnostics_text(&self, area: Rect) -> String {
+        let Some(device) = self.devices.selected() else {
+            return "Link Diagnostics\n\nNo device selected.".to_string();
+        };
+        let totals = &device.stats.totals;
+        let history = &device.stats.history;
+        let latest = history.back();
+        let mut text = format!(
+            "Link Diagnostics: {} ({})\n\n\
+             Rate:         {:>8.1} frames/s ({:.0}% of {:.1} requested, traffic {})\n\
+             Throughput:   {:>8.1} kB/s\n\
+             Received:     {:>8}\n\
+             Lost:         {:>8}  ({:.2}%)\n\
+             Duplicates:   {:>8}\n\
+             Reordered:    {:>8}\n\
+             Restarts:     {:>8}\n\
+             Parse errors: {:>8}\n\
+             Bytes read:   {:>8}\n\n",
+            device.id,
+            device.status.label(),
+            latest.map_or(0.0, |s| s.rate_hz),
+            latest.map_or(0.0, |s| s.rate_hz) / device.config.requested_rate_hz() * 100.0,
+            device.config.requested_rate_hz(),
+            device.config.traffic.label(),
+            latest.map_or(0.0, |s| s.bytes_per_s / 1000.0),
+            totals.received,
+            totals.lost,
+            totals.loss_pct(),
+            totals.duplicates,
+            totals.reordered,
+            totals.resets,
+            totals.parse_errors,
+            totals.bytes
+        );
+
+        let width = (area.width as usize).saturating_sub(24).max(8);
+        let recent: Vec<_> = history.iter().skip(history.len().saturating_sub(width)).collect();
+        if recent.len() < 2 {
+            text.push_str("History builds up once per second.");
+            return text;
+        }
+        let series: [(&str, Vec<f32>); 4] = [
+            ("Rate /s", recent.iter().map(|s| s.rate_hz).collect()),
+            ("Loss %", recent.iter().map(|s| s.loss_pct).collect()),
+            ("kB/s", recent.iter().map(|s| s.bytes_per_s / 1000.0).collect()),
+            ("Errors /s", recent.iter().map(|s| s.parse_errors as f32).collect()),
+        ];
+        text.push_str(&format!("Last {} s (max):\n", recent.len()));
+        for (name, values) in series {
+            let max = values.iter().cloned().fold(0.0f32, f32::max);
+            text.push_str(&format!("{:<10} {:>8.1} {}\n", name, max, sparkline(&values)));
+        }
+        text
+    }
+
+    fn survey_text(&self) -> String {
+        let Some(run) = &self.survey else {
+            return "Channel Survey\n\nPress 'v' to step the selected device through channels 1-13,\nor run ':survey channels=1,6,11 dwell=10s'.".to_string();
+        };
+        let survey = &run.survey;
+        let mut text = format!("Channel Survey of {}\n\n", run.device);
+        if let Some(channel) = survey.current_channel() {
+            let (here, total) = survey.remaining(chrono::Utc::now());
+            text.push_str(&format!(
+                "On channel {} for {} s more, {} s in total (v: cancel)\n\n",
+                channel,
+                here.num_seconds() + 1,
+                total.num_seconds() + 1
+            ));
+        } else {
+            text.push_str(&format!(
+                "{} channel(s), {} s each (v: survey again)\n\n",
+                survey.results.len(),
+                survey.plan.dwell.num_seconds()
+            ));
+        }
+        text.push_str(&survey.table());
+        if let Some(best) = survey.best() {
+            text.push_str(&format!(
+                "\n* Best so far: channel {} ({:.1} dB above the noise floor, {} transmitter(s))",
+                best.channel,
+                best.snr_db().unwrap_or(0.0),
+                best.transmitters.len()
+            ));
+        }
+        text
+    }
+
     fn draw_status_bar(&self, frame: &mut Frame, area: Rect) {
         use ratatui::widgets::Paragraph;
 
-        let status = format!("{} | q: Quit | c: Connect | d: Demo | s: Start | e: Stop | t: Tab", self.status_message);
+        if let Some(input) = &self.palette {
+            let prompt = format!(":{}_ | Enter: Run | Esc: Cancel | [@device|@all|@selected] start|stop|query|disconnect|configure channel=.. bandwidth=.. interval=.. | capture duration=..|frames=.. [repeat=..] [every=..] [delay=..|at=HH:MM] | capture stop | survey [channels=..] [dwell=..] | survey stop", input);
+            frame.render_widget(Paragraph::new(prompt).style(Style::default().fg(Color::Cyan)), area);
+            return;
+        }
+
+        let status = format!("{} | q: Quit | :: Command | c: Add device | d: Demo | r: Remove | Up/Down: Device | s: Start | e: Stop | i: Query | f: Wi-Fi/traffic | a: CSI flags | t: Tab | x: Export STFT | b: Calibrate | m: Model | l: Label | w: Save | o: Record | n: Dataset | p: Export PCA | v: Survey | +/-: Threshold", self.status_message);
         let widget = Paragraph::new(status)
             .style(Style::default().fg(Color::Yellow));
 
@@ -279,9 +1648,34 @@ Collection Rate: ~10 Hz",
     }
 }
 
-pub async fn run() -> Result<()> {
+/// One glyph per value, scaled between the series' minimum and maximum.
+fn sparkline(values: &[f32]) -> String {
+    let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let span = (max - min).max(f32::EPSILON);
+    values
+        .iter()
+        .map(|v| SPARK_GLYPHS[(((v - min) / span) * (SPARK_GLYPHS.len() - 1) as f32).round() as usize])
+        .collect()
+}
+
+/// Whether a count that just grew by `added` passed a multiple of `step`.
+fn crossed(len: usize, added: usize, step: usize) -> bool {
+    let step = step.max(1);
+    len.saturating_sub(added) / step != len / step
+}
+
+/// Runs the TUI; a seed makes demo devices deterministic, and `port` replaces the
+/// default address of devices connected with `c`.
+pub async fn run(seed: Option<u64>, port: Option<String>) -> Result<()> {
     setup_terminal()?;
     let mut app = App::new();
+    if let Some(port) = port {
+        app.state.device_config.port = port;
+    }
+    if let Some(seed) = seed {
+        app.devices.seed_demos(seed);
+    }
     let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
 
     loop {
@@ -307,3 +1701,103 @@ pub async fn run() -> Result<()> {
     cleanup_terminal()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::EspClient;
+    use ratatui::backend::TestBackend;
+
+    /// Expected screens, one per tab; written by running with `UPDATE_SNAPSHOTS=1`.
+    const SNAPSHOT_DIR: &str = "fixtures/snapshots";
+    const TAB_NAMES: [&str; NUM_TABS] = [
+        "magnitude", "phase", "heatmap", "spectrogram", "detection", "vitals", "baseline", "activity", "labels",
+        "pca", "devices", "diagnostics", "survey",
+    ];
+
+    /// An app holding 40 s from a seeded demo device, as if just collected.
+    fn seeded_app() -> App {
+        let config = DeviceConfig::default();
+        let mut client = EspClient::new(String::new(), config.baud_rate);
+        client.enable_demo_mode(&config, Some(7));
+        let info = client.query(Duration::ZERO).unwrap();
+        let frames = (0..400).map(|_| client.read_measurement().unwrap().unwrap()).collect();
+
+        let mut app = App::new();
+        // Independent of any model in the working directory.
+        app.classifier = None;
+        app.devices.add_captured(config, true, Some(info), frames);
+        app.collect_data();
+        app
+    }
+
+    fn render(app: &App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(140, 45)).unwrap();
+        terminal.draw(|frame| app.ui(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let mut screen = String::new();
+        for y in 0..buffer.area.height {
+            let line: String = (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect();
+            screen.push_str(line.trim_end());
+            screen.push('\n');
+        }
+        screen
+    }
+
+    fn run_palette(app: &mut App, line: &str) -> String {
+        app.palette = Some(line.to_string());
+        app.handle_palette_key(KeyCode::Enter);
+        app.status_message.clone()
+    }
+
+    #[test]
+    fn detector_thresholds_keep_exit_below_enter() {
+        let mut app = App::new();
+        for _ in 0..10 {
+            app.handle_key_event(KeyCode::Char('-'));
+        }
+        let config = &app.detector.config;
+        assert!(config.exit_threshold >= 1.0 && config.exit_threshold < config.enter_threshold);
+        assert!((config.enter_threshold / config.exit_threshold - 2.0).abs() < 1e-3);
+        assert!(app.status_message.contains("minimum"), "{}", app.status_message);
+
+        app.handle_key_event(KeyCode::Char('+'));
+        let config = &app.detector.config;
+        assert!(config.exit_threshold < config.enter_threshold);
+        assert!(app.status_message.starts_with("Detector thresholds: enter"));
+    }
+
+    #[test]
+    fn palette_matches_whole_command_words() {
+        let mut app = App::new();
+        assert_eq!(run_palette(&mut app, "  capture   stop "), "No capture scheduled");
+        assert_eq!(run_palette(&mut app, "survey stop"), "No survey running");
+        assert!(run_palette(&mut app, "capturestop").starts_with("Command failed"));
+        assert!(run_palette(&mut app, "surveys stop").starts_with("Command failed"));
+        assert!(app.capture.is_none());
+    }
+
+    #[test]
+    fn seeded_demo_renders_the_same_every_run() {
+        let mut app = seeded_app();
+        let mut again = seeded_app();
+        let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+        for (tab, name) in TAB_NAMES.iter().enumerate() {
+            app.state.current_tab = tab;
+            again.state.current_tab = tab;
+            let screen = render(&app);
+            assert_eq!(screen, render(&again), "{} tab differs between runs", name);
+
+            let path = Path::new(SNAPSHOT_DIR).join(format!("{:02}_{}.txt", tab, name));
+            if update {
+                std::fs::create_dir_all(SNAPSHOT_DIR).unwrap();
+                std::fs::write(&path, &screen).unwrap();
+                continue;
+            }
+            let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+                panic!("{}: {}; run with UPDATE_SNAPSHOTS=1 to create it", path.display(), e)
+            });
+            assert_eq!(screen, expected, "{} tab changed; rerun with UPDATE_SNAPSHOTS=1 if intended", name);
+        }
+    }
+}
@@ -2,20 +2,36 @@ use ratatui::{
     prelude::*,
     widgets::{Block, Borders, Paragraph},
 };
+use crate::detection::PresenceState;
 use crate::models::AppState;
 
 pub fn draw_header(frame: &mut Frame, area: Rect, state: &AppState) {
     let header = if state.is_connected {
         format!(
-            "esp-csi-tui-rs | Connected | Collecting: {} | Measurements: {}",
+            "esp-csi-tui-rs | Connected | Collecting: {} | Measurements: {} | ",
             if state.is_collecting { "Yes" } else { "No" },
-            state.measurements.len()
+            state.received
         )
     } else {
-        "esp-csi-tui-rs | Disconnected".to_string()
+        "esp-csi-tui-rs | Disconnected | ".to_string()
     };
 
-    let header_widget = Paragraph::new(header)
+    let presence_style = match state.presence {
+        PresenceState::Present => Style::default().fg(Color::White).bg(Color::Red),
+        PresenceState::Empty => Style::default().fg(Color::Black).bg(Color::Green),
+        PresenceState::Uncalibrated => Style::default().fg(Color::DarkGray),
+    };
+    let mut spans = vec![
+        Span::raw(header),
+        Span::styled(format!(" {} ", state.presence.label()), presence_style),
+    ];
+    if let Some(capture) = &state.capture {
+        spans.push(Span::raw(" | "));
+        spans.push(Span::styled(capture.clone(), Style::default().fg(Color::Black).bg(Color::Yellow)));
+    }
+    let line = Line::from(spans);
+
+    let header_widget = Paragraph::new(line)
         .block(Block::default().borders(Borders::BOTTOM))
         .style(Style::default().fg(Color::Cyan).bold());
 
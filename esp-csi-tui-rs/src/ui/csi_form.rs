@@ -0,0 +1,59 @@
+use crate::models::CsiConfig;
+use crate::ui::FormAction;
+use crossterm::event::KeyCode;
+
+/// Toggles for a device's CSI acquisition flags. Up/Down move between settings,
+/// Left/Right/Space change the selected one.
+pub struct CsiForm {
+    csi: CsiConfig,
+    focus: usize,
+}
+
+impl CsiForm {
+    pub fn new(csi: &CsiConfig) -> Self {
+        Self { csi: csi.clone(), focus: 0 }
+    }
+
+    pub fn config(&self) -> &CsiConfig {
+        &self.csi
+    }
+
+    pub fn handle_key(&mut self, key_code: KeyCode) -> FormAction {
+        let settings = self.csi.settings();
+        match key_code {
+            KeyCode::Enter => return FormAction::Submit,
+            KeyCode::Esc => return FormAction::Cancel,
+            KeyCode::Up => self.focus = (self.focus + settings.len() - 1) % settings.len(),
+            KeyCode::Down | KeyCode::Tab => self.focus = (self.focus + 1) % settings.len(),
+            KeyCode::Left | KeyCode::Right | KeyCode::Char(' ') => {
+                let (name, value) = settings[self.focus];
+                let count = CsiConfig::max_value(name) + 1;
+                let value = if key_code == KeyCode::Left { (value + count - 1) % count } else { (value + 1) % count };
+                // Names and values come from the config itself, so this cannot fail.
+                let _ = self.csi.set(name, &value.to_string());
+            }
+            _ => {}
+        }
+        FormAction::Continue
+    }
+
+    pub fn text(&self) -> String {
+        let family = match self.csi {
+            CsiConfig::Esp32 { .. } => "ESP32 / S3 / C3",
+            CsiConfig::Esp32C6 { .. } => "ESP32-C6",
+        };
+        let mut text = format!("CSI Acquisition ({})\n\n", family);
+        for (index, (name, value)) in self.csi.settings().into_iter().enumerate() {
+            let marker = if index == self.focus { ">" } else { " " };
+            let shown = if CsiConfig::max_value(name) == 1 {
+                format!("[{}]", if value == 1 { "x" } else { " " })
+            } else {
+                format!("< {} >", value)
+            };
+            text.push_str(&format!("{} {:<12} {}\n", marker, name, shown));
+        }
+        text.push_str("\nUp/Down: Setting | Left/Right/Space: Change | Enter: Apply | Esc: Cancel\n");
+        text.push_str("The flag set follows the chip reported by the last query ('i')");
+        text
+    }
+}
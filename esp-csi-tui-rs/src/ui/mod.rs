@@ -1,5 +1,14 @@
 pub mod app;
+pub mod csi_form;
 pub mod components;
 pub mod terminal;
+pub mod wifi_form;
 
 pub use terminal::{setup_terminal, cleanup_terminal};
+
+/// What a form wants after a key press.
+pub enum FormAction {
+    Continue,
+    Submit,
+    Cancel,
+}
@@ -0,0 +1,214 @@
+use crate::models::{DeviceConfig, DeviceInfo, TrafficConfig, TrafficKind, WifiConfig, WifiMode};
+use crate::ui::FormAction;
+use anyhow::{Result, anyhow};
+use crossterm::event::KeyCode;
+
+const MODE: usize = 0;
+const CHANNEL: usize = 1;
+const STA_SSID: usize = 2;
+const STA_PASSWORD: usize = 3;
+const AP_SSID: usize = 4;
+const AP_PASSWORD: usize = 5;
+const AP_HIDDEN: usize = 6;
+const MAX_CONNECTIONS: usize = 7;
+const TRAFFIC_KIND: usize = 8;
+const TRAFFIC_HZ: usize = 9;
+
+const LABELS: [&str; 10] = [
+    "Mode",
+    "Channel",
+    "Station SSID",
+    "Station password",
+    "AP SSID",
+    "AP password",
+    "Hide AP SSID",
+    "Max connections",
+    "Traffic type",
+    "Traffic rate (Hz)",
+];
+
+/// Editor for a device's Wi-Fi mode, credentials, channel and traffic generation.
+/// Up/Down move between fields, Left/Right/Space cycle the mode and traffic type and
+/// toggle the hidden flag, other fields take typed text.
+pub struct WifiForm {
+    mode: WifiMode,
+    /// Modes the device's firmware runs, from its query reply; all without one.
+    modes: Vec<WifiMode>,
+    ap_hidden: bool,
+    traffic_kind: TrafficKind,
+    /// Text of each field; unused for the mode, hidden and traffic type fields.
+    values: [String; 10],
+    focus: usize,
+}
+
+impl WifiForm {
+    pub fn new(config: &DeviceConfig, info: Option<&DeviceInfo>) -> Self {
+        let wifi = &config.wifi;
+        let modes = info.map_or_else(|| WifiMode::ALL.to_vec(), DeviceInfo::wifi_modes);
+        let mode = if modes.contains(&wifi.mode) { wifi.mode } else { modes[0] };
+        let mut values: [String; 10] = Default::default();
+        values[CHANNEL] = config.channel.to_string();
+        values[STA_SSID] = wifi.sta_ssid.clone();
+        values[STA_PASSWORD] = wifi.sta_password.clone();
+        values[AP_SSID] = wifi.ap_ssid.clone();
+        values[AP_PASSWORD] = wifi.ap_password.clone();
+        values[MAX_CONNECTIONS] = wifi.max_connections.to_string();
+        values[TRAFFIC_HZ] = config.traffic.frequency_hz.to_string();
+        let traffic_kind = Some(config.traffic.kind).filter(|kind| TrafficKind::SUPPORTED.contains(kind)).unwrap_or_default();
+        Self { mode, modes, ap_hidden: wifi.ap_hidden, traffic_kind, values, focus: MODE }
+    }
+
+    pub fn handle_key(&mut self, key_code: KeyCode) -> FormAction {
+        match key_code {
+            KeyCode::Enter => return FormAction::Submit,
+            KeyCode::Esc => return FormAction::Cancel,
+            KeyCode::Up | KeyCode::BackTab => self.move_focus(-1),
+            KeyCode::Down | KeyCode::Tab => self.move_focus(1),
+            KeyCode::Left | KeyCode::Right | KeyCode::Char(' ') if self.focus == MODE => {
+                let step = if key_code == KeyCode::Left { self.modes.len() - 1 } else { 1 };
+                let index = self.modes.iter().position(|m| *m == self.mode).unwrap_or(0);
+                self.mode = self.modes[(index + step) % self.modes.len()];
+            }
+            KeyCode::Left | KeyCode::Right | KeyCode::Char(' ') if self.focus == AP_HIDDEN => {
+                self.ap_hidden = !self.ap_hidden;
+            }
+            KeyCode::Left | KeyCode::Right | KeyCode::Char(' ') if self.focus == TRAFFIC_KIND => {
+                let kinds = TrafficKind::SUPPORTED;
+                let step = if key_code == KeyCode::Left { kinds.len() - 1 } else { 1 };
+                let index = kinds.iter().position(|k| *k == self.traffic_kind).unwrap_or(0);
+                self.traffic_kind = kinds[(index + step) % kinds.len()];
+            }
+            KeyCode::Char(c) if !matches!(self.focus, MODE | AP_HIDDEN | TRAFFIC_KIND) => self.values[self.focus].push(c),
+            KeyCode::Backspace => {
+                self.values[self.focus].pop();
+            }
+            _ => {}
+        }
+        FormAction::Continue
+    }
+
+    /// Skips fields the selected mode does not use.
+    fn move_focus(&mut self, step: isize) {
+        let count = LABELS.len() as isize;
+        let mut focus = self.focus as isize;
+        for _ in 0..count {
+            focus = (focus + step).rem_euclid(count);
+            if self.is_used(focus as usize) {
+                break;
+            }
+        }
+        self.focus = focus as usize;
+    }
+
+    fn is_used(&self, field: usize) -> bool {
+        match field {
+            STA_SSID | STA_PASSWORD => self.mode.uses_station(),
+            AP_SSID | AP_PASSWORD | AP_HIDDEN | MAX_CONNECTIONS => self.mode.uses_access_point(),
+            TRAFFIC_KIND | TRAFFIC_HZ => self.mode.uses_station(),
+            _ => true,
+        }
+    }
+
+    /// `base` with the form's channel, Wi-Fi and traffic settings, once they pass
+    /// validation. Traffic is off in modes without a station.
+    pub fn to_config(&self, base: &DeviceConfig) -> Result<DeviceConfig> {
+        let channel = self.values[CHANNEL]
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid channel '{}'", self.values[CHANNEL]))?;
+        let max_connections = self.values[MAX_CONNECTIONS]
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid max connections '{}'", self.values[MAX_CONNECTIONS]))?;
+        let frequency_hz = if self.mode.uses_station() {
+            self.values[TRAFFIC_HZ]
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Invalid traffic rate '{}'", self.values[TRAFFIC_HZ]))?
+        } else {
+            0
+        };
+        let config = DeviceConfig {
+            channel,
+            wifi: WifiConfig {
+                mode: self.mode,
+                sta_ssid: self.values[STA_SSID].clone(),
+                sta_password: self.values[STA_PASSWORD].clone(),
+                ap_ssid: self.values[AP_SSID].clone(),
+                ap_password: self.values[AP_PASSWORD].clone(),
+                ap_hidden: self.ap_hidden,
+                max_connections,
+            },
+            traffic: TrafficConfig { kind: self.traffic_kind, frequency_hz },
+            ..base.clone()
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = String::from("Wi-Fi Mode\n\n");
+        for (field, label) in LABELS.iter().enumerate() {
+            let value = match field {
+                MODE => format!("< {} >", self.mode.as_str()),
+                AP_HIDDEN => format!("[{}]", if self.ap_hidden { "x" } else { " " }),
+                TRAFFIC_KIND => format!("< {} >", self.traffic_kind.as_str()),
+                STA_PASSWORD | AP_PASSWORD => "*".repeat(self.values[field].chars().count()),
+                _ => self.values[field].clone(),
+            };
+            let marker = if field == self.focus { ">" } else { " " };
+            if self.is_used(field) {
+                text.push_str(&format!("{} {:<17} {}{}\n", marker, label, value, if field == self.focus { "_" } else { "" }));
+            } else {
+                text.push_str(&format!("  {:<17} (not used in {} mode)\n", label, self.mode.as_str()));
+            }
+        }
+        if let [mode] = self.modes[..] {
+            text.push_str(&format!("\nThe device's firmware only runs in {} mode", mode.as_str()));
+        }
+        text.push_str("\nUp/Down: Field | Left/Right/Space: Change | Enter: Apply | Esc: Cancel\n");
+        text.push_str("Passwords: empty for an open network, otherwise 8-63 characters\n");
+        text.push_str("Traffic makes the access point answer so CSI arrives at that rate; 0 Hz is off");
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn info(wifi_mode: &str) -> DeviceInfo {
+        DeviceInfo {
+            firmware_version: "0.1.0".to_string(),
+            chip: "esp32c3".to_string(),
+            mac: "24:0a:c4:00:11:22".to_string(),
+            wifi_mode: wifi_mode.to_string(),
+            channel: 6,
+            csi_flags: Vec::new(),
+            uptime_us: 0,
+            received_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn modes_follow_the_query_reply() {
+        let config = DeviceConfig::default();
+        let mut form = WifiForm::new(&config, Some(&info("sniffer")));
+        form.handle_key(KeyCode::Right);
+        assert_eq!(form.mode, WifiMode::Sniffer);
+        assert!(form.text().contains("only runs in sniffer mode"));
+
+        let mut form = WifiForm::new(&config, Some(&info("station")));
+        assert_eq!(form.mode, WifiMode::Station);
+        form.handle_key(KeyCode::Left);
+        assert_eq!(form.mode, WifiMode::Station);
+
+        let mut form = WifiForm::new(&config, Some(&info("demo")));
+        form.handle_key(KeyCode::Right);
+        assert_eq!(form.mode, WifiMode::Station);
+        let mut form = WifiForm::new(&config, None);
+        form.handle_key(KeyCode::Left);
+        assert_eq!(form.mode, WifiMode::AccessPointStation);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::dsp::filter::BandPass;
+use crate::dsp::Resampler;
+use crate::models::CsiMeasurement;
+use anyhow::Result;
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// FFT length used for the rate search; zero padding gives ~0.15 breaths/min bins at 10 Hz.
+const FFT_LEN: usize = 4096;
+/// Half-width of the band around the peak counted as "breathing" power for the confidence.
+const PEAK_HALF_WIDTH_HZ: f32 = 0.03;
+
+#[derive(Debug, Clone)]
+pub struct BreathingConfig {
+    pub sample_rate_hz: f32,
+    /// Length of the analysis window ending at the latest measurement.
+    pub window_s: f32,
+    /// Shortest window that still gives an estimate (two cycles at the low band edge).
+    pub min_duration_s: f32,
+    pub band_low_hz: f32,
+    pub band_high_hz: f32,
+    /// Number of most breathing-sensitive subcarriers combined into the estimate.
+    pub num_subcarriers: usize,
+}
+
+impl Default for BreathingConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate_hz: 10.0,
+            window_s: 30.0,
+            min_duration_s: 20.0,
+            band_low_hz: 0.1,
+            band_high_hz: 0.5,
+            num_subcarriers: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BreathingEstimate {
+    pub breaths_per_minute: f32,
+    /// Fraction of in-band power concentrated around the peak, 0..1.
+    pub confidence: f32,
+    /// Subcarriers selected as most sensitive, strongest first.
+    pub subcarriers: Vec<usize>,
+    /// Duration of data the estimate is based on.
+    pub duration_s: f32,
+}
+
+pub struct BreathingEstimator;
+
+impl BreathingEstimator {
+    /// Estimates breathing rate for a stationary subject from the last `window_s` of CSI.
+    ///
+    /// Amplitudes are resampled to a uniform rate and band-passed to the breathing band;
+    /// the subcarriers with the most relative in-band energy are sign-aligned and averaged,
+    /// and the rate is the spectral peak of that combined signal. Returns `None` until
+    /// `min_duration_s` of data is available.
+    pub fn estimate(measurements: &[CsiMeasurement], config: &BreathingConfig) -> Result<Option<BreathingEstimate>> {
+        let series = Resampler::amplitudes(measurements, config.sample_rate_hz)?;
+        let window = (config.window_s * config.sample_rate_hz) as usize;
+        let samples = &series.samples[series.samples.len().saturating_sub(window)..];
+        let duration_s = samples.len() as f32 / config.sample_rate_hz;
+        if duration_s < config.min_duration_s || series.num_subcarriers() == 0 {
+            return Ok(None);
+        }
+
+        let band = BandPass::new(config.band_low_hz, config.band_high_hz, config.sample_rate_hz)?;
+        let mut filtered: Vec<(usize, f32, Vec<f32>)> = (0..series.num_subcarriers())
+            .map(|sc| {
+                let raw: Vec<f32> = samples.iter().map(|s| s[sc]).collect();
+                let mean = raw.iter().sum::<f32>() / raw.len() as f32;
+                let signal = band.filtfilt(&raw);
+                let energy = signal.iter().map(|x| x * x).sum::<f32>() / mean.powi(2).max(f32::EPSILON);
+                (sc, energy, signal)
+            })
+            .collect();
+        filtered.sort_by(|a, b| b.1.total_cmp(&a.1));
+        filtered.truncate(config.num_subcarriers.max(1));
+
+        // Breathing moves some subcarriers up and others down; flip those that are
+        // anti-correlated with the strongest one before averaging.
+        let reference = filtered[0].2.clone();
+        let mut combined = vec![0.0f32; reference.len()];
+        for (_, _, signal) in &filtered {
+            let dot: f32 = signal.iter().zip(&reference).map(|(a, b)| a * b).sum();
+            let sign = if dot < 0.0 { -1.0 } else { 1.0 };
+            for (acc, x) in combined.iter_mut().zip(signal) {
+                *acc += sign * x;
+            }
+        }
+
+        let Some((frequency_hz, confidence)) = spectral_peak(&combined, config) else {
+            return Ok(None);
+        };
+
+        Ok(Some(BreathingEstimate {
+            breaths_per_minute: frequency_hz * 60.0,
+            confidence,
+            subcarriers: filtered.iter().map(|(sc, _, _)| *sc).collect(),
+            duration_s,
+        }))
+    }
+}
+
+/// Returns the in-band peak frequency (parabolically interpolated) and the share of
+/// in-band power within `PEAK_HALF_WIDTH_HZ` of it.
+fn spectral_peak(signal: &[f32], config: &BreathingConfig) -> Option<(f32, f32)> {
+    let n = FFT_LEN.max(signal.len().next_power_of_two());
+    let mut buffer: Vec<Complex<f32>> = signal.iter().map(|&x| Complex::new(x, 0.0)).collect();
+    buffer.resize(n, Complex::new(0.0, 0.0));
+    FftPlanner::<f32>::new().plan_fft_forward(n).process(&mut buffer);
+
+    let resolution = config.sample_rate_hz / n as f32;
+    let low = (config.band_low_hz / resolution).ceil() as usize;
+    let high = ((config.band_high_hz / resolution).floor() as usize).min(n / 2 - 1);
+    let power: Vec<f32> = buffer[..=high + 1].iter().map(|c| c.norm_sqr()).collect();
+
+    let peak = (low..=high).max_by(|&a, &b| power[a].total_cmp(&power[b]))?;
+    let total: f32 = power[low..=high].iter().sum();
+    if total <= f32::EPSILON {
+        return None;
+    }
+
+    let (left, centre, right) = (power[peak - 1], power[peak], power[peak + 1]);
+    let curvature = left - 2.0 * centre + right;
+    let offset = if curvature.abs() > f32::EPSILON { 0.5 * (left - right) / curvature } else { 0.0 };
+    let frequency = (peak as f32 + offset.clamp(-0.5, 0.5)) * resolution;
+
+    let half_width = (PEAK_HALF_WIDTH_HZ / resolution).round() as usize;
+    let near: f32 = power[peak.saturating_sub(half_width).max(low)..=(peak + half_width).min(high)]
+        .iter()
+        .sum();
+
+    Some((frequency, (near / total).clamp(0.0, 1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{self, Scenario, Scene};
+    use chrono::Utc;
+
+    fn synthetic_capture(breathing_bpm: Option<f32>, seconds: f32, rate_hz: f32) -> Vec<CsiMeasurement> {
+        let scene = breathing_bpm.map_or(Scene::EmptyRoom, |bpm| Scene::Breathing { bpm: bpm as f64 });
+        synthetic::capture(Scenario::new(scene).with_seed(7), Utc::now(), seconds as f64, rate_hz as f64)
+    }
+
+    #[test]
+    fn recovers_known_breathing_rate() {
+        let config = BreathingConfig::default();
+        for bpm in [9.0, 15.0, 24.0] {
+            let capture = synthetic_capture(Some(bpm), 40.0, config.sample_rate_hz);
+            let estimate = BreathingEstimator::estimate(&capture, &config).unwrap().unwrap();
+            assert!(
+                (estimate.breaths_per_minute - bpm).abs() < 1.0,
+                "expected {} bpm, estimated {:.2}",
+                bpm,
+                estimate.breaths_per_minute
+            );
+            assert!(estimate.confidence > 0.5, "confidence {:.2} at {} bpm", estimate.confidence, bpm);
+        }
+    }
+
+    #[test]
+    fn low_confidence_without_breathing() {
+        let config = BreathingConfig::default();
+        let capture = synthetic_capture(None, 40.0, config.sample_rate_hz);
+        let estimate = BreathingEstimator::estimate(&capture, &config).unwrap().unwrap();
+        assert!(estimate.confidence < 0.5, "confidence {:.2} on noise", estimate.confidence);
+    }
+
+    #[test]
+    fn needs_minimum_duration() {
+        let config = BreathingConfig::default();
+        let capture = synthetic_capture(Some(15.0), 10.0, config.sample_rate_hz);
+        assert!(BreathingEstimator::estimate(&capture, &config).unwrap().is_none());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod breathing;
+
+pub use breathing::{BreathingConfig, BreathingEstimator};
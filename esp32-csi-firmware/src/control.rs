@@ -0,0 +1,361 @@
+//! Host commands, one per line, answered with `OK` or `ERROR`:
+//!
+//! ```text
+//! AT                                        handshake
+//! AT+CSICFG=<channel>,<bandwidth>,<ms>      channel, bandwidth (20/40 MHz), frame interval
+//! AT+CSISTART / AT+CSISTOP                  resume / pause streaming
+//! AT+CSIWIFI=<mode>,<sta_ssid>,<sta_password>,<ap_ssid>,<ap_password>,<hidden>,<max_conn>
+//!                                           mode: sniffer, station, ap or ap-station; a build
+//!                                           accepts only the mode it runs (sniffer, or station
+//!                                           with `net`)
+//! AT+CSIFLAGS=lltf=<0|1>,htltf=<0|1>,stbc-htltf=<0|1>,ltf-merge=<0|1>
+//!                                           CSI captures; any subset, others unchanged
+//! AT+CSITRAFFIC=<ping|udp|null>,<hz>        trigger traffic, 0 Hz = off; needs station mode,
+//!                                           only `udp` is implemented (`net` build)
+//! AT+CSIINFO?                               +INFO:version=..,chip=..,mac=..,mode=..,channel=..,csi=..,uptime_us=..
+//! ```
+
+use crate::frame::FrameSource;
+use core::fmt::Write;
+use esp_hal::{usb_serial_jtag::UsbSerialJtag, Blocking};
+use esp_println::Printer;
+
+pub type Line = heapless::String<256>;
+pub type Reply = heapless::String<192>;
+pub type Ssid = heapless::String<32>;
+pub type Password = heapless::String<64>;
+
+/// Maximum stations the access point accepts.
+pub const MAX_AP_CONNECTIONS: u8 = 10;
+/// Highest trigger traffic rate.
+pub const MAX_TRAFFIC_HZ: u32 = 1000;
+
+/// Radio and streaming state the host can change.
+pub struct Settings {
+    pub channel: u8,
+    pub bandwidth: u8,
+    pub interval_ms: u32,
+    pub streaming: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { channel: 6, bandwidth: 20, interval_ms: 100, streaming: true }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WifiMode {
+    Sniffer,
+    Station,
+    AccessPoint,
+    AccessPointStation,
+}
+
+impl WifiMode {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sniffer" => Some(WifiMode::Sniffer),
+            "station" => Some(WifiMode::Station),
+            "ap" => Some(WifiMode::AccessPoint),
+            "ap-station" => Some(WifiMode::AccessPointStation),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WifiMode::Sniffer => "sniffer",
+            WifiMode::Station => "station",
+            WifiMode::AccessPoint => "ap",
+            WifiMode::AccessPointStation => "ap-station",
+        }
+    }
+
+    pub fn uses_station(self) -> bool {
+        matches!(self, WifiMode::Station | WifiMode::AccessPointStation)
+    }
+
+    pub fn uses_access_point(self) -> bool {
+        matches!(self, WifiMode::AccessPoint | WifiMode::AccessPointStation)
+    }
+}
+
+/// Wi-Fi mode and credentials set with `AT+CSIWIFI`. Empty station credentials
+/// leave the `net` build on its build-time network.
+#[derive(Clone)]
+pub struct WifiSettings {
+    pub mode: WifiMode,
+    pub sta_ssid: Ssid,
+    pub sta_password: Password,
+    pub ap_ssid: Ssid,
+    pub ap_password: Password,
+    pub ap_hidden: bool,
+    pub max_connections: u8,
+}
+
+impl Default for WifiSettings {
+    fn default() -> Self {
+        Self {
+            mode: if cfg!(feature = "net") { WifiMode::Station } else { WifiMode::Sniffer },
+            sta_ssid: Ssid::new(),
+            sta_password: Password::new(),
+            ap_ssid: Ssid::new(),
+            ap_password: Password::new(),
+            ap_hidden: false,
+            max_connections: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TrafficKind {
+    Ping,
+    Udp,
+    NullData,
+}
+
+/// Frames sent to make the access point answer, since CSI is only measured on
+/// received frames. Only the `net` build sends any, and only UDP.
+#[derive(Clone, Copy)]
+pub struct Traffic {
+    pub kind: TrafficKind,
+    pub frequency_hz: u32,
+}
+
+impl Default for Traffic {
+    fn default() -> Self {
+        Self { kind: TrafficKind::Ping, frequency_hz: 0 }
+    }
+}
+
+/// Training fields CSI is captured from. The C3 has the original ESP32 set; the
+/// C6's per-PPDU flags are not accepted here.
+#[derive(Clone, Copy)]
+pub struct CsiFlags {
+    pub lltf: bool,
+    pub htltf: bool,
+    pub stbc_htltf: bool,
+    pub ltf_merge: bool,
+}
+
+impl Default for CsiFlags {
+    fn default() -> Self {
+        Self { lltf: true, htltf: true, stbc_htltf: true, ltf_merge: true }
+    }
+}
+
+impl CsiFlags {
+    fn names(&self) -> [(&'static str, bool); 4] {
+        [("lltf", self.lltf), ("htltf", self.htltf), ("stbc-htltf", self.stbc_htltf), ("ltf-merge", self.ltf_merge)]
+    }
+}
+
+/// Collects command bytes into lines, dropping lines that do not fit.
+#[derive(Default)]
+pub struct LineReader {
+    line: Line,
+    overflow: bool,
+}
+
+impl LineReader {
+    /// Feeds one byte and returns the line it completes, if any.
+    pub fn push(&mut self, byte: u8) -> Option<Line> {
+        match byte {
+            b'\r' => None,
+            b'\n' => {
+                let line = core::mem::take(&mut self.line);
+                (!core::mem::take(&mut self.overflow)).then_some(line)
+            }
+            _ => {
+                self.overflow |= self.line.push(byte as char).is_err();
+                None
+            }
+        }
+    }
+}
+
+/// Settings, command input and frame output of the running firmware.
+#[derive(Default)]
+pub struct Firmware {
+    pub settings: Settings,
+    pub wifi: WifiSettings,
+    pub csi: CsiFlags,
+    pub traffic: Traffic,
+    /// Set when the host changed `wifi` and the radio has not applied it yet.
+    wifi_changed: bool,
+    reader: LineReader,
+    source: FrameSource,
+}
+
+impl Firmware {
+    /// Handles any commands waiting on the USB serial port, replying there.
+    pub fn poll_serial(&mut self, usb: &mut UsbSerialJtag<'static, Blocking>) {
+        while let Ok(byte) = usb.read_byte() {
+            if let Some(line) = self.reader.push(byte) {
+                Printer::write_bytes(self.handle(&line).as_bytes());
+            }
+        }
+    }
+
+    /// Applies one command line and returns the reply.
+    pub fn handle(&mut self, line: &str) -> Reply {
+        let mut reply = Reply::new();
+        let settings = &mut self.settings;
+        let ok = match line.trim() {
+            "AT" => true,
+            "AT+CSISTART" => {
+                settings.streaming = true;
+                true
+            }
+            "AT+CSISTOP" => {
+                settings.streaming = false;
+                true
+            }
+            "AT+CSIINFO?" => write_info(&mut reply, settings, self.wifi.mode, &self.csi).is_ok(),
+            command => {
+                if let Some(args) = command.strip_prefix("AT+CSICFG=") {
+                    configure(args, settings).is_some()
+                } else if let Some(args) = command.strip_prefix("AT+CSITRAFFIC=") {
+                    parse_traffic(args, self.wifi.mode).map(|traffic| self.traffic = traffic).is_some()
+                } else if let Some(args) = command.strip_prefix("AT+CSIFLAGS=") {
+                    parse_csi_flags(args, self.csi).map(|csi| self.csi = csi).is_some()
+                } else if let Some(wifi) = command.strip_prefix("AT+CSIWIFI=").and_then(parse_wifi) {
+                    self.wifi = wifi;
+                    self.wifi_changed = true;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        let _ = reply.push_str(if ok { "OK\r\n" } else { "ERROR\r\n" });
+        reply
+    }
+
+    /// Wi-Fi settings the host changed since the last call.
+    pub fn take_wifi_change(&mut self) -> Option<WifiSettings> {
+        core::mem::take(&mut self.wifi_changed).then(|| self.wifi.clone())
+    }
+
+    /// Time between frames.
+    pub fn frame_interval_ms(&self) -> u32 {
+        self.settings.interval_ms
+    }
+
+    /// The next CSI frame, or `None` while streaming is paused.
+    pub fn next_frame(&mut self) -> Option<&[u8]> {
+        if !self.settings.streaming {
+            return None;
+        }
+        self.source.next_frame(&self.settings)
+    }
+}
+
+fn configure(args: &str, settings: &mut Settings) -> Option<()> {
+    let mut fields = args.split(',').map(str::trim);
+    let channel: u8 = fields.next()?.parse().ok()?;
+    let bandwidth: u8 = fields.next()?.parse().ok()?;
+    let interval_ms: u32 = fields.next()?.parse().ok()?;
+    if !(1..=14).contains(&channel) || !matches!(bandwidth, 20 | 40) || interval_ms < 10 {
+        return None;
+    }
+    *settings = Settings { channel, bandwidth, interval_ms, ..*settings };
+    Some(())
+}
+
+fn parse_wifi(args: &str) -> Option<WifiSettings> {
+    let mut fields = args.split(',');
+    let mode = WifiMode::parse(fields.next()?.trim())?;
+    let sta_ssid = Ssid::try_from(fields.next()?).ok()?;
+    let sta_password = Password::try_from(fields.next()?).ok()?;
+    let ap_ssid = Ssid::try_from(fields.next()?).ok()?;
+    let ap_password = Password::try_from(fields.next()?).ok()?;
+    let ap_hidden = match fields.next()?.trim() {
+        "0" => false,
+        "1" => true,
+        _ => return None,
+    };
+    let max_connections: u8 = fields.next()?.trim().parse().ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    // Only the running build's own mode can be applied: the serial build has no radio
+    // driver and stays a sniffer, the network sink's interface can only be a station.
+    if mode != WifiSettings::default().mode {
+        return None;
+    }
+
+    let password_ok = |p: &Password| p.is_empty() || (8..=63).contains(&p.len());
+    if mode.uses_station() && (sta_ssid.is_empty() || !password_ok(&sta_password)) {
+        return None;
+    }
+    if mode.uses_access_point()
+        && (ap_ssid.is_empty() || !password_ok(&ap_password) || !(1..=MAX_AP_CONNECTIONS).contains(&max_connections))
+    {
+        return None;
+    }
+    Some(WifiSettings { mode, sta_ssid, sta_password, ap_ssid, ap_password, ap_hidden, max_connections })
+}
+
+fn parse_traffic(args: &str, mode: WifiMode) -> Option<Traffic> {
+    let (kind, frequency_hz) = args.split_once(',')?;
+    let kind = match kind.trim() {
+        "ping" => TrafficKind::Ping,
+        "udp" => TrafficKind::Udp,
+        "null" => TrafficKind::NullData,
+        _ => return None,
+    };
+    let frequency_hz: u32 = frequency_hz.trim().parse().ok()?;
+    if frequency_hz > MAX_TRAFFIC_HZ || (frequency_hz > 0 && !mode.uses_station()) {
+        return None;
+    }
+    // Only the network sink sends traffic, and only UDP; ping and null-data need ICMP
+    // and raw 802.11 frames, which neither build can send.
+    if frequency_hz > 0 && !(cfg!(feature = "net") && kind == TrafficKind::Udp) {
+        return None;
+    }
+    Some(Traffic { kind, frequency_hz })
+}
+
+fn parse_csi_flags(args: &str, mut csi: CsiFlags) -> Option<CsiFlags> {
+    for setting in args.split(',') {
+        let (name, value) = setting.trim().split_once('=')?;
+        let value = match value {
+            "0" => false,
+            "1" => true,
+            _ => return None,
+        };
+        match name {
+            "lltf" => csi.lltf = value,
+            "htltf" => csi.htltf = value,
+            "stbc-htltf" => csi.stbc_htltf = value,
+            "ltf-merge" => csi.ltf_merge = value,
+            _ => return None,
+        }
+    }
+    Some(csi)
+}
+
+fn write_info(reply: &mut Reply, settings: &Settings, mode: WifiMode, csi: &CsiFlags) -> core::fmt::Result {
+    let mac = esp_hal::efuse::Efuse::get_mac_address();
+    write!(
+        reply,
+        "+INFO:version={},chip=esp32c3,mac={:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x},mode={},channel={},csi=",
+        env!("CARGO_PKG_VERSION"),
+        mac[0],
+        mac[1],
+        mac[2],
+        mac[3],
+        mac[4],
+        mac[5],
+        mode.as_str(),
+        settings.channel,
+    )?;
+    let mut separator = "";
+    for (name, _) in csi.names().into_iter().filter(|&(_, enabled)| enabled) {
+        write!(reply, "{}{}", separator, name)?;
+        separator = "|";
+    }
+    write!(reply, ",uptime_us={}\r\n", esp_hal::time::now().ticks())
+}
@@ -0,0 +1,179 @@
+//! CSI frame encoding, shared by the serial and network sinks.
+//!
+//! Text frames are `+CSI:` lines:
+//!
+//! ```text
+//! +CSI:channel,bandwidth,rssi,noise_floor,subcarriers,sequence,timestamp_us,transmitter,[i0,q0,...]\r\n
+//! ```
+//!
+//! With the `binary` feature frames are sent in the compact layout below, all integers
+//! little-endian, which 115200 baud can carry at 100 Hz:
+//!
+//! ```text
+//! sync[2]=C5 1A  version[1]=2  length[2] | sequence[4] timestamp_us[8] channel[1]
+//! bandwidth[1] rssi[1] noise_floor[1] subcarriers[2] transmitter[6] | i0 q0 i1 q1 ... (int8) | crc16[2]
+//! ```
+//!
+//! `length` counts the metadata and I/Q bytes; the CRC-16/CCITT-FALSE covers everything
+//! from the version byte to the end of the I/Q payload. The host decoder must match
+//! (`esp-csi-tui-rs/src/device/codec.rs`).
+
+#[cfg(not(feature = "binary"))]
+use core::fmt::Write;
+use crate::control::Settings;
+use esp_println::println;
+
+const NUM_SUBCARRIERS: u16 = 52;
+/// Longest text frame: header plus 52 I/Q pairs of up to four characters each.
+const FRAME_CAPACITY: usize = 1024;
+
+#[cfg(feature = "binary")]
+const SYNC: [u8; 2] = [0xC5, 0x1A];
+#[cfg(feature = "binary")]
+const FORMAT_VERSION: u8 = 2;
+#[cfg(feature = "binary")]
+const METADATA_LEN: usize = 24;
+
+/// Metadata of one captured frame.
+struct CsiHeader {
+    channel: u8,
+    bandwidth: u8,
+    rssi: i8,
+    noise_floor: i8,
+    sequence: u32,
+    timestamp_us: u64,
+    /// MAC of the station the frame came from.
+    transmitter: [u8; 6],
+}
+
+type Buffer = heapless::Vec<u8, FRAME_CAPACITY>;
+
+/// Produces the frames sent to the host, one per call.
+pub struct FrameSource {
+    sequence: u32,
+    buffer: Buffer,
+    iq: [i8; 2 * NUM_SUBCARRIERS as usize],
+}
+
+impl Default for FrameSource {
+    fn default() -> Self {
+        Self { sequence: 0, buffer: Buffer::new(), iq: [0; 2 * NUM_SUBCARRIERS as usize] }
+    }
+}
+
+impl FrameSource {
+    pub fn next_frame(&mut self, settings: &Settings) -> Option<&[u8]> {
+        let sequence = self.sequence;
+        self.sequence = sequence.wrapping_add(1);
+
+        let header = CsiHeader {
+            channel: settings.channel,
+            bandwidth: settings.bandwidth,
+            rssi: -40 - (sequence % 20) as i8,
+            noise_floor: -95,
+            sequence,
+            timestamp_us: esp_hal::time::now().ticks(),
+            transmitter: placeholder_transmitter(settings.channel, sequence),
+        };
+        for (i, iq) in self.iq.chunks_exact_mut(2).enumerate() {
+            let (real, imag) = placeholder_subcarrier(i as u32, sequence);
+            iq[0] = real;
+            iq[1] = imag;
+        }
+
+        self.buffer.clear();
+        match encode(&mut self.buffer, &header, &self.iq) {
+            Ok(()) => Some(self.buffer.as_slice()),
+            Err(()) => {
+                println!("CSI frame {} exceeds {} bytes, dropped", sequence, FRAME_CAPACITY);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "binary"))]
+fn encode(out: &mut Buffer, header: &CsiHeader, iq: &[i8]) -> Result<(), ()> {
+    let mut out = TextWriter(out);
+    write!(
+        out,
+        "+CSI:{},{},{},{},{},{},{},{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x},[",
+        header.channel,
+        header.bandwidth,
+        header.rssi,
+        header.noise_floor,
+        iq.len() / 2,
+        header.sequence,
+        header.timestamp_us,
+        header.transmitter[0],
+        header.transmitter[1],
+        header.transmitter[2],
+        header.transmitter[3],
+        header.transmitter[4],
+        header.transmitter[5]
+    )
+    .map_err(|_| ())?;
+    for (i, value) in iq.iter().enumerate() {
+        if i > 0 {
+            out.write_char(',').map_err(|_| ())?;
+        }
+        write!(out, "{}", value).map_err(|_| ())?;
+    }
+    out.write_str("]\r\n").map_err(|_| ())
+}
+
+#[cfg(feature = "binary")]
+fn encode(out: &mut Buffer, header: &CsiHeader, iq: &[i8]) -> Result<(), ()> {
+    let body_len = (METADATA_LEN + iq.len()) as u16;
+    out.extend_from_slice(&SYNC)?;
+    out.push(FORMAT_VERSION).map_err(|_| ())?;
+    out.extend_from_slice(&body_len.to_le_bytes())?;
+    out.extend_from_slice(&header.sequence.to_le_bytes())?;
+    out.extend_from_slice(&header.timestamp_us.to_le_bytes())?;
+    out.extend_from_slice(&[header.channel, header.bandwidth, header.rssi as u8, header.noise_floor as u8])?;
+    out.extend_from_slice(&((iq.len() / 2) as u16).to_le_bytes())?;
+    out.extend_from_slice(&header.transmitter)?;
+    for &value in iq {
+        out.push(value as u8).map_err(|_| ())?;
+    }
+    let crc = crc16(&out[SYNC.len()..]);
+    out.extend_from_slice(&crc.to_le_bytes())
+}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, no reflection.
+#[cfg(feature = "binary")]
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// `fmt::Write` into a byte buffer, failing when it is full.
+#[cfg(not(feature = "binary"))]
+struct TextWriter<'a>(&'a mut Buffer);
+
+#[cfg(not(feature = "binary"))]
+impl Write for TextWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.0.extend_from_slice(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
+}
+
+/// A few locally administered addresses per channel standing in for the sender the
+/// CSI callback reports.
+fn placeholder_transmitter(channel: u8, sequence: u32) -> [u8; 6] {
+    let stations = 1 + channel as u32 % 3;
+    [0x02, 0, 0, 0, channel, (sequence % stations) as u8 + 1]
+}
+
+/// Deterministic int8 I/Q pattern standing in for the Wi-Fi CSI callback buffer.
+fn placeholder_subcarrier(index: u32, sequence: u32) -> (i8, i8) {
+    let real = ((index * 5 + sequence) % 48) as i8 - 24;
+    let imag = ((index * 3 + sequence * 2) % 48) as i8 - 24;
+    (real, imag)
+}
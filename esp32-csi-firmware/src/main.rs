@@ -1,32 +1,57 @@
 #![no_std]
 #![no_main]
 
+mod control;
+mod frame;
+#[cfg(feature = "net")]
+mod net;
+
+use control::Firmware;
 use esp_backtrace as _;
 use esp_hal::{
     delay::Delay,
     prelude::*,
+    usb_serial_jtag::UsbSerialJtag,
+    Blocking,
 };
 use esp_println::println;
 
 #[esp_hal::entry]
 fn main() -> ! {
+    let peripherals = esp_hal::init(esp_hal::Config::default());
     let delay = Delay::new();
+    let mut usb = UsbSerialJtag::new(peripherals.USB_DEVICE);
 
     println!("\n\n=== ESP32-C3 CSI Firmware ===");
     println!("Firmware initialized");
-    println!("Starting CSI data transmission...\n");
 
-    let mut measurement_count = 0u32;
+    let mut firmware = Firmware::default();
+
+    #[cfg(feature = "net")]
+    net::run(
+        &mut firmware,
+        &mut usb,
+        delay,
+        peripherals.TIMG0,
+        peripherals.RNG,
+        peripherals.RADIO_CLK,
+        peripherals.WIFI,
+    );
+
+    #[cfg(not(feature = "net"))]
+    {
+        println!("Starting CSI data transmission...\n");
+        serve_serial(&mut firmware, &mut usb, delay)
+    }
+}
 
+/// Streams frames and takes commands on USB serial only, forever.
+fn serve_serial(firmware: &mut Firmware, usb: &mut UsbSerialJtag<'static, Blocking>, delay: Delay) -> ! {
     loop {
-        // Send CSI-like data over serial
-        // Format: +CSI:channel,bandwidth,rssi,noise_floor,subcarriers
-        println!(
-            "+CSI:6,20,-{},95,52",
-            40 + (measurement_count % 20)
-        );
-        
-        measurement_count += 1;
-        delay.delay_millis(100);
+        firmware.poll_serial(usb);
+        if let Some(frame) = firmware.next_frame() {
+            esp_println::Printer::write_bytes(frame);
+        }
+        delay.delay_millis(firmware.frame_interval_ms());
     }
 }
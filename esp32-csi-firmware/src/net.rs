@@ -0,0 +1,295 @@
+//! Wi-Fi sink: joins an access point and streams CSI frames to the host over
+//! UDP or TCP. Settings are baked in at build time:
+//!
+//! ```text
+//! CSI_WIFI_SSID=lab CSI_WIFI_PASSWORD=secret CSI_HOST=192.168.1.10 CSI_PORT=5555 \
+//!     CSI_PROTOCOL=udp cargo build --release --features net
+//! ```
+//!
+//! `AT+CSIWIFI` with station credentials makes the board rejoin with them. The
+//! network interface is a station, so other modes are refused in this build.
+//!
+//! `AT+CSITRAFFIC=udp,<hz>` sends a small datagram to the host's port + 1 at that rate,
+//! independent of the frame interval; the access point's acknowledgements (and the
+//! host's port-unreachable replies) are the received frames CSI is measured on.
+
+use crate::control::{Firmware, LineReader, TrafficKind};
+use core::str::FromStr;
+use embedded_io::{Read, ReadReady, Write};
+use esp_hal::{
+    delay::Delay,
+    peripherals::{RADIO_CLK, RNG, TIMG0, WIFI},
+    rng::Rng,
+    timer::timg::TimerGroup,
+    usb_serial_jtag::UsbSerialJtag,
+    Blocking,
+};
+use esp_println::{println, Printer};
+use esp_wifi::{
+    current_millis,
+    wifi::{
+        utils::create_network_interface, ClientConfiguration, Configuration, WifiController, WifiStaDevice,
+    },
+    wifi_interface::{UdpSocket, WifiStack},
+    EspWifiInitFor,
+};
+use smoltcp::{
+    iface::SocketStorage,
+    wire::{IpAddress, Ipv4Address},
+};
+
+macro_rules! env_or {
+    ($name:literal, $default:literal) => {
+        match option_env!($name) {
+            Some(value) => value,
+            None => $default,
+        }
+    };
+}
+
+const SSID: &str = env_or!("CSI_WIFI_SSID", "");
+const PASSWORD: &str = env_or!("CSI_WIFI_PASSWORD", "");
+const HOST: &str = env_or!("CSI_HOST", "192.168.1.10");
+const PORT: &str = env_or!("CSI_PORT", "5555");
+/// `udp` (default) or `tcp`.
+const PROTOCOL: &str = env_or!("CSI_PROTOCOL", "udp");
+const TRIGGER: &[u8] = b"csi-trigger";
+
+/// Connects to Wi-Fi and sends frames forever. Frames are printed on serial while the
+/// network is not up, so a misconfigured board is still usable; setup errors are
+/// reported there and leave the board streaming on serial only. Commands are accepted
+/// on serial throughout and from the host: over UDP from datagrams sent to the frame
+/// port, over TCP from the connection, with replies on the same stream as the frames.
+pub fn run(
+    firmware: &mut Firmware,
+    usb: &mut UsbSerialJtag<'static, Blocking>,
+    delay: Delay,
+    timg0: TIMG0,
+    rng: RNG,
+    radio_clk: RADIO_CLK,
+    wifi: WIFI,
+) -> ! {
+    esp_alloc::heap_allocator!(72 * 1024);
+
+    let Ok(host) = Ipv4Address::from_str(HOST).map(IpAddress::Ipv4) else {
+        println!("CSI_HOST '{}' is not an IPv4 address; streaming on serial only", HOST);
+        crate::serve_serial(firmware, usb, delay)
+    };
+    let Ok(port) = PORT.parse::<u16>() else {
+        println!("CSI_PORT '{}' is not a port number; streaming on serial only", PORT);
+        crate::serve_serial(firmware, usb, delay)
+    };
+    let Some(config) = client_configuration(SSID, PASSWORD) else {
+        println!("CSI_WIFI_SSID or CSI_WIFI_PASSWORD is too long; streaming on serial only");
+        crate::serve_serial(firmware, usb, delay)
+    };
+
+    let timg0 = TimerGroup::new(timg0);
+    let init = match esp_wifi::init(EspWifiInitFor::Wifi, timg0.timer0, Rng::new(rng), radio_clk) {
+        Ok(init) => init,
+        Err(e) => {
+            println!("Wi-Fi init failed: {:?}; streaming on serial only", e);
+            crate::serve_serial(firmware, usb, delay)
+        }
+    };
+
+    let mut socket_storage: [SocketStorage; 4] = Default::default();
+    let (iface, device, mut controller, sockets) =
+        match create_network_interface(&init, wifi, WifiStaDevice, &mut socket_storage) {
+            Ok(interface) => interface,
+            Err(e) => {
+                println!("Wi-Fi interface failed: {:?}; streaming on serial only", e);
+                crate::serve_serial(firmware, usb, delay)
+            }
+        };
+    let stack = WifiStack::new(iface, device, sockets, current_millis);
+
+    let started = controller
+        .set_configuration(&Configuration::Client(config))
+        .and_then(|_| controller.start());
+    if let Err(e) = started {
+        println!("Wi-Fi start failed: {:?}; streaming on serial only", e);
+        crate::serve_serial(firmware, usb, delay)
+    }
+    println!("Connecting to Wi-Fi '{}'...", SSID);
+    if let Err(e) = controller.connect() {
+        println!("Wi-Fi connect failed: {:?}", e);
+    }
+
+    // Serial output continues until the link is up and DHCP has assigned an address.
+    while !matches!(controller.is_connected(), Ok(true)) || !stack.is_iface_up() {
+        stack.work();
+        firmware.poll_serial(usb);
+        apply_wifi_change(firmware, &mut controller);
+        if let Some(frame) = firmware.next_frame() {
+            Printer::write_bytes(frame);
+        }
+        delay.delay_millis(firmware.frame_interval_ms());
+    }
+    println!("Wi-Fi up: {:?}", stack.get_ip_info());
+
+    println!("Sending CSI over {} to {}:{}", PROTOCOL, HOST, port);
+    let mut pacing = Pacing::default();
+
+    if PROTOCOL == "tcp" {
+        let mut rx_buffer = [0u8; 256];
+        let mut tx_buffer = [0u8; 2048];
+        let mut socket = stack.get_socket(&mut rx_buffer, &mut tx_buffer);
+        let mut reader = LineReader::default();
+        let mut received = [0u8; 128];
+        let (mut trigger_rx_meta, mut trigger_rx) = ([smoltcp::socket::udp::PacketMetadata::EMPTY; 1], [0u8; 16]);
+        let (mut trigger_tx_meta, mut trigger_tx) = ([smoltcp::socket::udp::PacketMetadata::EMPTY; 2], [0u8; 64]);
+        let mut trigger =
+            stack.get_udp_socket(&mut trigger_rx_meta, &mut trigger_rx, &mut trigger_tx_meta, &mut trigger_tx);
+        if let Err(e) = trigger.bind(port + 1) {
+            println!("Binding UDP port {} failed: {:?}; streaming on serial only", port + 1, e);
+            crate::serve_serial(firmware, usb, delay)
+        }
+        loop {
+            firmware.poll_serial(usb);
+            apply_wifi_change(firmware, &mut controller);
+            if !socket.is_connected() {
+                socket.disconnect();
+                if let Err(e) = socket.open(host, port) {
+                    println!("TCP connect to {}:{} failed: {:?}", HOST, port, e);
+                    delay.delay_millis(1000);
+                    continue;
+                }
+                reader = LineReader::default();
+            }
+            while matches!(socket.read_ready(), Ok(true)) {
+                let len = match socket.read(&mut received) {
+                    Ok(0) => break,
+                    Ok(len) => len,
+                    Err(e) => {
+                        println!("TCP receive failed: {:?}", e);
+                        break;
+                    }
+                };
+                for &byte in &received[..len] {
+                    if let Some(line) = reader.push(byte) {
+                        let reply = firmware.handle(&line);
+                        if socket.write_all(reply.as_bytes()).is_err() {
+                            println!("TCP reply failed");
+                        }
+                    }
+                }
+            }
+            if pacing.frame_due(firmware) {
+                if let Some(frame) = firmware.next_frame() {
+                    if socket.write_all(frame).and_then(|_| socket.flush()).is_err() {
+                        println!("TCP send failed, reconnecting");
+                        socket.disconnect();
+                    }
+                }
+            }
+            if pacing.traffic_due(firmware) {
+                send_traffic(&mut trigger, host, port);
+            }
+            socket.work();
+            delay.delay_millis(1);
+        }
+    } else {
+        let mut rx_meta = [smoltcp::socket::udp::PacketMetadata::EMPTY; 2];
+        let mut rx_buffer = [0u8; 256];
+        let mut tx_meta = [smoltcp::socket::udp::PacketMetadata::EMPTY; 4];
+        let mut tx_buffer = [0u8; 4096];
+        let mut socket = stack.get_udp_socket(&mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer);
+        if let Err(e) = socket.bind(port) {
+            println!("Binding UDP port {} failed: {:?}; streaming on serial only", port, e);
+            crate::serve_serial(firmware, usb, delay)
+        }
+        let mut reader = LineReader::default();
+        let mut datagram = [0u8; 128];
+        loop {
+            firmware.poll_serial(usb);
+            while let Ok((len, from, from_port)) = socket.receive(&mut datagram) {
+                for &byte in &datagram[..len] {
+                    if let Some(line) = reader.push(byte) {
+                        let reply = firmware.handle(&line);
+                        if let Err(e) = socket.send(from, from_port, reply.as_bytes()) {
+                            println!("UDP reply failed: {:?}", e);
+                        }
+                    }
+                }
+            }
+            apply_wifi_change(firmware, &mut controller);
+            if pacing.frame_due(firmware) {
+                if let Some(frame) = firmware.next_frame() {
+                    if let Err(e) = socket.send(host, port, frame) {
+                        println!("UDP send failed: {:?}", e);
+                    }
+                }
+            }
+            if pacing.traffic_due(firmware) {
+                send_traffic(&mut socket, host, port);
+            }
+            socket.work();
+            delay.delay_millis(1);
+        }
+    }
+}
+
+/// Deadlines of the next frame and trigger datagram, in microseconds of uptime. The
+/// two run at their own rates: traffic never changes the frame interval.
+#[derive(Default)]
+struct Pacing {
+    next_frame_us: u64,
+    next_traffic_us: u64,
+}
+
+impl Pacing {
+    fn frame_due(&mut self, firmware: &Firmware) -> bool {
+        due(&mut self.next_frame_us, firmware.frame_interval_ms() as u64 * 1000)
+    }
+
+    fn traffic_due(&mut self, firmware: &Firmware) -> bool {
+        match firmware.traffic.frequency_hz {
+            0 => false,
+            hz => firmware.traffic.kind == TrafficKind::Udp && due(&mut self.next_traffic_us, 1_000_000 / hz as u64),
+        }
+    }
+}
+
+/// Whether a deadline has passed, moving it one period on (or to now, after a stall).
+fn due(next_us: &mut u64, period_us: u64) -> bool {
+    let now = esp_hal::time::now().ticks();
+    if now < *next_us {
+        return false;
+    }
+    *next_us = (*next_us + period_us).max(now);
+    true
+}
+
+/// Sends one trigger datagram to the host's port + 1.
+fn send_traffic(socket: &mut UdpSocket<'_, '_, WifiStaDevice>, host: IpAddress, port: u16) {
+    if let Err(e) = socket.send(host, port + 1, TRIGGER) {
+        println!("Traffic send failed: {:?}", e);
+    }
+}
+
+/// Rejoins with station credentials the host sent with `AT+CSIWIFI`.
+fn apply_wifi_change(firmware: &mut Firmware, controller: &mut WifiController<'_>) {
+    let Some(wifi) = firmware.take_wifi_change() else { return };
+    let Some(config) = client_configuration(&wifi.sta_ssid, &wifi.sta_password) else {
+        println!("Wi-Fi credentials for '{}' do not fit; keeping the current network", wifi.sta_ssid);
+        return;
+    };
+    println!("Rejoining Wi-Fi '{}'...", wifi.sta_ssid);
+    let result = controller
+        .disconnect()
+        .and_then(|_| controller.set_configuration(&Configuration::Client(config)))
+        .and_then(|_| controller.connect());
+    if let Err(e) = result {
+        println!("Wi-Fi reconnect failed: {:?}", e);
+    }
+}
+
+/// Station configuration for `ssid`, or `None` when the credentials are too long.
+fn client_configuration(ssid: &str, password: &str) -> Option<ClientConfiguration> {
+    Some(ClientConfiguration {
+        ssid: ssid.try_into().ok()?,
+        password: password.try_into().ok()?,
+        ..Default::default()
+    })
+}